@@ -63,19 +63,30 @@ fn main() {
 
     let scene = gd.new_scene();
     let &logo = obj.get(&"rust_logo".to_string()).expect("geometry not found from import");
-    let logo_draw = gd.get_draw(logo).expect("Could not get draw binding");
+    // the logo has a child for each of its materials, every gear draws
+    // all of them in its own colour
+    let logo_geometry: Vec<Entity> = gd.child_iter(logo)
+                                       .map(|c| c.filter_map(|c| gd.get_draw(c))
+                                                 .map(|d| d.geometry)
+                                                 .collect())
+                                       .unwrap_or(Vec::new());
+    assert!(logo_geometry.len() > 0, "Could not get draw binding");
 
     let scene_logos = vec!((gd.new_object(Some(scene.to_entity())), gd.standard_graphics().materials.flat.green),
                            (gd.new_object(Some(scene.to_entity())), gd.standard_graphics().materials.flat.blue),
                            (gd.new_object(Some(scene.to_entity())), gd.standard_graphics().materials.flat.red));
 
     for (idx, &(logo, material)) in scene_logos.iter().enumerate() {
-        gd.set_draw(logo, logo_draw.geometry, material);
         gd.set_scale(logo, 1.36);
         gd.set_displacement(logo, Vector3::new((idx as f32 - 1.) * 10., 0f32, 0f32));
         gd.set_rotation(logo, Rotation3::from_euler(rad(0f32),
                                                     deg(90f32).to_rad(),
                                                     deg(90f32).to_rad()));
+        for &geometry in logo_geometry.iter() {
+            let part = gd.new_object(Some(logo));
+            gd.set_delta(part, Some(logo), Transform::identity());
+            gd.set_draw(part, geometry, material);
+        }
         gd.gears.push(logo);
     }
 
@@ -16,6 +16,7 @@
 #![feature(os)]
 #![feature(core)]
 #![feature(path)]
+#![feature(io)]
 
 extern crate cgmath;
 extern crate snowmew;
@@ -32,6 +33,7 @@ pub use snowmew::{
 };
 
 use std::str::FromStr;
+use std::old_io::fs::PathExtensions;
 use std::f32;
 
 use snowmew::common::*;
@@ -40,8 +42,8 @@ use cgmath::*;
 use core::Game;
 use graphics::light;
 use graphics::{Graphics};
-use input::{integrator, InputIntegratorState};
-use loader::Obj;
+use input::{integrator, GetIoState, InputIntegratorState, ActionMap, Binding, Button, Axis};
+use loader::{Obj, Mode};
 use position::{Positions};
 use render::{Renderable, DefaultRender, Camera};
 
@@ -65,18 +67,27 @@ fn main() {
     };
 
     let mut db = GameData::new();
-    let loader = Obj::load(&path).ok().expect("Failed to load OBJ");
+    let loader = match Obj::load_with(&path, Mode::Lenient) {
+        Ok(loader) => loader,
+        Err(e) => {
+            println!("Failed to load OBJ: {}", e);
+            return;
+        }
+    };
+    for w in loader.warnings().iter() {
+        println!("warning: {}", w);
+    }
     let objs = loader.import(&mut db);
 
     let scene = db.new_scene();
     for (_, &id) in objs.iter() {
-        match db.get_draw(id) {
-            Some(d) => {
-                let obj = db.new_object(Some(scene.to_entity()));
-                db.set_draw(obj, d.geometry, d.material);
-                db.set_scale(obj, scale);
-            }
-            None => ()
+        let draws: Vec<_> = db.child_iter(id)
+            .map(|c| c.filter_map(|c| db.get_draw(c)).collect())
+            .unwrap_or(Vec::new());
+        for d in draws.into_iter() {
+            let obj = db.new_object(Some(scene.to_entity()));
+            db.set_draw(obj, d.geometry, d.material);
+            db.set_scale(obj, scale);
         }
     }
 
@@ -102,11 +113,42 @@ fn main() {
     db.set_scene(scene);
     db.set_camera(camera_loc);
     db.new_light(light::Light::Directional(sun));
+    db.get_io_state_mut().capture_mouse = true;
 
-    let (game, gd) = integrator(Noclip, db);
+    let (game, mut gd) = integrator(Noclip, db);
+    gd.set_actions(controls(&path));
     sc.start(Box::new(DefaultRender::new()), game, gd);
 }
 
+/// Bindings saved next to the obj replace the defaults
+const CONTROLS: &'static str = "noclip-controls.json";
+
+/// The bindings, set `NOCLIP_SAVE_CONTROLS` to write the defaults next
+/// to the obj so they can be edited
+fn controls(obj: &Path) -> ActionMap {
+    let mut map = ActionMap::new();
+    map.analog("move_forward");
+    map.bind("move_forward", Binding::button(Button::KeyboardW));
+    map.bind("move_forward", Binding::button(Button::KeyboardS).scale(-1.));
+    map.bind("move_forward", Binding::axis(0, Axis::LeftY).scale(-1.));
+    map.analog("move_left");
+    map.bind("move_left", Binding::button(Button::KeyboardA));
+    map.bind("move_left", Binding::button(Button::KeyboardD).scale(-1.));
+    map.bind("move_left", Binding::axis(0, Axis::LeftX).scale(-1.));
+
+    let path = obj.with_filename(CONTROLS);
+    if path.exists() {
+        if let Err(e) = map.load_bindings(&path) {
+            println!("Failed to load {}: {}", path.display(), e);
+        }
+    } else if std::os::getenv("NOCLIP_SAVE_CONTROLS").is_some() {
+        if let Err(e) = map.save_bindings(&path) {
+            println!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+    map
+}
+
 struct Noclip;
 
 impl Game<GameData, InputIntegratorState> for Noclip {
@@ -120,7 +162,17 @@ impl Game<GameData, InputIntegratorState> for Noclip {
         let camera = Camera::new(w, h, next.position(camera_key));
         let (mut rx, ry, mut rz) = next.get_rotation(camera_key).expect("no rot").to_euler();
 
-        let (x, y) = state.mouse_delta();
+        // escape lets go of the mouse and a click captures it again
+        if state.button_pressed(Button::KeyboardEscape) {
+            next.get_io_state_mut().capture_mouse = false;
+        } else if state.button_pressed(Button::MouseLeft) {
+            next.get_io_state_mut().capture_mouse = true;
+        }
+        let (x, y) = if next.get_io_state().capture_mouse {
+            state.mouse_motion()
+        } else {
+            (0., 0.)
+        };
         rx = rx.add_a(rad((-x / 120.) as f32));
         rz = rz.add_a(rad((-y / 120.) as f32));
 
@@ -132,11 +184,9 @@ impl Game<GameData, InputIntegratorState> for Noclip {
         }
 
         let input_vec = Vector3::new(
-            if state.button_down(input::Button::KeyboardA) {0.05f32} else {0f32} +
-            if state.button_down(input::Button::KeyboardD) {-0.05f32} else {0f32},
+            state.action_value("move_left") as f32 * 0.05,
             0f32,
-            if state.button_down(input::Button::KeyboardW) {0.05f32} else {0f32} +
-            if state.button_down(input::Button::KeyboardS) {-0.05f32} else {0f32}
+            state.action_value("move_forward") as f32 * 0.05
         ).mul_s(-1f32);
 
         let head_trans = Decomposed{scale: 1f32,
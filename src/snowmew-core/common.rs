@@ -135,6 +135,12 @@ pub trait Common {
         new_key
     }
 
+    /// Create an Iterator over the direct children of an object, returns
+    /// `None` if the object has never had a child
+    fn child_iter<'a>(&'a self, oid: Entity) -> Option<StaticSetIterator<'a>> {
+        self.get_common().parent_child.get(oid).map(|c| c.iter())
+    }
+
     /// Create an Iterator that iterators over the scene supplied.
     fn scene_iter<'a>(&'a self, oid: Scene) -> StaticSetIterator<'a> {
         let Scene(oid) = oid;
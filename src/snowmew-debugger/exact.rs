@@ -0,0 +1,464 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! Json that keeps floats exact. The layout matches `json::Encoder`
+//! except that a float is written as the integer of its bits, and a
+//! map is written as a list of `[key, value]` pairs so the keys can be
+//! any type. Floats that were written as json numbers are still read.
+
+use std::collections::BTreeMap;
+use std::{mem, f32, f64};
+
+use rustc_serialize::{self, Encodable, Decodable};
+use rustc_serialize::json::Json;
+use rustc_serialize::json::DecoderError::{self, ExpectedError, MissingFieldError,
+                                          UnknownVariantError, ApplicationError};
+
+pub type DecodeResult<T> = Result<T, DecoderError>;
+
+/// Convert a value to json
+pub fn encode<T: Encodable>(value: &T) -> Json {
+    let mut e = Encoder::new();
+    // building a tree can not fail
+    let _ = value.encode(&mut e);
+    e.values.pop().unwrap_or(Json::Null)
+}
+
+/// Convert json written by `encode` back to a value
+pub fn decode<T: Decodable>(json: Json) -> DecodeResult<T> {
+    Decodable::decode(&mut Decoder { stack: vec![json] })
+}
+
+/// Builds json values, containers are built with an encoder of their own
+/// that collects their elements
+struct Encoder {
+    values: Vec<Json>,
+    keys: Vec<String>
+}
+
+impl Encoder {
+    fn new() -> Encoder {
+        Encoder {
+            values: Vec::new(),
+            keys: Vec::new()
+        }
+    }
+
+    fn put(&mut self, value: Json) -> Result<(), ()> {
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// The values written by `f`
+    fn collect<F>(f: F) -> Result<Encoder, ()> where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        let mut e = Encoder::new();
+        try!(f(&mut e));
+        Ok(e)
+    }
+}
+
+impl rustc_serialize::Encoder for Encoder {
+    type Error = ();
+
+    fn emit_nil(&mut self) -> Result<(), ()> { self.put(Json::Null) }
+
+    fn emit_usize(&mut self, v: usize) -> Result<(), ()> { self.put(Json::U64(v as u64)) }
+    fn emit_u64(&mut self, v: u64) -> Result<(), ()> { self.put(Json::U64(v)) }
+    fn emit_u32(&mut self, v: u32) -> Result<(), ()> { self.put(Json::U64(v as u64)) }
+    fn emit_u16(&mut self, v: u16) -> Result<(), ()> { self.put(Json::U64(v as u64)) }
+    fn emit_u8(&mut self, v: u8) -> Result<(), ()> { self.put(Json::U64(v as u64)) }
+    fn emit_isize(&mut self, v: isize) -> Result<(), ()> { self.put(Json::I64(v as i64)) }
+    fn emit_i64(&mut self, v: i64) -> Result<(), ()> { self.put(Json::I64(v)) }
+    fn emit_i32(&mut self, v: i32) -> Result<(), ()> { self.put(Json::I64(v as i64)) }
+    fn emit_i16(&mut self, v: i16) -> Result<(), ()> { self.put(Json::I64(v as i64)) }
+    fn emit_i8(&mut self, v: i8) -> Result<(), ()> { self.put(Json::I64(v as i64)) }
+
+    fn emit_bool(&mut self, v: bool) -> Result<(), ()> { self.put(Json::Boolean(v)) }
+
+    fn emit_f64(&mut self, v: f64) -> Result<(), ()> {
+        self.put(Json::U64(unsafe { mem::transmute::<f64, u64>(v) }))
+    }
+
+    fn emit_f32(&mut self, v: f32) -> Result<(), ()> {
+        self.put(Json::U64(unsafe { mem::transmute::<f32, u32>(v) } as u64))
+    }
+
+    fn emit_char(&mut self, v: char) -> Result<(), ()> { self.put(Json::String(v.to_string())) }
+    fn emit_str(&mut self, v: &str) -> Result<(), ()> { self.put(Json::String(v.to_string())) }
+
+    fn emit_enum<F>(&mut self, _: &str, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_enum_variant<F>(&mut self, name: &str, _: usize, len: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        if len == 0 {
+            return self.put(Json::String(name.to_string()));
+        }
+        let fields = try!(Encoder::collect(f));
+        let mut obj = BTreeMap::new();
+        obj.insert("variant".to_string(), Json::String(name.to_string()));
+        obj.insert("fields".to_string(), Json::Array(fields.values));
+        self.put(Json::Object(obj))
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self, name: &str, id: usize, len: usize, f: F)
+            -> Result<(), ()> where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_enum_variant(name, id, len, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self, _: &str, idx: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_enum_variant_arg(idx, f)
+    }
+
+    fn emit_struct<F>(&mut self, _: &str, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        let fields = try!(Encoder::collect(f));
+        let obj = fields.keys.into_iter().zip(fields.values.into_iter()).collect();
+        self.put(Json::Object(obj))
+    }
+
+    fn emit_struct_field<F>(&mut self, name: &str, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.keys.push(name.to_string());
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, len: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_arg<F>(&mut self, idx: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _: &str, len: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_struct_arg<F>(&mut self, idx: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> Result<(), ()> { self.put(Json::Null) }
+
+    fn emit_option_some<F>(&mut self, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        let items = try!(Encoder::collect(f));
+        self.put(Json::Array(items.values))
+    }
+
+    fn emit_seq_elt<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        let items = try!(Encoder::collect(f));
+        let pairs = items.values.chunks(2).map(|p| Json::Array(p.to_vec())).collect();
+        self.put(Json::Array(pairs))
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+            where F: FnOnce(&mut Encoder) -> Result<(), ()> {
+        f(self)
+    }
+}
+
+/// Reads the values of a json tree, the next value is on the top of
+/// the stack
+struct Decoder {
+    stack: Vec<Json>
+}
+
+impl Decoder {
+    fn pop(&mut self) -> Json {
+        self.stack.pop().unwrap_or(Json::Null)
+    }
+
+    fn expected(what: &str, found: &Json) -> DecoderError {
+        ExpectedError(what.to_string(), format!("{}", found))
+    }
+
+    fn unsigned(&mut self) -> DecodeResult<u64> {
+        match self.pop() {
+            Json::U64(v) => Ok(v),
+            Json::I64(v) if v >= 0 => Ok(v as u64),
+            v => Err(Decoder::expected("Number", &v))
+        }
+    }
+
+    fn signed(&mut self) -> DecodeResult<i64> {
+        match self.pop() {
+            Json::I64(v) => Ok(v),
+            Json::U64(v) => Ok(v as i64),
+            v => Err(Decoder::expected("Number", &v))
+        }
+    }
+
+    fn array(&mut self) -> DecodeResult<Vec<Json>> {
+        match self.pop() {
+            Json::Array(v) => Ok(v),
+            v => Err(Decoder::expected("Array", &v))
+        }
+    }
+
+    /// Push the items of an array so the first is read first
+    fn push_all(&mut self, items: Vec<Json>) -> usize {
+        let len = items.len();
+        self.stack.extend(items.into_iter().rev());
+        len
+    }
+}
+
+impl rustc_serialize::Decoder for Decoder {
+    type Error = DecoderError;
+
+    fn read_nil(&mut self) -> DecodeResult<()> {
+        match self.pop() {
+            Json::Null => Ok(()),
+            v => Err(Decoder::expected("Null", &v))
+        }
+    }
+
+    fn read_usize(&mut self) -> DecodeResult<usize> { self.unsigned().map(|v| v as usize) }
+    fn read_u64(&mut self) -> DecodeResult<u64> { self.unsigned() }
+    fn read_u32(&mut self) -> DecodeResult<u32> { self.unsigned().map(|v| v as u32) }
+    fn read_u16(&mut self) -> DecodeResult<u16> { self.unsigned().map(|v| v as u16) }
+    fn read_u8(&mut self) -> DecodeResult<u8> { self.unsigned().map(|v| v as u8) }
+    fn read_isize(&mut self) -> DecodeResult<isize> { self.signed().map(|v| v as isize) }
+    fn read_i64(&mut self) -> DecodeResult<i64> { self.signed() }
+    fn read_i32(&mut self) -> DecodeResult<i32> { self.signed().map(|v| v as i32) }
+    fn read_i16(&mut self) -> DecodeResult<i16> { self.signed().map(|v| v as i16) }
+    fn read_i8(&mut self) -> DecodeResult<i8> { self.signed().map(|v| v as i8) }
+
+    fn read_bool(&mut self) -> DecodeResult<bool> {
+        match self.pop() {
+            Json::Boolean(v) => Ok(v),
+            v => Err(Decoder::expected("Boolean", &v))
+        }
+    }
+
+    fn read_f64(&mut self) -> DecodeResult<f64> {
+        match self.pop() {
+            Json::U64(bits) => Ok(unsafe { mem::transmute::<u64, f64>(bits) }),
+            Json::F64(v) => Ok(v),
+            Json::Null => Ok(f64::NAN),
+            v => Err(Decoder::expected("Number", &v))
+        }
+    }
+
+    fn read_f32(&mut self) -> DecodeResult<f32> {
+        match self.pop() {
+            Json::U64(bits) if bits <= 0xFFFF_FFFF => {
+                Ok(unsafe { mem::transmute::<u32, f32>(bits as u32) })
+            }
+            Json::F64(v) => Ok(v as f32),
+            Json::Null => Ok(f32::NAN),
+            v => Err(Decoder::expected("Number", &v))
+        }
+    }
+
+    fn read_char(&mut self) -> DecodeResult<char> {
+        let s = try!(self.read_str());
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(Decoder::expected("single character string", &Json::String(s.clone())))
+        }
+    }
+
+    fn read_str(&mut self) -> DecodeResult<String> {
+        match self.pop() {
+            Json::String(v) => Ok(v),
+            v => Err(Decoder::expected("String", &v))
+        }
+    }
+
+    fn read_enum<T, F>(&mut self, _: &str, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        f(self)
+    }
+
+    fn read_enum_variant<T, F>(&mut self, names: &[&str], mut f: F) -> DecodeResult<T>
+            where F: FnMut(&mut Decoder, usize) -> DecodeResult<T> {
+        let name = match self.pop() {
+            Json::String(name) => name,
+            Json::Object(mut obj) => {
+                let fields = match obj.remove(&"fields".to_string()) {
+                    Some(Json::Array(fields)) => fields,
+                    Some(v) => return Err(Decoder::expected("Array", &v)),
+                    None => return Err(MissingFieldError("fields".to_string()))
+                };
+                self.push_all(fields);
+                match obj.remove(&"variant".to_string()) {
+                    Some(Json::String(name)) => name,
+                    Some(v) => return Err(Decoder::expected("String", &v)),
+                    None => return Err(MissingFieldError("variant".to_string()))
+                }
+            }
+            v => return Err(Decoder::expected("String or Object", &v))
+        };
+        match names.iter().position(|n| *n == &name[]) {
+            Some(idx) => f(self, idx),
+            None => Err(UnknownVariantError(name))
+        }
+    }
+
+    fn read_enum_variant_arg<T, F>(&mut self, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        f(self)
+    }
+
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> DecodeResult<T>
+            where F: FnMut(&mut Decoder, usize) -> DecodeResult<T> {
+        self.read_enum_variant(names, f)
+    }
+
+    fn read_enum_struct_variant_field<T, F>(&mut self, _: &str, idx: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        self.read_enum_variant_arg(idx, f)
+    }
+
+    fn read_struct<T, F>(&mut self, _: &str, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        let value = try!(f(self));
+        self.pop();
+        Ok(value)
+    }
+
+    fn read_struct_field<T, F>(&mut self, name: &str, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        let mut obj = match self.pop() {
+            Json::Object(obj) => obj,
+            v => return Err(Decoder::expected("Object", &v))
+        };
+        // a missing field is read as null so options can be left out
+        let field = obj.remove(&name.to_string());
+        let missing = field.is_none();
+        self.stack.push(field.unwrap_or(Json::Null));
+        let value = match f(self) {
+            Ok(v) => v,
+            Err(_) if missing => return Err(MissingFieldError(name.to_string())),
+            Err(e) => return Err(e)
+        };
+        self.stack.push(Json::Object(obj));
+        Ok(value)
+    }
+
+    fn read_tuple<T, F>(&mut self, len: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        self.read_seq(move |d, found| {
+            if found == len {
+                f(d)
+            } else {
+                Err(ApplicationError(format!("expected a tuple of {}, found {}", len, found)))
+            }
+        })
+    }
+
+    fn read_tuple_arg<T, F>(&mut self, idx: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        self.read_seq_elt(idx, f)
+    }
+
+    fn read_tuple_struct<T, F>(&mut self, _: &str, len: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        self.read_tuple(len, f)
+    }
+
+    fn read_tuple_struct_arg<T, F>(&mut self, idx: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        self.read_tuple_arg(idx, f)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> DecodeResult<T>
+            where F: FnMut(&mut Decoder, bool) -> DecodeResult<T> {
+        match self.pop() {
+            Json::Null => f(self, false),
+            v => {
+                self.stack.push(v);
+                f(self, true)
+            }
+        }
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder, usize) -> DecodeResult<T> {
+        let items = try!(self.array());
+        let len = self.push_all(items);
+        f(self, len)
+    }
+
+    fn read_seq_elt<T, F>(&mut self, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder, usize) -> DecodeResult<T> {
+        let pairs = try!(self.array());
+        let len = pairs.len();
+        let mut items = Vec::with_capacity(len * 2);
+        for pair in pairs.into_iter() {
+            match pair {
+                Json::Array(ref kv) if kv.len() == 2 => items.push_all(&kv[]),
+                v => return Err(Decoder::expected("[key, value]", &v))
+            }
+        }
+        self.push_all(items);
+        f(self, len)
+    }
+
+    fn read_map_elt_key<T, F>(&mut self, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        f(self)
+    }
+
+    fn read_map_elt_val<T, F>(&mut self, _: usize, f: F) -> DecodeResult<T>
+            where F: FnOnce(&mut Decoder) -> DecodeResult<T> {
+        f(self)
+    }
+
+    fn error(&mut self, err: &str) -> DecoderError {
+        ApplicationError(err.to_string())
+    }
+}
@@ -14,14 +14,22 @@
 
 #![feature(old_impl_check)]
 #![feature(alloc)]
+#![feature(io)]
+#![feature(path)]
 
 extern crate "snowmew-core" as core;
+extern crate "rustc-serialize" as rustc_serialize;
 
 use std::sync::Arc;
 use std::collections::{VecMap, BTreeMap};
 use std::ops::{Deref, DerefMut};
 use core::game::Game;
 
+pub use record::{Recorder, Player, PlaybackError};
+
+mod record;
+mod exact;
+
 #[derive(Clone)]
 pub struct Debugger<Game> {
     game: Game
@@ -0,0 +1,188 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::old_io::{File, BufferedReader, BufferedWriter, IoError, IoResult};
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::{json, Encodable, Decodable};
+use rustc_serialize::json::Json;
+use core::game::Game;
+
+use exact;
+
+/// Wraps a game and writes every event that is passed to it, one json
+/// value per line. Floats are written as their bits so they are read
+/// back exactly. Since `step` is a pure function of the event and the
+/// game data, feeding the events back with a `Player` into the same
+/// starting data reproduces the same run.
+pub struct Recorder<Game, W> {
+    game: Game,
+    out: W,
+    error: Option<IoError>
+}
+
+impl<Game> Recorder<Game, BufferedWriter<File>> {
+    /// Record into a new file at `path`, this replaces an existing file
+    pub fn create(game: Game, path: &Path) -> IoResult<Recorder<Game, BufferedWriter<File>>> {
+        let file = try!(File::create(path));
+        Ok(Recorder::new(game, BufferedWriter::new(file)))
+    }
+}
+
+impl<Game, W: Writer> Recorder<Game, W> {
+    pub fn new(game: Game, out: W) -> Recorder<Game, W> {
+        Recorder {
+            game: game,
+            out: out,
+            error: None
+        }
+    }
+
+    /// The first write that failed, the game keeps running but nothing
+    /// is recorded after an error
+    pub fn error(&self) -> Option<&IoError> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> IoResult<()> {
+        self.out.flush()
+    }
+
+    /// Stop recording and get back the game and the writer
+    pub fn into_inner(mut self) -> (Game, W) {
+        let _ = self.out.flush();
+        (self.game, self.out)
+    }
+}
+
+impl<GameData,
+     Event: Encodable,
+     InputGame: Game<GameData, Event>,
+     W: Writer> Game<GameData, Event> for Recorder<InputGame, W> {
+    fn step(&mut self, event: Event, gd: GameData) -> GameData {
+        if self.error.is_none() {
+            let line = format!("{}\n", exact::encode(&event));
+            if let Err(e) = self.out.write_str(&line[]) {
+                self.error = Some(e);
+            }
+        }
+        self.game.step(event, gd)
+    }
+}
+
+/// Something went wrong reading a recording
+#[derive(Debug)]
+pub enum PlaybackError {
+    Io(IoError),
+    /// the line, counted from one, that could not be decoded
+    Decode(usize, json::DecoderError)
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlaybackError::Io(ref e) => write!(f, "{}", e),
+            PlaybackError::Decode(line, ref e) => write!(f, "{}: invalid event: {:?}", line, e)
+        }
+    }
+}
+
+impl Error for PlaybackError {
+    fn description(&self) -> &str {
+        match *self {
+            PlaybackError::Io(_) => "failed to read recording",
+            PlaybackError::Decode(_, _) => "invalid event"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            PlaybackError::Io(ref e) => Some(e as &Error),
+            PlaybackError::Decode(_, _) => None
+        }
+    }
+}
+
+/// The events of a recording in the order they were recorded
+#[derive(Clone, Debug)]
+pub struct Player<Event> {
+    events: Vec<Event>,
+    position: usize
+}
+
+impl<Event: Decodable> Player<Event> {
+    pub fn open(path: &Path) -> Result<Player<Event>, PlaybackError> {
+        let file = try!(File::open(path).map_err(|e| PlaybackError::Io(e)));
+        Player::read(&mut BufferedReader::new(file))
+    }
+
+    /// Read a recording that was written by a `Recorder`, blank lines
+    /// are skipped
+    pub fn read<B: Buffer>(input: &mut B) -> Result<Player<Event>, PlaybackError> {
+        let mut events = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = try!(line.map_err(|e| PlaybackError::Io(e)));
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = Json::from_str(line.trim())
+                .map_err(|e| json::DecoderError::ParseError(e))
+                .and_then(|j| exact::decode(j));
+            match event {
+                Ok(event) => events.push(event),
+                Err(e) => return Err(PlaybackError::Decode(i + 1, e))
+            }
+        }
+        Ok(Player::new(events))
+    }
+}
+
+impl<Event> Player<Event> {
+    pub fn new(events: Vec<Event>) -> Player<Event> {
+        Player {
+            events: events,
+            position: 0
+        }
+    }
+
+    pub fn events(&self) -> &[Event] { &self.events[] }
+
+    /// The number of events that have been played
+    pub fn position(&self) -> usize { self.position }
+
+    pub fn is_finished(&self) -> bool { self.position >= self.events.len() }
+
+    /// Start from the first event again
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+}
+
+impl<Event: Clone> Iterator for Player<Event> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let event = self.events.get(self.position).map(|e| e.clone());
+        if event.is_some() {
+            self.position += 1;
+        }
+        event
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.events.len() - self.position;
+        (left, Some(left))
+    }
+}
@@ -0,0 +1,340 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! Parametric meshes that are built on demand. Every shape is centred on
+//! the origin with +y up, front faces wind counter-clockwise and texture
+//! coordinates start at the top left. Tangents follow increasing u.
+
+use std::cmp::{min, max};
+use std::collections::HashMap;
+use std::num::Float;
+use std::f32::consts::PI;
+use std::mem;
+
+use snowmew::Entity;
+use geometry::{VertexBuffer, Geometry, VertexGeoTexNorm, VertexGeoTexNormTan, generate_tangents};
+use Graphics;
+
+/// The vertices and triangles of a shape
+#[derive(Clone)]
+pub struct Mesh {
+    pub vertices: Vec<VertexGeoTexNormTan>,
+    pub indices: Vec<u32>
+}
+
+fn vertex(position: [f32; 3], texture: [f32; 2], normal: [f32; 3]) -> VertexGeoTexNorm {
+    VertexGeoTexNorm {
+        position: position,
+        texture: texture,
+        normal: normal
+    }
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len == 0. {
+        [0., 1., 0.]
+    } else {
+        [v[0] / len, v[1] / len, v[2] / len]
+    }
+}
+
+/// Connect a grid of `columns + 1` by `rows + 1` vertices into triangles.
+/// Triangles with two vertices in the same place, such as the ones at the
+/// pole of a sphere, are left out.
+fn grid(vertices: &[VertexGeoTexNorm], columns: usize, rows: usize) -> Vec<u32> {
+    let at = |column: usize, row: usize| (row * (columns + 1) + column) as u32;
+    let same = |a: u32, b: u32| vertices[a as usize].position == vertices[b as usize].position;
+    let mut out = Vec::new();
+    for row in (0..rows) {
+        for column in (0..columns) {
+            let (a, b) = (at(column, row), at(column + 1, row));
+            let (c, d) = (at(column, row + 1), at(column + 1, row + 1));
+            if !same(a, b) {
+                out.push_all(&[a, c, b]);
+            }
+            if !same(c, d) {
+                out.push_all(&[b, c, d]);
+            }
+        }
+    }
+    out
+}
+
+/// A point of the outline that is spun around the y axis
+#[derive(Copy)]
+struct Profile {
+    radius: f32,
+    y: f32,
+    /// the normal in the plane of the outline
+    normal: [f32; 2],
+    /// the texture coordinate down the outline
+    v: f32
+}
+
+/// Spin an outline around the y axis. The outline runs from the top of
+/// the shape to the bottom with the outside on its right.
+fn lathe(profile: &[Profile], segments: usize) -> (Vec<VertexGeoTexNorm>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(profile.len() * (segments + 1));
+    for p in profile.iter() {
+        for i in (0..segments + 1) {
+            let u = i as f32 / segments as f32;
+            // the seam is duplicated so the texture can wrap
+            let (s, c) = if i == segments { (0., 1.) } else { (u * 2. * PI).sin_cos() };
+            vertices.push(vertex([p.radius * s, p.y, p.radius * c],
+                                 [u, p.v],
+                                 [p.normal[0] * s, p.normal[1], p.normal[0] * c]));
+        }
+    }
+    let indices = grid(&vertices[], segments, profile.len() - 1);
+    (vertices, indices)
+}
+
+/// A flat disc at height `y` facing up or down
+fn disc(vertices: &mut Vec<VertexGeoTexNorm>, indices: &mut Vec<u32>,
+        y: f32, radius: f32, segments: usize, up: bool) {
+    let normal = if up { [0., 1., 0.] } else { [0., -1., 0.] };
+    let center = vertices.len() as u32;
+    vertices.push(vertex([0., y, 0.], [0.5, 0.5], normal));
+    for i in (0..segments) {
+        let (s, c) = (i as f32 / segments as f32 * 2. * PI).sin_cos();
+        // seen from its front the texture is not mirrored
+        let u = if up { 0.5 + 0.5 * s } else { 0.5 - 0.5 * s };
+        vertices.push(vertex([radius * s, y, radius * c], [u, 0.5 + 0.5 * c], normal));
+    }
+    for i in (0..segments as u32) {
+        let (a, b) = (center + 1 + i, center + 1 + (i + 1) % segments as u32);
+        if up {
+            indices.push_all(&[center, a, b]);
+        } else {
+            indices.push_all(&[center, b, a]);
+        }
+    }
+}
+
+impl Mesh {
+    /// Build a mesh from vertices that do not have tangents yet
+    pub fn new(vertices: Vec<VertexGeoTexNorm>, indices: Vec<u32>) -> Mesh {
+        Mesh {
+            vertices: generate_tangents(&vertices[], &indices[]),
+            indices: indices
+        }
+    }
+
+    /// A flat rectangle on the xz plane facing +y, split into
+    /// `columns` by `rows` quads
+    pub fn plane(width: f32, depth: f32, columns: usize, rows: usize) -> Mesh {
+        assert!(columns > 0 && rows > 0);
+        let mut vertices = Vec::with_capacity((columns + 1) * (rows + 1));
+        for row in (0..rows + 1) {
+            for column in (0..columns + 1) {
+                let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                vertices.push(vertex([(u - 0.5) * width, 0., (v - 0.5) * depth],
+                                     [u, v],
+                                     [0., 1., 0.]));
+            }
+        }
+        let indices = grid(&vertices[], columns, rows);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A sphere made of `segments` slices around its axis and `rings`
+    /// bands from pole to pole
+    pub fn uv_sphere(radius: f32, segments: usize, rings: usize) -> Mesh {
+        assert!(segments > 2 && rings > 1);
+        let profile: Vec<Profile> = (0..rings + 1).map(|j| {
+            let v = j as f32 / rings as f32;
+            let (s, c) = (v * PI).sin_cos();
+            // the poles are exact so that their triangles are left out
+            let s = if j == 0 || j == rings { 0. } else { s };
+            Profile { radius: radius * s, y: radius * c, normal: [s, c], v: v }
+        }).collect();
+        let (vertices, indices) = lathe(&profile[], segments);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A sphere made by splitting each triangle of an icosahedron into
+    /// four `subdivisions` times, the triangles are close to the same size
+    pub fn icosphere(radius: f32, subdivisions: usize) -> Mesh {
+        let t = (1. + 5f32.sqrt()) / 2.;
+        let mut points: Vec<[f32; 3]> = vec![
+            [-1., t, 0.], [1., t, 0.], [-1., -t, 0.], [1., -t, 0.],
+            [0., -1., t], [0., 1., t], [0., -1., -t], [0., 1., -t],
+            [t, 0., -1.], [t, 0., 1.], [-t, 0., -1.], [-t, 0., 1.]
+        ].into_iter().map(normalize).collect();
+        let mut faces: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]
+        ];
+
+        for _ in (0..subdivisions) {
+            let mut middle: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut split = |points: &mut Vec<[f32; 3]>, a: u32, b: u32| -> u32 {
+                let key = if a < b { (a, b) } else { (b, a) };
+                if let Some(&m) = middle.get(&key) {
+                    return m;
+                }
+                let (p, q) = (points[a as usize], points[b as usize]);
+                points.push(normalize([p[0] + q[0], p[1] + q[1], p[2] + q[2]]));
+                let m = points.len() as u32 - 1;
+                middle.insert(key, m);
+                m
+            };
+
+            faces = faces.iter().flat_map(|f| {
+                let ab = split(&mut points, f[0], f[1]);
+                let bc = split(&mut points, f[1], f[2]);
+                let ca = split(&mut points, f[2], f[0]);
+                vec![[f[0], ab, ca], [f[1], bc, ab], [f[2], ca, bc], [ab, bc, ca]].into_iter()
+            }).collect();
+        }
+
+        // the texture is wrapped around the sphere, so triangles that cross
+        // the seam and the vertices at the poles need their own copies
+        let uv = |p: [f32; 3]| [p[0].atan2(p[2]) / (2. * PI) + 0.5, p[1].max(-1.).min(1.).acos() / PI];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut shared: HashMap<[u32; 5], u32> = HashMap::new();
+        for f in faces.iter() {
+            let p = [points[f[0] as usize], points[f[1] as usize], points[f[2] as usize]];
+            let mut t = [uv(p[0]), uv(p[1]), uv(p[2])];
+            let right = t.iter().fold(0f32, |m, t| m.max(t[0]));
+            for t in t.iter_mut() {
+                if right - t[0] > 0.5 {
+                    t[0] += 1.;
+                }
+            }
+            for i in (0..3) {
+                if p[i][1].abs() > 0.9999 {
+                    t[i][0] = (t[(i + 1) % 3][0] + t[(i + 2) % 3][0]) / 2.;
+                }
+            }
+
+            for i in (0..3) {
+                let bits: [u32; 5] = unsafe {
+                    [mem::transmute(p[i][0]), mem::transmute(p[i][1]), mem::transmute(p[i][2]),
+                     mem::transmute(t[i][0]), mem::transmute(t[i][1])]
+                };
+                let next = vertices.len() as u32;
+                let index = *shared.entry(bits).get().unwrap_or_else(|v| v.insert(next));
+                if index == next {
+                    let q = p[i];
+                    vertices.push(vertex([q[0] * radius, q[1] * radius, q[2] * radius], t[i], q));
+                }
+                indices.push(index);
+            }
+        }
+        Mesh::new(vertices, indices)
+    }
+
+    /// A closed cylinder standing on the y axis
+    pub fn cylinder(radius: f32, height: f32, segments: usize) -> Mesh {
+        assert!(segments > 2);
+        let h = height / 2.;
+        let profile = [Profile { radius: radius, y: h, normal: [1., 0.], v: 0. },
+                       Profile { radius: radius, y: -h, normal: [1., 0.], v: 1. }];
+        let (mut vertices, mut indices) = lathe(&profile, segments);
+        disc(&mut vertices, &mut indices, h, radius, segments, true);
+        disc(&mut vertices, &mut indices, -h, radius, segments, false);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A cone with its point up and a closed base
+    pub fn cone(radius: f32, height: f32, segments: usize) -> Mesh {
+        assert!(segments > 2);
+        let h = height / 2.;
+        let n = normalize([height, radius, 0.]);
+        let profile = [Profile { radius: 0., y: h, normal: [n[0], n[1]], v: 0. },
+                       Profile { radius: radius, y: -h, normal: [n[0], n[1]], v: 1. }];
+        let (mut vertices, mut indices) = lathe(&profile, segments);
+        disc(&mut vertices, &mut indices, -h, radius, segments, false);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A ring lying on the xz plane. `radius` is the distance from the
+    /// centre to the middle of the tube.
+    pub fn torus(radius: f32, tube: f32, segments: usize, sides: usize) -> Mesh {
+        assert!(segments > 2 && sides > 2);
+        let profile: Vec<Profile> = (0..sides + 1).map(|j| {
+            let v = j as f32 / sides as f32;
+            let (s, c) = if j == sides { (0., 1.) } else { (v * 2. * PI).sin_cos() };
+            Profile { radius: radius + tube * c, y: -tube * s, normal: [c, -s], v: v }
+        }).collect();
+        let (vertices, indices) = lathe(&profile[], segments);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A cylinder with a half sphere on each end, `height` is the length of
+    /// the straight part. The texture is spread evenly along the outline.
+    pub fn capsule(radius: f32, height: f32, segments: usize, rings: usize) -> Mesh {
+        assert!(segments > 2 && rings > 0);
+        let h = height / 2.;
+        let length = PI * radius + height;
+        let mut profile = Vec::with_capacity(rings * 2 + 2);
+        for j in (0..rings + 1) {
+            let a = j as f32 / rings as f32 * PI / 2.;
+            let (s, c) = a.sin_cos();
+            let s = if j == 0 { 0. } else { s };
+            profile.push(Profile { radius: radius * s, y: h + radius * c,
+                                   normal: [s, c], v: a * radius / length });
+        }
+        for j in (0..rings + 1) {
+            let a = PI / 2. + j as f32 / rings as f32 * PI / 2.;
+            let (s, c) = a.sin_cos();
+            let s = if j == rings { 0. } else { s };
+            profile.push(Profile { radius: radius * s, y: -h + radius * c,
+                                   normal: [s, c], v: (a * radius + height) / length });
+        }
+        let (vertices, indices) = lathe(&profile[], segments);
+        Mesh::new(vertices, indices)
+    }
+
+    /// A terrain on the xz plane. `heights` holds `(columns + 1) * (rows + 1)`
+    /// samples row by row starting at -z, normals come from the slope
+    /// between neighbouring samples.
+    pub fn heightfield(width: f32, depth: f32, columns: usize, rows: usize, heights: &[f32]) -> Mesh {
+        assert!(columns > 0 && rows > 0);
+        assert_eq!(heights.len(), (columns + 1) * (rows + 1));
+        let at = |column: usize, row: usize| heights[row * (columns + 1) + column];
+        let (dx, dz) = (width / columns as f32, depth / rows as f32);
+
+        let mut vertices = Vec::with_capacity(heights.len());
+        for row in (0..rows + 1) {
+            for column in (0..columns + 1) {
+                let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                let (l, r) = (max(column, 1) - 1, min(column + 1, columns));
+                let (b, f) = (max(row, 1) - 1, min(row + 1, rows));
+                let slope_x = (at(r, row) - at(l, row)) / ((r - l) as f32 * dx);
+                let slope_z = (at(column, f) - at(column, b)) / ((f - b) as f32 * dz);
+                vertices.push(vertex([(u - 0.5) * width, at(column, row), (v - 0.5) * depth],
+                                     [u, v],
+                                     normalize([-slope_x, 1., -slope_z])));
+            }
+        }
+        let indices = grid(&vertices[], columns, rows);
+        Mesh::new(vertices, indices)
+    }
+
+    /// Store the mesh as a vertex buffer and return a geometry that draws
+    /// all of it
+    pub fn build<G: Graphics>(self, gd: &mut G) -> Entity {
+        let count = self.indices.len();
+        let vb = gd.new_vertex_buffer(
+            VertexBuffer::new_position_texture_normal_tangent(self.vertices, self.indices)
+        );
+        gd.new_geometry(Geometry::triangles(vb, 0, count))
+    }
+}
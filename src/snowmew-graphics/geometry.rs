@@ -14,6 +14,10 @@
 
 
 use std::default::Default;
+use std::num::Float;
+use std::iter::repeat;
+use cgmath::{Point, Point3, Vector, Vector3, EuclideanVector};
+use collision::sphere::Sphere;
 use snowmew::common::Entity;
 use rustc_serialize::{Encodable, Decodable, Encoder, Decoder};
 
@@ -65,6 +69,33 @@ impl Decodable for F32v2 {
     }
 }
 
+#[derive(Copy)]
+struct F32v4([f32; 4]);
+
+impl Encodable for F32v4 {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_seq(4, |s| {
+            try!(s.emit_seq_elt(0, |s| self.0[0].encode(s)));
+            try!(s.emit_seq_elt(1, |s| self.0[1].encode(s)));
+            try!(s.emit_seq_elt(2, |s| self.0[2].encode(s)));
+            try!(s.emit_seq_elt(3, |s| self.0[3].encode(s)));
+            Ok(())
+        })
+    }
+}
+
+impl Decodable for F32v4 {
+    fn decode<D: Decoder>(d: &mut D) -> Result<F32v4, D::Error> {
+        d.read_seq(|d, _| {
+            let a = try!(d.read_seq_elt(0, |d| Decodable::decode(d)));
+            let b = try!(d.read_seq_elt(1, |d| Decodable::decode(d)));
+            let c = try!(d.read_seq_elt(2, |d| Decodable::decode(d)));
+            let e = try!(d.read_seq_elt(3, |d| Decodable::decode(d)));
+            Ok(F32v4([a, b, c, e]))
+        })
+    }
+}
+
 
 #[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
 pub enum Primative {
@@ -259,7 +290,9 @@ pub struct VertexGeoTexNormTan {
     pub position: [f32; 3],
     pub texture:  [f32; 2],
     pub normal:   [f32; 3],
-    pub tangent:  [f32; 3],
+    /// xyz is the tangent, w is 1 or -1 and flips the bitangent
+    /// where the texture is mirrored
+    pub tangent:  [f32; 4],
 }
 
 impl Encodable for VertexGeoTexNormTan {
@@ -268,7 +301,7 @@ impl Encodable for VertexGeoTexNormTan {
             try!(s.emit_seq_elt(0, |s| F32v3(self.position).encode(s)));
             try!(s.emit_seq_elt(1, |s| F32v2(self.texture).encode(s)));
             try!(s.emit_seq_elt(2, |s| F32v3(self.normal).encode(s)));
-            try!(s.emit_seq_elt(3, |s| F32v3(self.tangent).encode(s)));
+            try!(s.emit_seq_elt(3, |s| F32v4(self.tangent).encode(s)));
             Ok(())
         })
     }
@@ -280,7 +313,7 @@ impl Decodable for VertexGeoTexNormTan {
             let a: F32v3 = try!(d.read_seq_elt(0, |d| Decodable::decode(d)));
             let b: F32v2 = try!(d.read_seq_elt(1, |d| Decodable::decode(d)));
             let c: F32v3 = try!(d.read_seq_elt(2, |d| Decodable::decode(d)));
-            let d: F32v3 = try!(d.read_seq_elt(3, |d| Decodable::decode(d)));
+            let d: F32v4 = try!(d.read_seq_elt(3, |d| Decodable::decode(d)));
             Ok(VertexGeoTexNormTan {
                 position: a.0,
                 texture: b.0,
@@ -383,6 +416,83 @@ pub fn to_triangles_adjacency<IDX: Eq+Clone>(index: &[IDX]) -> Vec<IDX> {
     vec
 }
 
+/// Calculate a sphere that encloses all of the supplied points
+pub fn bounding_sphere<'a, I: Iterator<Item=&'a [f32; 3]>>(points: I) -> Sphere<f32> {
+    let points: Vec<Point3<f32>> = points.map(|p| Point3::new(p[0], p[1], p[2])).collect();
+    if points.len() == 0 {
+        return Sphere::new(Point3::new(0f32, 0., 0.), 0.);
+    }
+
+    let mut min = points[0];
+    let mut max = points[0];
+    for p in points.iter() {
+        min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+        max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+    }
+
+    let center = Point3::new((min.x + max.x) / 2.,
+                             (min.y + max.y) / 2.,
+                             (min.z + max.z) / 2.);
+    let radius = points.iter()
+        .map(|p| p.sub_p(&center).length())
+        .fold(0f32, |a, b| a.max(b));
+
+    Sphere::new(center, radius)
+}
+
+/// Calculate a tangent for each vertex from the direction that the texture
+/// coordinates increase in across each triangle. The tangents are smoothed
+/// across shared vertices and made perpendicular to the normal. Vertices
+/// without usable texture coordinates get a tangent that is perpendicular
+/// to the normal. The w of each tangent is -1 where the texture is
+/// mirrored, the bitangent is `cross(normal, tangent) * w`.
+pub fn generate_tangents(vert: &[VertexGeoTexNorm], index: &[u32]) -> Vec<VertexGeoTexNormTan> {
+    let v3 = |&: a: [f32; 3]| Vector3::new(a[0], a[1], a[2]);
+    let mut acc: Vec<Vector3<f32>> = repeat(Vector3::zero()).take(vert.len()).collect();
+    let mut acc_b: Vec<Vector3<f32>> = repeat(Vector3::zero()).take(vert.len()).collect();
+
+    for tri in index.chunks(3) {
+        if tri.len() != 3 {
+            break;
+        }
+        let (a, b, c) = (&vert[tri[0] as usize], &vert[tri[1] as usize], &vert[tri[2] as usize]);
+        let e1 = v3(b.position).sub_v(&v3(a.position));
+        let e2 = v3(c.position).sub_v(&v3(a.position));
+        let (du1, dv1) = (b.texture[0] - a.texture[0], b.texture[1] - a.texture[1]);
+        let (du2, dv2) = (c.texture[0] - a.texture[0], c.texture[1] - a.texture[1]);
+
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let t = e1.mul_s(dv2).sub_v(&e2.mul_s(dv1)).div_s(det);
+        let bt = e2.mul_s(du1).sub_v(&e1.mul_s(du2)).div_s(det);
+        for &i in tri.iter() {
+            acc[i as usize].add_self_v(&t);
+            acc_b[i as usize].add_self_v(&bt);
+        }
+    }
+
+    vert.iter().zip(acc.iter().zip(acc_b.iter())).map(|(v, (t, bt))| {
+        let n = v3(v.normal);
+        // Gram-Schmidt against the normal
+        let mut t = t.sub_v(&n.mul_s(n.dot(t)));
+        if t.length2() < 1e-12 {
+            let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+            t = axis.sub_v(&n.mul_s(n.dot(&axis)));
+        }
+        let t = t.normalize();
+        let w = if n.cross(&t).dot(bt) < 0. { -1. } else { 1. };
+
+        VertexGeoTexNormTan {
+            position: v.position,
+            texture: v.texture,
+            normal: v.normal,
+            tangent: [t.x, t.y, t.z, w]
+        }
+    }).collect()
+}
+
 impl Geometry {
     pub fn triangles(vb: Entity, offset: usize, count: usize) -> Geometry {
         Geometry {
@@ -16,6 +16,7 @@
 #![crate_type = "lib"]
 #![feature(plugin)]
 #![feature(core)]
+#![feature(collections)]
 #![feature(hash)]
 
 #![plugin(gfx_macros)]
@@ -39,19 +40,23 @@ use snowmew::table::{Static, StaticIterator};
 
 pub use geometry::{Geometry, VertexBuffer};
 pub use material::Material;
-pub use texture::Texture;
+pub use pbr::{PbrMaterial, Environment};
+pub use texture::{Texture, Kind, Format, Sampler, Wrap, Filter};
 pub use light::Light;
 
 pub use light::{
     Directional,
-    Point
+    Point,
+    Spot
 };
 
 pub mod geometry;
 pub mod material;
+pub mod pbr;
 pub mod standard;
 pub mod texture;
 pub mod light;
+pub mod builder;
 
 #[derive(Clone, Default, Eq, PartialEq, PartialOrd, Hash, Debug, RustcEncodable, RustcDecodable, Copy)]
 pub struct Drawable {
@@ -79,8 +84,12 @@ pub struct GraphicsData {
     material:           Static<Material>,
     material_index:     Static<i32>,
     material_idx_last:  i32,
+    pbr_material:       Static<PbrMaterial>,
+    environment:        Option<Environment>,
     texture:            Static<Texture>,
     lights:             Static<light::Light>,
+    revision:           Static<u32>,
+    revision_last:      u32,
     standard:           Option<standard::Standard>
 }
 
@@ -92,10 +101,14 @@ impl GraphicsData {
             vertex: Static::new(),
             material: Static::new(),
             material_index: Static::new(),
+            pbr_material: Static::new(),
+            environment: None,
             texture: Static::new(),
             lights: Static::new(),
             material_idx_last: 0,
             sphere: Static::new(),
+            revision: Static::new(),
+            revision_last: 0,
             standard: None
         }
     }
@@ -120,20 +133,51 @@ pub trait Graphics: Common + Sized {
 
     fn new_vertex_buffer(&mut self, vb: VertexBuffer) -> Entity {
         let oid = self.new_object(None);
-        self.get_graphics_mut().vertex.insert(oid, vb);
+        self.set_vertex_buffer(oid, vb);
         oid
     }
 
+    /// replace the contents of a vertex buffer, geometry that uses it
+    /// should be updated to match
+    fn set_vertex_buffer(&mut self, oid: Entity, vb: VertexBuffer) {
+        self.get_graphics_mut().vertex.insert(oid, vb);
+        self.bump_revision(oid);
+    }
+
+    /// A number that changes every time a vertex buffer or texture is
+    /// replaced, renderers use it to know when to upload the data again
+    fn revision(&self, oid: Entity) -> u32 {
+        self.get_graphics().revision.get(oid).map(|&r| r).unwrap_or(0)
+    }
+
+    fn bump_revision(&mut self, oid: Entity) {
+        self.get_graphics_mut().revision_last += 1;
+        let r = self.get_graphics().revision_last;
+        self.get_graphics_mut().revision.insert(oid, r);
+    }
+
     fn geometry<'a>(&'a self, oid: Entity) -> Option<&'a Geometry> {
         self.get_graphics().geometry.get(oid)
     }
 
     fn new_geometry(&mut self, geo: Geometry) -> Entity {
         let oid = self.new_object(None);
-        self.get_graphics_mut().geometry.insert(oid, geo);
+        self.set_geometry(oid, geo);
         oid
     }
 
+    /// replace a geometry, the bounding sphere is recalculated
+    fn set_geometry(&mut self, oid: Entity, geo: Geometry) {
+        self.get_graphics_mut().geometry.insert(oid, geo);
+        let sphere = self.geometry_vertex_iter(oid).map(|iter| {
+            geometry::bounding_sphere(iter.map(|(_, p, _, _)| p))
+        });
+        match sphere {
+            Some(sphere) => { self.get_graphics_mut().sphere.insert(oid, sphere); }
+            None => { self.get_graphics_mut().sphere.remove(oid); }
+        }
+    }
+
     fn sphere(&self, geo: Entity) -> Sphere<f32> {
         match self.get_graphics().sphere.get(geo) {
             Some(s) => { s.clone() }
@@ -161,10 +205,44 @@ pub trait Graphics: Common + Sized {
         obj
     }
 
+    /// replace a material, it keeps its material index
+    fn set_material(&mut self, oid: Entity, material: Material) {
+        self.get_graphics_mut().material.insert(oid, material);
+        self.bump_revision(oid);
+    }
+
     fn material_iter<'a>(&'a self) -> StaticIterator<'a, Material> {
         self.get_graphics().material.iter()
     }
 
+    /// create a physically based material, this may be used anywhere
+    /// a `Material` is expected
+    fn new_pbr_material(&mut self, material: PbrMaterial) -> Entity {
+        let obj = self.new_object(None);
+        self.get_graphics_mut().pbr_material.insert(obj, material);
+        let idx = self.get_graphics().material_idx_last;
+        self.get_graphics_mut().material_idx_last += 1;
+        self.get_graphics_mut().material_index.insert(obj, idx);
+        obj
+    }
+
+    fn pbr_material<'a>(&'a self, oid: Entity) -> Option<&'a PbrMaterial> {
+        self.get_graphics().pbr_material.get(oid)
+    }
+
+    fn pbr_material_iter<'a>(&'a self) -> StaticIterator<'a, PbrMaterial> {
+        self.get_graphics().pbr_material.iter()
+    }
+
+    /// set the ambient lighting used by physically based materials
+    fn set_environment(&mut self, env: Option<Environment>) {
+        self.get_graphics_mut().environment = env;
+    }
+
+    fn environment<'a>(&'a self) -> Option<&'a Environment> {
+        self.get_graphics().environment.as_ref()
+    }
+
     fn set_draw(&mut self, oid: Entity, geo: Entity, material: Entity) {
         let draw = Drawable {
             geometry: geo,
@@ -189,6 +267,10 @@ pub trait Graphics: Common + Sized {
         self.get_graphics().draw.iter()
     }
 
+    fn vertex_buffer<'a>(&'a self, oid: Entity) -> Option<&'a VertexBuffer> {
+        self.get_graphics().vertex.get(oid)
+    }
+
     fn vertex_buffer_iter<'a>(&'a self) -> StaticIterator<'a, VertexBuffer> {
         self.get_graphics().vertex.iter()
     }
@@ -214,10 +296,16 @@ pub trait Graphics: Common + Sized {
 
     fn new_texture(&mut self, texture: Texture) -> Entity {
         let oid = self.new_object(None);
-        self.get_graphics_mut().texture.insert(oid, texture);
+        self.set_texture(oid, texture);
         oid
     }
 
+    /// replace the image of a texture
+    fn set_texture(&mut self, oid: Entity, texture: Texture) {
+        self.get_graphics_mut().texture.insert(oid, texture);
+        self.bump_revision(oid);
+    }
+
     fn get_texture<'a>(&'a self, oid: Entity) -> Option<&'a Texture> {
         self.get_graphics().texture.get(oid)
     }
@@ -254,12 +342,16 @@ impl Duplicate for GraphicsData {
         x.map(|x| self.material.insert(dst, x));
         let x = self.material_index.get(src).map(|x| x.clone());
         x.map(|x| self.material_index.insert(dst, x));
+        let x = self.pbr_material.get(src).map(|x| x.clone());
+        x.map(|x| self.pbr_material.insert(dst, x));
         let x = self.texture.get(src).map(|x| x.clone());
         x.map(|x| self.texture.insert(dst, x));
         let x = self.lights.get(src).map(|x| x.clone());
         x.map(|x| self.lights.insert(dst, x));
         let x = self.sphere.get(src).map(|x| x.clone());
         x.map(|x| self.sphere.insert(dst, x));
+        let x = self.revision.get(src).map(|x| x.clone());
+        x.map(|x| self.revision.insert(dst, x));
     }
 }
 
@@ -271,9 +363,11 @@ impl Delete for GraphicsData {
         self.vertex.remove(oid)           |
         self.material.remove(oid)         |
         self.material_index.remove(oid)   |
+        self.pbr_material.remove(oid)     |
         self.texture.remove(oid)          |
         self.lights.remove(oid)           |
-        self.sphere.remove(oid)
+        self.sphere.remove(oid)           |
+        self.revision.remove(oid)
     }
 }
 
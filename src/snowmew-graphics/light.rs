@@ -13,14 +13,22 @@
 //   limitations under the License.
 
 use std::default::Default;
+use std::num::Float;
 use cgmath::Vector3;
 
+/// The fraction of a light's intensity where it is considered to have
+/// no further effect, this is used to calculate the range of a light
+const CUTOFF: f32 = 1. / 256.;
 
+fn range(intensity: f32) -> f32 {
+    (intensity / CUTOFF).sqrt()
+}
 
 #[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
 pub struct Point {
     color: Vector3<f32>,
-    intensity: f32
+    intensity: f32,
+    casts_shadows: bool
 }
 
 
@@ -30,18 +38,59 @@ impl Point {
         Point {
             color: color,
             intensity: intensity,
+            casts_shadows: false
         }
     }
 
     pub fn color(&self) -> Vector3<f32> {self.color.clone()}
     pub fn intensity(&self) -> f32 {self.intensity.clone()}
+    /// The distance at which the light no longer has an effect
+    pub fn range(&self) -> f32 {range(self.intensity)}
+    pub fn casts_shadows(&self) -> bool {self.casts_shadows}
+    pub fn set_casts_shadows(&mut self, v: bool) {self.casts_shadows = v}
+}
+
+#[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
+pub struct Spot {
+    normal: Vector3<f32>,
+    color: Vector3<f32>,
+    intensity: f32,
+    angle: f32,
+    casts_shadows: bool
+}
+
+impl Spot {
+    /// Create a spot light pointing along `normal`, `angle` is half
+    /// of the cone's width in radians
+    pub fn new(normal: Vector3<f32>,
+               color: Vector3<f32>,
+               intensity: f32,
+               angle: f32) -> Spot {
+        Spot {
+            normal: normal,
+            color: color,
+            intensity: intensity,
+            angle: angle,
+            casts_shadows: false
+        }
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {self.normal.clone()}
+    pub fn color(&self) -> Vector3<f32> {self.color.clone()}
+    pub fn intensity(&self) -> f32 {self.intensity.clone()}
+    pub fn angle(&self) -> f32 {self.angle}
+    /// The distance at which the light no longer has an effect
+    pub fn range(&self) -> f32 {range(self.intensity)}
+    pub fn casts_shadows(&self) -> bool {self.casts_shadows}
+    pub fn set_casts_shadows(&mut self, v: bool) {self.casts_shadows = v}
 }
 
 #[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
 pub struct Directional {
     normal: Vector3<f32>,
     color: Vector3<f32>,
-    intensity: f32
+    intensity: f32,
+    casts_shadows: bool
 }
 
 impl Directional {
@@ -52,25 +101,45 @@ impl Directional {
             normal: normal,
             color: color,
             intensity: intensity,
+            casts_shadows: true
         }
     }
 
     pub fn normal(&self) -> Vector3<f32> {self.normal.clone()}
     pub fn color(&self) -> Vector3<f32> {self.color.clone()}
     pub fn intensity(&self) -> f32 {self.intensity.clone()}
+    pub fn casts_shadows(&self) -> bool {self.casts_shadows}
+    pub fn set_casts_shadows(&mut self, v: bool) {self.casts_shadows = v}
 }
 
 #[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
 pub enum Light {
     Directional(Directional),
-    Point(Point)
+    Point(Point),
+    Spot(Spot)
+}
+
+impl Light {
+    /// Check if the light should be rendered with a shadow
+    pub fn casts_shadows(&self) -> bool {
+        match *self {
+            Light::Directional(ref l) => l.casts_shadows(),
+            Light::Point(ref l) => l.casts_shadows(),
+            Light::Spot(ref l) => l.casts_shadows()
+        }
+    }
+
+    pub fn set_casts_shadows(&mut self, v: bool) {
+        match *self {
+            Light::Directional(ref mut l) => l.set_casts_shadows(v),
+            Light::Point(ref mut l) => l.set_casts_shadows(v),
+            Light::Spot(ref mut l) => l.set_casts_shadows(v)
+        }
+    }
 }
 
 impl Default for Light {
     fn default() -> Light {
-        Light::Point(Point {
-            color: Vector3::new(0f32, 0., 0.),
-            intensity: 0.
-        })
+        Light::Point(Point::new(Vector3::new(0f32, 0., 0.), 0.))
     }
-}
\ No newline at end of file
+}
@@ -30,6 +30,7 @@ pub struct Material {
     tr: f32,
     d: f32,
     illum: i32,
+    bump_scale: f32,
 
     map_ka:   Option<Entity>,
     map_kd:   Option<Entity>,
@@ -38,6 +39,7 @@ pub struct Material {
     map_ns:   Option<Entity>,
     map_d:    Option<Entity>,
     map_bump: Option<Entity>,
+    map_norm: Option<Entity>,
     map_refl: Option<Entity>,
 }
 
@@ -54,6 +56,7 @@ impl Clone for Material {
             tr: self.tr,
             d: self.d,
             illum: self.illum,
+            bump_scale: self.bump_scale,
             map_ka: self.map_ka,
             map_kd: self.map_kd,
             map_ks: self.map_ks,
@@ -61,6 +64,7 @@ impl Clone for Material {
             map_ns: self.map_ns,
             map_d: self.map_d,
             map_bump: self.map_bump,
+            map_norm: self.map_norm,
             map_refl: self.map_refl
         }
     }
@@ -83,8 +87,9 @@ impl Material {
             ns: 0.,
             ni: 0.,
             tr: 0.,
-            d: 0.,
+            d: 1.,
             illum: 2,
+            bump_scale: 1.,
             map_ka:   None,
             map_kd:   None,
             map_ks:   None,
@@ -92,6 +97,7 @@ impl Material {
             map_ns:   None,
             map_d:    None,
             map_bump: None,
+            map_norm: None,
             map_refl: None,
         }
     }
@@ -137,9 +143,14 @@ impl Material {
     pub fn map_d(&self) -> Option<Entity> {self.map_d}
     pub fn set_map_d(&mut self, oid: Entity) {self.map_d = Some(oid);}
 
+    /// a height map
     pub fn map_bump(&self) -> Option<Entity> {self.map_bump}
     pub fn set_map_bump(&mut self, oid: Entity) {self.map_bump = Some(oid);}
 
+    /// a tangent space normal map
+    pub fn map_norm(&self) -> Option<Entity> {self.map_norm}
+    pub fn set_map_norm(&mut self, oid: Entity) {self.map_norm = Some(oid);}
+
     pub fn map_refl(&self) -> Option<Entity> {self.map_refl}
     pub fn set_map_refl(&mut self, oid: Entity) {self.map_refl = Some(oid);}
 
@@ -148,4 +159,19 @@ impl Material {
 
     pub fn ni(&self) -> f32 {self.ni}
     pub fn set_ni(&mut self, v: f32) {self.ni = v}
+
+    /// how strongly `map_bump` changes the surface normal
+    pub fn bump_scale(&self) -> f32 {self.bump_scale}
+    pub fn set_bump_scale(&mut self, v: f32) {self.bump_scale = v}
+
+    pub fn tr(&self) -> f32 {self.tr}
+    pub fn set_tr(&mut self, v: f32) {self.tr = v}
+
+    pub fn d(&self) -> f32 {self.d}
+    pub fn set_d(&mut self, v: f32) {self.d = v}
+
+    /// Check to see if the material must be drawn with blending
+    pub fn is_transparent(&self) -> bool {
+        self.d < 1. || self.map_d.is_some()
+    }
 }
@@ -0,0 +1,248 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::default::Default;
+use std::num::Float;
+use std::f32::consts::PI;
+
+use snowmew::Entity;
+use geometry::F32v3;
+use texture::Texture;
+
+/// A metallic-roughness material, this follows the model used by most
+/// modern content tools.
+#[derive(Clone, RustcEncodable, RustcDecodable, PartialEq, Copy)]
+pub struct PbrMaterial {
+    base_color: F32v3,
+    alpha: f32,
+    alpha_cutoff: f32,
+    metallic: f32,
+    roughness: f32,
+    emissive: F32v3,
+    occlusion_strength: f32,
+    normal_scale: f32,
+
+    map_base_color: Option<Entity>,
+    map_metallic_roughness: Option<Entity>,
+    map_emissive: Option<Entity>,
+    map_occlusion: Option<Entity>,
+    map_normal: Option<Entity>
+}
+
+impl Default for PbrMaterial {
+    fn default() -> PbrMaterial {
+        PbrMaterial::new()
+    }
+}
+
+impl PbrMaterial {
+    /// A white dielectric with medium roughness
+    pub fn new() -> PbrMaterial {
+        PbrMaterial {
+            base_color: F32v3([1., 1., 1.]),
+            alpha: 1.,
+            alpha_cutoff: 0.,
+            metallic: 0.,
+            roughness: 0.5,
+            emissive: F32v3([0., 0., 0.]),
+            occlusion_strength: 1.,
+            normal_scale: 1.,
+            map_base_color: None,
+            map_metallic_roughness: None,
+            map_emissive: None,
+            map_occlusion: None,
+            map_normal: None
+        }
+    }
+
+    pub fn simple(color: [f32; 3], metallic: f32, roughness: f32) -> PbrMaterial {
+        let mut mat = PbrMaterial::new();
+        mat.base_color = F32v3(color);
+        mat.metallic = metallic;
+        mat.roughness = roughness;
+        mat
+    }
+
+    pub fn base_color(&self) -> [f32; 3] {self.base_color.0}
+    pub fn set_base_color(&mut self, c: [f32; 3]) {self.base_color = F32v3(c);}
+
+    pub fn alpha(&self) -> f32 {self.alpha}
+    pub fn set_alpha(&mut self, v: f32) {self.alpha = v}
+
+    /// fragments with an alpha below the cutoff are discarded and the
+    /// rest are drawn opaque, zero turns the test off
+    pub fn alpha_cutoff(&self) -> f32 {self.alpha_cutoff}
+    pub fn set_alpha_cutoff(&mut self, v: f32) {self.alpha_cutoff = v}
+
+    pub fn metallic(&self) -> f32 {self.metallic}
+    pub fn set_metallic(&mut self, v: f32) {self.metallic = v}
+
+    pub fn roughness(&self) -> f32 {self.roughness}
+    pub fn set_roughness(&mut self, v: f32) {self.roughness = v}
+
+    pub fn emissive(&self) -> [f32; 3] {self.emissive.0}
+    pub fn set_emissive(&mut self, c: [f32; 3]) {self.emissive = F32v3(c);}
+
+    pub fn occlusion_strength(&self) -> f32 {self.occlusion_strength}
+    pub fn set_occlusion_strength(&mut self, v: f32) {self.occlusion_strength = v}
+
+    pub fn normal_scale(&self) -> f32 {self.normal_scale}
+    pub fn set_normal_scale(&mut self, v: f32) {self.normal_scale = v}
+
+    /// rgb colour, the alpha channel is multiplied with `alpha`
+    pub fn map_base_color(&self) -> Option<Entity> {self.map_base_color}
+    pub fn set_map_base_color(&mut self, oid: Entity) {self.map_base_color = Some(oid);}
+
+    /// metallic is read from the blue channel and roughness from green
+    pub fn map_metallic_roughness(&self) -> Option<Entity> {self.map_metallic_roughness}
+    pub fn set_map_metallic_roughness(&mut self, oid: Entity) {self.map_metallic_roughness = Some(oid);}
+
+    pub fn map_emissive(&self) -> Option<Entity> {self.map_emissive}
+    pub fn set_map_emissive(&mut self, oid: Entity) {self.map_emissive = Some(oid);}
+
+    /// ambient occlusion is read from the red channel
+    pub fn map_occlusion(&self) -> Option<Entity> {self.map_occlusion}
+    pub fn set_map_occlusion(&mut self, oid: Entity) {self.map_occlusion = Some(oid);}
+
+    /// a tangent space normal map
+    pub fn map_normal(&self) -> Option<Entity> {self.map_normal}
+    pub fn set_map_normal(&mut self, oid: Entity) {self.map_normal = Some(oid);}
+
+    /// Check to see if the material must be drawn with blending, masked
+    /// materials are drawn opaque
+    pub fn is_transparent(&self) -> bool {
+        self.alpha_cutoff == 0. && self.alpha < 1.
+    }
+}
+
+/// The number of spherical harmonic coefficients stored for irradiance
+pub const SH_COEFFICIENTS: usize = 9;
+
+/// The ambient lighting used by physically based materials. The diffuse
+/// part is stored as spherical harmonics so it can be evaluated without
+/// sampling a texture, the specular part is read from `map` which is an
+/// equirectangular image of the surroundings.
+#[derive(Clone, RustcEncodable, RustcDecodable, PartialEq)]
+pub struct Environment {
+    map: Option<Entity>,
+    irradiance: Vec<F32v3>,
+    intensity: f32
+}
+
+fn sh_basis(d: [f32; 3]) -> [f32; SH_COEFFICIENTS] {
+    let (x, y, z) = (d[0], d[1], d[2]);
+    [0.282095,
+     0.488603 * y,
+     0.488603 * z,
+     0.488603 * x,
+     1.092548 * x * y,
+     1.092548 * y * z,
+     0.315392 * (3. * z * z - 1.),
+     1.092548 * x * z,
+     0.546274 * (x * x - y * y)]
+}
+
+/// The cosine lobe convolution for each band, divided by pi so that the
+/// result can be multiplied directly with the albedo
+const BAND_SCALE: [f32; SH_COEFFICIENTS] = [1.,
+                                            2. / 3., 2. / 3., 2. / 3.,
+                                            0.25, 0.25, 0.25, 0.25, 0.25];
+
+/// Find the direction that an equirectangular texture coordinate faces,
+/// `u` wraps around the y axis starting at -z, `v` runs from +y to -y
+pub fn equirect_direction(u: f32, v: f32) -> [f32; 3] {
+    let phi = (u - 0.5) * 2. * PI;
+    let theta = v * PI;
+    [theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos()]
+}
+
+impl Environment {
+    /// A constant ambient colour with no reflections
+    pub fn uniform(color: [f32; 3]) -> Environment {
+        let mut irradiance: Vec<F32v3> = (0..SH_COEFFICIENTS).map(|_| F32v3([0.; 3])).collect();
+        let scale = 1. / sh_basis([0., 1., 0.])[0];
+        irradiance[0] = F32v3([color[0] * scale, color[1] * scale, color[2] * scale]);
+        Environment {
+            map: None,
+            irradiance: irradiance,
+            intensity: 1.
+        }
+    }
+
+    /// Create an environment from an equirectangular image. `map` is the
+    /// entity the same image was stored under, it is used for reflections.
+    /// A compressed image can not be read back so it gives no ambient light.
+    pub fn from_equirect(texture: &Texture, map: Entity) -> Environment {
+        let (w, h) = if texture.format().is_compressed() {
+            (0, 0)
+        } else {
+            (texture.width() as usize, texture.height() as usize)
+        };
+
+        // large images are sampled sparsely, the result is very smooth
+        let step = (w / 128).max(1);
+        let mut sh = [[0f32; 3]; SH_COEFFICIENTS];
+        let mut y = step / 2;
+        while y < h {
+            let v = (y as f32 + 0.5) / h as f32;
+            let weight = (2. * PI / w as f32) * (PI / h as f32) * (v * PI).sin() *
+                         (step * step) as f32;
+            let mut x = step / 2;
+            while x < w {
+                let u = (x as f32 + 0.5) / w as f32;
+                let basis = sh_basis(equirect_direction(u, v));
+                for c in (0..3) {
+                    let radiance = texture.texel(x as u32, y as u32, c);
+                    for i in (0..SH_COEFFICIENTS) {
+                        sh[i][c] += radiance * basis[i] * weight;
+                    }
+                }
+                x += step;
+            }
+            y += step;
+        }
+
+        Environment {
+            map: Some(map),
+            irradiance: sh.iter().zip(BAND_SCALE.iter()).map(|(c, &s)| {
+                F32v3([c[0] * s, c[1] * s, c[2] * s])
+            }).collect(),
+            intensity: 1.
+        }
+    }
+
+    /// the equirectangular image used for reflections
+    pub fn map(&self) -> Option<Entity> {self.map}
+
+    pub fn intensity(&self) -> f32 {self.intensity}
+    pub fn set_intensity(&mut self, v: f32) {self.intensity = v}
+
+    /// The irradiance coefficients, already divided by pi
+    pub fn coefficients(&self) -> Vec<[f32; 3]> {
+        self.irradiance.iter().map(|c| c.0).collect()
+    }
+
+    /// Evaluate the diffuse light arriving at a surface facing `normal`,
+    /// the result is divided by pi.
+    pub fn irradiance(&self, normal: [f32; 3]) -> [f32; 3] {
+        let basis = sh_basis(normal);
+        let mut out = [0f32; 3];
+        for (c, b) in self.irradiance.iter().zip(basis.iter()) {
+            for i in (0..3) {
+                out[i] += c.0[i] * *b * self.intensity;
+            }
+        }
+        out
+    }
+}
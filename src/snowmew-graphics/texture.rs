@@ -13,13 +13,166 @@
 //   limitations under the License.
 
 use std::default;
+use std::mem;
+use std::num::{Float, Int};
+use std::f32::consts::PI;
+
+/// The layout of each pixel of a texture
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum Format {
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    /// rgb stored with the sRGB curve, it is read back as linear
+    SRGB8,
+    /// sRGB colour with a linear alpha
+    SRGBA8,
+    R32F,
+    RG32F,
+    RGB32F,
+    RGBA32F,
+    /// ETC2 compressed blocks of 4x4 pixels
+    Etc2RGB,
+    Etc2SRGB,
+    Etc2RGBA
+}
+
+impl Format {
+    /// The number of channels of each pixel
+    pub fn components(&self) -> usize {
+        match *self {
+            Format::R8 | Format::R32F => 1,
+            Format::RG8 | Format::RG32F => 2,
+            Format::RGB8 | Format::SRGB8 | Format::RGB32F |
+            Format::Etc2RGB | Format::Etc2SRGB => 3,
+            Format::RGBA8 | Format::SRGBA8 | Format::RGBA32F |
+            Format::Etc2RGBA => 4
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        match *self {
+            Format::SRGB8 | Format::SRGBA8 | Format::Etc2SRGB => true,
+            _ => false
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        match *self {
+            Format::R32F | Format::RG32F | Format::RGB32F | Format::RGBA32F => true,
+            _ => false
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        match *self {
+            Format::Etc2RGB | Format::Etc2SRGB | Format::Etc2RGBA => true,
+            _ => false
+        }
+    }
+
+    /// The number of bytes of a pixel, compressed formats return 0
+    pub fn pixel_size(&self) -> usize {
+        if self.is_compressed() {
+            0
+        } else if self.is_float() {
+            self.components() * 4
+        } else {
+            self.components()
+        }
+    }
+
+    /// The number of bytes of an image of this size
+    pub fn size(&self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        match *self {
+            Format::Etc2RGB | Format::Etc2SRGB => ((w + 3) / 4) * ((h + 3) / 4) * 8,
+            Format::Etc2RGBA => ((w + 3) / 4) * ((h + 3) / 4) * 16,
+            _ => w * h * self.pixel_size()
+        }
+    }
+
+    /// The uncompressed 8 bit format with `depth` channels, if there is one
+    pub fn from_depth(depth: u32) -> Option<Format> {
+        match depth {
+            1 => Some(Format::R8),
+            2 => Some(Format::RG8),
+            3 => Some(Format::RGB8),
+            4 => Some(Format::RGBA8),
+            _ => None
+        }
+    }
+}
+
+/// What happens when a texture is sampled outside of 0 to 1
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp
+}
+
+/// How texels are blended when a texture is magnified or minified
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    /// bilinear with blending between mip levels
+    Trilinear,
+    /// trilinear with the number of samples used at steep angles
+    Anisotropic(u8)
+}
+
+impl Filter {
+    /// true if the filter reads from the mip levels
+    pub fn uses_mipmaps(&self) -> bool {
+        match *self {
+            Filter::Trilinear | Filter::Anisotropic(_) => true,
+            _ => false
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub struct Sampler {
+    pub wrap: Wrap,
+    pub filter: Filter
+}
+
+impl default::Default for Sampler {
+    fn default() -> Sampler {
+        Sampler {
+            wrap: Wrap::Repeat,
+            filter: Filter::Anisotropic(16)
+        }
+    }
+}
+
+/// How the images of a texture are arranged
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum Kind {
+    Texture2D,
+    /// six square faces in the order +x, -x, +y, -y, +z, -z
+    Cube,
+    /// a number of layers of the same size
+    Array(u32)
+}
+
+/// The number of faces of a cube map
+pub const CUBE_FACES: usize = 6;
 
 #[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct Texture {
     width: u32,
     height: u32,
-    depth: u32,
-    data: Vec<u8>
+    format: Format,
+    kind: Kind,
+    sampler: Sampler,
+    /// the image followed by each mip level, every level is half the
+    /// size of the one before it. Each level holds every layer one after
+    /// another.
+    levels: Vec<Vec<u8>>
 }
 
 fn offset(width: usize, depth: usize,
@@ -44,28 +197,270 @@ fn flip(dat: &mut Vec<u8>, height: usize, width: usize, depth: usize) {
     }
 }
 
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Read a channel of an uncompressed image as a linear value
+fn read(format: Format, data: &[u8], i: usize, component: usize) -> f32 {
+    if format.is_float() {
+        let b = &data[i * 4..i * 4 + 4];
+        let bits = (b[0] as u32) | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24;
+        unsafe { mem::transmute::<u32, f32>(bits) }
+    } else {
+        let v = data[i] as f32 / 255.;
+        // alpha is always stored linearly
+        if format.is_srgb() && component < 3 { srgb_to_linear(v) } else { v }
+    }
+}
+
+fn write(format: Format, data: &mut Vec<u8>, v: f32, component: usize) {
+    if format.is_float() {
+        let bits = unsafe { mem::transmute::<f32, u32>(v) };
+        data.push_all(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+    } else {
+        let v = if format.is_srgb() && component < 3 { linear_to_srgb(v) } else { v };
+        data.push((v * 255. + 0.5).max(0.).min(255.) as u8);
+    }
+}
+
+/// Halve an image with a box filter, the last row or column of an odd
+/// sized image is dropped
+fn downsample(format: Format, data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    let c = format.components();
+    let mut out = Vec::with_capacity(format.size(w as u32, h as u32));
+    for y in (0..h) {
+        for x in (0..w) {
+            let ys = [(y * 2).min(height - 1), (y * 2 + 1).min(height - 1)];
+            let xs = [(x * 2).min(width - 1), (x * 2 + 1).min(width - 1)];
+            for i in (0..c) {
+                let mut sum = 0.;
+                for &sy in ys.iter() {
+                    for &sx in xs.iter() {
+                        sum += read(format, data, (sy * width + sx) * c + i, i);
+                    }
+                }
+                write(format, &mut out, sum / 4., i);
+            }
+        }
+    }
+    out
+}
+
+/// The direction through a point of a cube face, `s` and `t` go from -1
+/// to 1 across and down the face
+fn cube_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1., -t, -s],
+        1 => [-1., -t, s],
+        2 => [s, 1., t],
+        3 => [s, -1., -t],
+        4 => [s, -t, 1.],
+        _ => [-s, -t, -1.]
+    }
+}
+
+/// Bilinear sample of an equirectangular image, wrapping around the
+/// horizon and clamping at the poles
+fn sample_equirect(src: &Texture, d: [f32; 3], component: usize) -> f32 {
+    let len = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+    let u = d[0].atan2(-d[2]) / (2. * PI) + 0.5;
+    let v = (d[1] / len).max(-1.).min(1.).acos() / PI;
+
+    let (w, h) = (src.width() as f32, src.height() as f32);
+    let (x, y) = (u * w - 0.5, (v * h - 0.5).max(0.).min(h - 1.));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let column = |x: f32| (((x as i32) % w as i32 + w as i32) % w as i32) as u32;
+    let row = |y: f32| y.min(h - 1.) as u32;
+
+    let a = src.texel(column(x0), row(y0), component);
+    let b = src.texel(column(x0 + 1.), row(y0), component);
+    let c = src.texel(column(x0), row(y0 + 1.), component);
+    let e = src.texel(column(x0 + 1.), row(y0 + 1.), component);
+    (a * (1. - fx) + b * fx) * (1. - fy) + (c * (1. - fx) + e * fx) * fy
+}
+
+/// Project an equirectangular image onto a cube map with faces of `size`
+/// pixels. The directions match `pbr::equirect_direction`. Float images
+/// stay float and sRGB images stay sRGB.
+pub fn cube_from_equirect(src: &Texture, size: u32) -> Texture {
+    let format = if src.format().is_float() {
+        Format::RGBA32F
+    } else if src.format().is_srgb() {
+        Format::SRGBA8
+    } else {
+        Format::RGBA8
+    };
+
+    let faces = (0..CUBE_FACES).map(|face| {
+        let mut data = Vec::with_capacity(format.size(size, size));
+        for y in (0..size) {
+            for x in (0..size) {
+                let s = 2. * (x as f32 + 0.5) / size as f32 - 1.;
+                let t = 2. * (y as f32 + 0.5) / size as f32 - 1.;
+                let d = cube_direction(face, s, t);
+                for c in (0..4) {
+                    write(format, &mut data, sample_equirect(src, d, c), c);
+                }
+            }
+        }
+        data
+    }).collect();
+    Texture::cube(size, format, faces)
+}
 
 impl default::Default for Texture {
     fn default() -> Texture {
-        Texture::new(0, 0, 0, Vec::new())
+        Texture::with_format(0, 0, Format::RGBA8, Vec::new())
     }
 }
 
 impl Texture {
+    /// Create a texture with `depth` 8 bit channels per pixel, panics
+    /// unless `depth` is 1 to 4
     pub fn new(width: u32, height: u32, depth: u32, data: Vec<u8>) -> Texture {
+        let format = Format::from_depth(depth).expect("unsupported color depth");
+        Texture::with_format(width, height, format, data)
+    }
+
+    pub fn with_format(width: u32, height: u32, format: Format, data: Vec<u8>) -> Texture {
         Texture {
             width: width,
             height: height,
-            depth: depth,
-            data: data
+            format: format,
+            kind: Kind::Texture2D,
+            sampler: default::Default::default(),
+            levels: vec![data]
+        }
+    }
+
+    /// Create a cube map from six square faces, see `Kind::Cube` for
+    /// their order
+    pub fn cube(size: u32, format: Format, faces: Vec<Vec<u8>>) -> Texture {
+        assert_eq!(faces.len(), CUBE_FACES);
+        let mut texture = Texture::array(size, size, format, faces);
+        texture.kind = Kind::Cube;
+        texture.sampler.wrap = Wrap::Clamp;
+        texture
+    }
+
+    /// Create an array texture, every layer must be the same size
+    pub fn array(width: u32, height: u32, format: Format, layers: Vec<Vec<u8>>) -> Texture {
+        let count = layers.len() as u32;
+        let mut data = Vec::with_capacity(format.size(width, height) * layers.len());
+        for layer in layers.into_iter() {
+            assert_eq!(layer.len(), format.size(width, height));
+            data.push_all(&layer[]);
         }
+        let mut texture = Texture::with_format(width, height, format, data);
+        texture.kind = Kind::Array(count);
+        texture
     }
 
     pub fn width(&self) -> u32 { self.width }
     pub fn height(&self) -> u32 { self.height }
-    pub fn depth(&self) -> u32 { self.depth }
-    pub fn data<'a>(&'a self) -> &'a [u8] { &self.data[] }
+    /// bytes per pixel, 0 for compressed textures
+    pub fn depth(&self) -> u32 { self.format.pixel_size() as u32 }
+    pub fn format(&self) -> Format { self.format }
+    pub fn kind(&self) -> Kind { self.kind }
+
+    /// The number of images in each level, 6 for a cube map
+    pub fn layers(&self) -> usize {
+        match self.kind {
+            Kind::Texture2D => 1,
+            Kind::Cube => CUBE_FACES,
+            Kind::Array(n) => n as usize
+        }
+    }
+
+    /// One layer or face of a mip level
+    pub fn layer<'a>(&'a self, level: usize, layer: usize) -> &'a [u8] {
+        let (w, h) = self.level_size(level);
+        let size = self.format.size(w, h);
+        &self.levels[level][layer * size..(layer + 1) * size]
+    }
+    pub fn data<'a>(&'a self) -> &'a [u8] { &self.levels[0][] }
+
+    /// Read a channel of a pixel of the first layer as a linear value
+    /// between 0 and 1, missing channels repeat the last one
+    pub fn texel(&self, x: u32, y: u32, component: usize) -> f32 {
+        assert!(!self.format.is_compressed(), "can not read a compressed texture");
+        let c = self.format.components();
+        let component = component.min(c - 1);
+        let i = (y as usize * self.width as usize + x as usize) * c + component;
+        read(self.format, &self.levels[0][], i, component)
+    }
+
+    pub fn sampler(&self) -> Sampler { self.sampler }
+    pub fn set_sampler(&mut self, sampler: Sampler) { self.sampler = sampler; }
+
+    /// The number of mip levels, including the image itself
+    pub fn levels(&self) -> usize { self.levels.len() }
+    pub fn level<'a>(&'a self, level: usize) -> &'a [u8] { &self.levels[level][] }
+
+    /// The size of a mip level
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// The number of levels a full mip chain of this texture has
+    pub fn full_levels(&self) -> usize {
+        let size = self.width.max(self.height).max(1);
+        32 - size.leading_zeros() as usize
+    }
+
+    /// Add the next mip level, this is how compressed textures get their
+    /// mip levels since they can not be generated
+    pub fn push_level(&mut self, data: Vec<u8>) {
+        self.levels.push(data);
+    }
+
+    /// Replace the mip levels with a full chain made by repeatedly halving
+    /// the image. sRGB images are filtered in linear space. Compressed
+    /// textures are left as they are.
+    pub fn generate_mipmaps(&mut self) {
+        if self.format.is_compressed() {
+            return;
+        }
+        self.levels.truncate(1);
+        for level in (1..self.full_levels()) {
+            let (w, h) = self.level_size(level - 1);
+            let mut next = Vec::new();
+            for layer in (0..self.layers()) {
+                next.push_all(&downsample(self.format, self.layer(level - 1, layer),
+                                          w as usize, h as usize)[]);
+            }
+            self.levels.push(next);
+        }
+    }
+
+    /// Flip each layer upside down, any mip levels are regenerated
     pub fn flip(&mut self) {
-        flip(&mut self.data, self.height as usize, self.width as usize, self.depth as usize);
+        assert!(!self.format.is_compressed(), "can not flip a compressed texture");
+        let mipmapped = self.levels.len() > 1;
+        let mut flipped = Vec::with_capacity(self.levels[0].len());
+        for layer in (0..self.layers()) {
+            let mut data = self.layer(0, layer).to_vec();
+            flip(&mut data, self.height as usize, self.width as usize, self.format.pixel_size());
+            flipped.push_all(&data[]);
+        }
+        self.levels[0] = flipped;
+        if mipmapped {
+            self.generate_mipmaps();
+        }
     }
-}
\ No newline at end of file
+}
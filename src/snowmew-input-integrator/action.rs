@@ -0,0 +1,290 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::collections::BTreeMap;
+use std::old_io::{File, IoError};
+use std::error::Error;
+use std::fmt;
+use std::num::Float;
+
+use rustc_serialize::json;
+use input::{Button, Axis, DeviceId};
+
+use InputIntegratorState;
+
+/// A digital binding to an axis is active once the axis is pushed
+/// this far in the direction of the binding's scale
+pub const AXIS_THRESHOLD: f64 = 0.5;
+
+/// What drives a binding
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Input {
+    /// a key, mouse button or gamepad button, this is 1 while held
+    Button(Button),
+    /// a gamepad axis
+    Axis(DeviceId, Axis)
+}
+
+/// Connects an input to an action
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Binding {
+    pub input: Input,
+    /// buttons that have to be held for the binding to count. When two
+    /// bindings share an input the one with more held modifiers wins, so
+    /// `Ctrl+S` does not also trigger an action bound to `S`.
+    pub modifiers: Vec<Button>,
+    /// the input's value is multiplied by this, a negative scale lets
+    /// two keys drive the same analog action in opposite directions
+    pub scale: f64
+}
+
+impl Binding {
+    pub fn button(button: Button) -> Binding {
+        Binding {
+            input: Input::Button(button),
+            modifiers: Vec::new(),
+            scale: 1.
+        }
+    }
+
+    pub fn axis(device: DeviceId, axis: Axis) -> Binding {
+        Binding {
+            input: Input::Axis(device, axis),
+            modifiers: Vec::new(),
+            scale: 1.
+        }
+    }
+
+    /// Require `modifier` to be held as well
+    pub fn with(mut self, modifier: Button) -> Binding {
+        if !self.modifiers.contains(&modifier) {
+            self.modifiers.push(modifier);
+        }
+        self
+    }
+
+    pub fn scale(mut self, scale: f64) -> Binding {
+        self.scale = scale;
+        self
+    }
+
+    fn modifiers_held(&self, state: &InputIntegratorState) -> bool {
+        self.modifiers.iter().all(|&b| state.button_down(b))
+    }
+
+    /// The scaled value of the input ignoring the modifiers
+    fn raw(&self, state: &InputIntegratorState) -> f64 {
+        let value = match self.input {
+            Input::Button(b) => if state.button_down(b) { 1. } else { 0. },
+            Input::Axis(device, axis) => state.axis(device, axis)
+        };
+        value * self.scale
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum ActionKind {
+    /// on or off, like jump or fire
+    Digital,
+    /// a value between -1 and 1, like walking forward
+    Analog
+}
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+struct Action {
+    kind: ActionKind,
+    bindings: Vec<Binding>
+}
+
+/// Something went wrong reading or writing a bindings file
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(IoError),
+    Decode(json::DecoderError)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref e) => write!(f, "{}", e),
+            ConfigError::Decode(ref e) => write!(f, "invalid bindings: {:?}", e)
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        match *self {
+            ConfigError::Io(_) => "failed to access bindings file",
+            ConfigError::Decode(_) => "invalid bindings"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            ConfigError::Io(ref e) => Some(e as &Error),
+            ConfigError::Decode(_) => None
+        }
+    }
+}
+
+/// Named actions and the inputs bound to them. The game declares the
+/// actions and their default bindings, a bindings file can then
+/// replace the bindings of any of them.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct ActionMap {
+    actions: BTreeMap<String, Action>
+}
+
+impl ActionMap {
+    pub fn new() -> ActionMap {
+        ActionMap { actions: BTreeMap::new() }
+    }
+
+    /// Declare an action, this clears its bindings if it already existed
+    pub fn declare(&mut self, name: &str, kind: ActionKind) {
+        self.actions.insert(name.to_string(), Action {
+            kind: kind,
+            bindings: Vec::new()
+        });
+    }
+
+    pub fn digital(&mut self, name: &str) {
+        self.declare(name, ActionKind::Digital);
+    }
+
+    pub fn analog(&mut self, name: &str) {
+        self.declare(name, ActionKind::Analog);
+    }
+
+    /// Add a binding to an action, returns false if the action was
+    /// never declared
+    pub fn bind(&mut self, name: &str, binding: Binding) -> bool {
+        match self.actions.get_mut(name) {
+            Some(action) => {
+                if !action.bindings.contains(&binding) {
+                    action.bindings.push(binding);
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Remove every binding of an action
+    pub fn unbind(&mut self, name: &str) {
+        if let Some(action) = self.actions.get_mut(name) {
+            action.bindings.clear();
+        }
+    }
+
+    pub fn kind(&self, name: &str) -> Option<ActionKind> {
+        self.actions.get(name).map(|a| a.kind)
+    }
+
+    pub fn bindings(&self, name: &str) -> &[Binding] {
+        match self.actions.get(name) {
+            Some(action) => &action.bindings[],
+            None => &[]
+        }
+    }
+
+    /// The names of all declared actions
+    pub fn actions(&self) -> Vec<&str> {
+        self.actions.keys().map(|k| &k[]).collect()
+    }
+
+    /// Check if a binding loses to another binding of the same input
+    /// that needs more modifiers, all of which are held
+    fn shadowed(&self, binding: &Binding, state: &InputIntegratorState) -> bool {
+        self.actions.values()
+            .flat_map(|a| a.bindings.iter())
+            .any(|other| {
+                other.input == binding.input &&
+                other.modifiers.len() > binding.modifiers.len() &&
+                binding.modifiers.iter().all(|m| other.modifiers.contains(m)) &&
+                other.modifiers_held(state)
+            })
+    }
+
+    fn live<'a>(&'a self, name: &str, state: &'a InputIntegratorState) -> Vec<&'a Binding> {
+        self.bindings(name).iter()
+            .filter(|b| b.modifiers_held(state) && !self.shadowed(*b, state))
+            .collect()
+    }
+
+    /// Check if any binding of the action is held, an axis counts once
+    /// it is pushed past `AXIS_THRESHOLD`
+    pub fn active(&self, state: &InputIntegratorState, name: &str) -> bool {
+        self.live(name, state).iter().any(|b| b.raw(state) >= AXIS_THRESHOLD)
+    }
+
+    /// The sum of the action's bindings clamped between -1 and 1,
+    /// a digital action is either 0 or 1
+    pub fn value(&self, state: &InputIntegratorState, name: &str) -> f64 {
+        match self.kind(name) {
+            Some(ActionKind::Digital) => if self.active(state, name) { 1. } else { 0. },
+            Some(ActionKind::Analog) => {
+                let sum = self.live(name, state).iter().fold(0., |sum, b| sum + b.raw(state));
+                sum.max(-1.).min(1.)
+            }
+            None => 0.
+        }
+    }
+
+    /// Check if one of the action's buttons was pressed this frame
+    /// while its modifiers were held
+    pub fn pressed(&self, state: &InputIntegratorState, name: &str) -> bool {
+        self.live(name, state).iter().any(|b| {
+            match b.input {
+                Input::Button(button) => b.scale > 0. && state.button_pressed(button),
+                Input::Axis(_, _) => false
+            }
+        })
+    }
+
+    /// The bindings of every action as json
+    pub fn bindings_to_string(&self) -> String {
+        let bindings: BTreeMap<&String, &Vec<Binding>> =
+            self.actions.iter().map(|(k, a)| (k, &a.bindings)).collect();
+        json::as_pretty_json(&bindings).to_string()
+    }
+
+    /// Replace the bindings of the actions named in the json, actions
+    /// that are not declared are ignored and actions that are left out
+    /// keep their bindings
+    pub fn bindings_from_str(&mut self, text: &str) -> Result<(), ConfigError> {
+        let bindings: BTreeMap<String, Vec<Binding>> = match json::decode(text) {
+            Ok(b) => b,
+            Err(e) => return Err(ConfigError::Decode(e))
+        };
+        for (name, bindings) in bindings.into_iter() {
+            if let Some(action) = self.actions.get_mut(&name) {
+                action.bindings = bindings;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_bindings(&self, path: &Path) -> Result<(), ConfigError> {
+        File::create(path)
+            .write_str(&self.bindings_to_string()[])
+            .map_err(|e| ConfigError::Io(e))
+    }
+
+    pub fn load_bindings(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let text = try!(File::open(path).read_to_string().map_err(|e| ConfigError::Io(e)));
+        self.bindings_from_str(&text[])
+    }
+}
@@ -0,0 +1,102 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! Gestures are found by comparing where each finger was on the last
+//! frame with where it is now. A finger that touched down this frame
+//! counts as not having moved.
+
+use std::num::Float;
+use std::f64::consts::PI;
+
+/// A finger on the screen
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Touch {
+    /// where the finger first touched down
+    pub start: (f64, f64),
+    /// the position at the end of the last frame
+    pub last: (f64, f64),
+    pub position: (f64, f64),
+    /// false once the finger was lifted, it is removed at the end
+    /// of the frame
+    pub down: bool
+}
+
+impl Touch {
+    pub fn new(x: f64, y: f64) -> Touch {
+        Touch {
+            start: (x, y),
+            last: (x, y),
+            position: (x, y),
+            down: true
+        }
+    }
+}
+
+/// Two fingers moving towards or away from each other
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pinch {
+    /// the point half way between the fingers
+    pub center: (f64, f64),
+    /// the distance between the fingers over the distance on the last
+    /// frame, above one when they spread apart
+    pub scale: f64,
+    /// how far the line between the fingers turned in radians
+    pub rotation: f64
+}
+
+fn center(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    ((a.0 + b.0) / 2., (a.1 + b.1) / 2.)
+}
+
+/// Find a pinch if exactly two fingers are down
+pub fn pinch(touches: &[Touch]) -> Option<Pinch> {
+    let down: Vec<&Touch> = touches.iter().filter(|t| t.down).collect();
+    if down.len() != 2 {
+        return None;
+    }
+    let (a, b) = (down[0], down[1]);
+    let now = (b.position.0 - a.position.0, b.position.1 - a.position.1);
+    let last = (b.last.0 - a.last.0, b.last.1 - a.last.1);
+    let (now_len, last_len) = (now.0.hypot(now.1), last.0.hypot(last.1));
+    if now_len == 0. || last_len == 0. {
+        return None;
+    }
+
+    let mut rotation = now.1.atan2(now.0) - last.1.atan2(last.0);
+    if rotation > PI {
+        rotation -= 2. * PI;
+    } else if rotation < -PI {
+        rotation += 2. * PI;
+    }
+
+    Some(Pinch {
+        center: center(a.position, b.position),
+        scale: now_len / last_len,
+        rotation: rotation
+    })
+}
+
+/// How far the middle of the fingers that are down moved this frame,
+/// `None` if no finger is down
+pub fn pan(touches: &[Touch]) -> Option<(f64, f64)> {
+    let down: Vec<&Touch> = touches.iter().filter(|t| t.down).collect();
+    if down.is_empty() {
+        return None;
+    }
+    let n = down.len() as f64;
+    let (dx, dy) = down.iter().fold((0., 0.), |(x, y), t| {
+        (x + t.position.0 - t.last.0, y + t.position.1 - t.last.1)
+    });
+    Some((dx / n, dy / n))
+}
@@ -12,6 +12,11 @@
 //   See the License for the specific language governing permissions and
 //   limitations under the License.
 
+#![feature(core)]
+#![feature(collections)]
+#![feature(io)]
+#![feature(path)]
+
 extern crate "snowmew-core" as core;
 extern crate "snowmew-input" as input;
 extern crate "snowmew-graphics" as graphics;
@@ -19,11 +24,18 @@ extern crate "snowmew-position" as position;
 extern crate "snowmew-render" as render;
 extern crate "rustc-serialize" as rustc_serialize;
 
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashSet, HashMap, BTreeMap};
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 
 use core::game::Game;
-use input::{Event, Button, IoState, GetIoState};
+use input::{Event, Button, Modifiers, Axis, DeviceId, PointerId, TouchPhase, IoState, GetIoState};
+
+pub use action::{ActionMap, ActionKind, Binding, Input, ConfigError, AXIS_THRESHOLD};
+pub use gesture::{Touch, Pinch};
+
+mod action;
+mod gesture;
 
 /// This `wraps` your game to allow the `input integrator` to
 /// collect input events to simplify event handling.
@@ -43,13 +55,21 @@ impl<Game> InputIntegrator<Game> {
 pub struct InputIntegratorState {
     buttons_down: HashMap<Button, u64>,
     buttons_released: HashSet<Button>,
+    buttons_repeated: HashSet<Button>,
+    modifiers: Modifiers,
+    text: String,
     index: u64,
     time: f64,
     last_time: f64,
     last_mouse: Option<(u64, f64, f64)>,
     mouse: Option<(u64, f64, f64)>,
+    motion: (f64, f64),
+    pointers: HashMap<PointerId, (f64, f64)>,
+    touches: BTreeMap<PointerId, Touch>,
     scroll: (f64, f64),
-    last_scroll: (f64, f64)
+    last_scroll: (f64, f64),
+    axes: HashMap<(DeviceId, Axis), f64>,
+    actions: Arc<ActionMap>
 }
 
 impl InputIntegratorState {
@@ -72,6 +92,18 @@ impl InputIntegratorState {
         self.buttons_released.contains(&button)
     }
 
+    /// check to see if the key was repeated by the OS this frame
+    /// because it is being held
+    pub fn button_repeated(&self, button: Button) -> bool {
+        self.buttons_repeated.contains(&button)
+    }
+
+    /// get the modifier keys that were held at the last button event
+    pub fn modifiers(&self) -> Modifiers { self.modifiers }
+
+    /// get the text that was typed this frame
+    pub fn text(&self) -> &str { &self.text[] }
+
     /// get the absolute mouse position
     pub fn mouse_position(&self) -> (f64, f64) {
         if let Some((_, x, y)) = self.mouse {
@@ -95,6 +127,40 @@ impl InputIntegratorState {
         }
     }
 
+    /// get how far the mouse moved this frame, this keeps working when the
+    /// cursor is at the edge of the screen or captured by the window
+    pub fn mouse_motion(&self) -> (f64, f64) { self.motion }
+
+    /// get the last position of a pointer, the mouse is `MOUSE_POINTER`
+    pub fn pointer_position(&self, pointer: PointerId) -> Option<(f64, f64)> {
+        if pointer == input::MOUSE_POINTER {
+            self.mouse.map(|(_, x, y)| (x, y))
+        } else {
+            self.pointers.get(&pointer).map(|&p| p)
+        }
+    }
+
+    /// get the fingers that are on the screen or were lifted this frame
+    pub fn touches(&self) -> Vec<(PointerId, Touch)> {
+        self.touches.iter().map(|(&id, &t)| (id, t)).collect()
+    }
+
+    pub fn touch(&self, pointer: PointerId) -> Option<Touch> {
+        self.touches.get(&pointer).map(|&t| t)
+    }
+
+    /// get the pinch of two fingers this frame
+    pub fn pinch(&self) -> Option<Pinch> {
+        let touches: Vec<Touch> = self.touches.values().map(|&t| t).collect();
+        gesture::pinch(&touches[])
+    }
+
+    /// get how far the fingers on the screen were dragged this frame
+    pub fn pan(&self) -> Option<(f64, f64)> {
+        let touches: Vec<Touch> = self.touches.values().map(|&t| t).collect();
+        gesture::pan(&touches[])
+    }
+
     /// get the scroll wheels absolute position (total number of turns)
     pub fn scroll_position(&self) -> (f64, f64) { self.scroll }
 
@@ -105,6 +171,30 @@ impl InputIntegratorState {
         (x - ox, y - oy)
     }
 
+    /// get the position of a gamepad axis, this is zero if the
+    /// device is not connected or the axis is in its dead zone
+    pub fn axis(&self, device: DeviceId, axis: Axis) -> f64 {
+        self.axes.get(&(device, axis)).map(|&v| v).unwrap_or(0.)
+    }
+
+    /// get the actions that the game has declared
+    pub fn actions(&self) -> &ActionMap { &*self.actions }
+
+    /// check to see if any input bound to the action is held
+    pub fn action_active(&self, name: &str) -> bool {
+        self.actions.active(self, name)
+    }
+
+    /// get the value of an analog action between -1 and 1
+    pub fn action_value(&self, name: &str) -> f64 {
+        self.actions.value(self, name)
+    }
+
+    /// check to see if a button bound to the action was just pressed
+    pub fn action_pressed(&self, name: &str) -> bool {
+        self.actions.pressed(self, name)
+    }
+
     /// get the current frame index
     pub fn index(&self) -> u64 { self.index }
 
@@ -130,19 +220,37 @@ impl<GameData> InputIntegratorGameData<GameData> {
             state: InputIntegratorState {
                 buttons_down: HashMap::new(),
                 buttons_released: HashSet::new(),
+                buttons_repeated: HashSet::new(),
+                modifiers: Modifiers::none(),
+                text: String::new(),
                 index: 0,
                 time: 0.,
                 last_time: 0.,
                 last_mouse: None,
                 mouse: None,
+                motion: (0., 0.),
+                pointers: HashMap::new(),
+                touches: BTreeMap::new(),
                 scroll: (0., 0.),
-                last_scroll: (0., 0.)
+                last_scroll: (0., 0.),
+                axes: HashMap::new(),
+                actions: Arc::new(ActionMap::new())
             },
             inner: inner
         }
     }
 }
 
+impl<GameData> InputIntegratorGameData<GameData> {
+    /// Replace the actions that are visible through the
+    /// `InputIntegratorState`
+    pub fn set_actions(&mut self, actions: ActionMap) {
+        self.state.actions = Arc::new(actions);
+    }
+
+    pub fn actions(&self) -> &ActionMap { &*self.state.actions }
+}
+
 impl<T> Deref for InputIntegratorGameData<T> {
     type Target = T;
     fn deref<'a>(&'a self) -> &'a <Self as Deref>::Target {
@@ -178,22 +286,77 @@ impl<GameData,
                 gd.inner = self.game.step(gd.state.clone(), gd.inner);
                 gd.state.last_mouse = gd.state.mouse;
                 gd.state.buttons_released.clear();
+                gd.state.buttons_repeated.clear();
+                gd.state.text.clear();
                 gd.state.last_scroll = gd.state.scroll;
+                gd.state.motion = (0., 0.);
+                gd.state.touches = gd.state.touches.iter()
+                    .filter(|&(_, t)| t.down)
+                    .map(|(&id, t)| (id, Touch { last: t.position, .. *t }))
+                    .collect();
             }
-            input::Event::ButtonDown(button) => {
+            input::Event::ButtonDown(button, modifiers) => {
                 gd.state.buttons_down.insert(button, gd.state.index);
+                gd.state.modifiers = modifiers;
             }
-            input::Event::ButtonUp(button) => {
+            input::Event::ButtonUp(button, modifiers) => {
                 gd.state.buttons_down.remove(&button);
                 gd.state.buttons_released.insert(button);
+                gd.state.modifiers = modifiers;
+            }
+            input::Event::ButtonRepeat(button, modifiers) => {
+                gd.state.buttons_repeated.insert(button);
+                gd.state.modifiers = modifiers;
             }
-            input::Event::Move(x, y) => {
+            input::Event::Char(c) => {
+                gd.state.text.push(c);
+            }
+            input::Event::Move(input::MOUSE_POINTER, x, y) => {
                 gd.state.mouse = Some((gd.state.index, x, y));
             }
+            input::Event::Move(pointer, x, y) => {
+                gd.state.pointers.insert(pointer, (x, y));
+            }
+            input::Event::Touch(pointer, phase, x, y) => {
+                gd.state.pointers.insert(pointer, (x, y));
+                match phase {
+                    TouchPhase::Begin => {
+                        gd.state.touches.insert(pointer, Touch::new(x, y));
+                        gd.state.buttons_down.insert(Button::Touch(pointer), gd.state.index);
+                    }
+                    TouchPhase::Move => {
+                        if let Some(t) = gd.state.touches.get_mut(&pointer) {
+                            t.position = (x, y);
+                        }
+                    }
+                    TouchPhase::End | TouchPhase::Cancel => {
+                        if let Some(t) = gd.state.touches.get_mut(&pointer) {
+                            t.position = (x, y);
+                            t.down = false;
+                        }
+                        gd.state.pointers.remove(&pointer);
+                        gd.state.buttons_down.remove(&Button::Touch(pointer));
+                        if phase == TouchPhase::End {
+                            gd.state.buttons_released.insert(Button::Touch(pointer));
+                        }
+                    }
+                }
+            }
+            input::Event::Motion(dx, dy) => {
+                let (x, y) = gd.state.motion;
+                gd.state.motion = (x + dx, y + dy);
+            }
             input::Event::Scroll(dx, dy) => {
                 let (x, y) = gd.state.scroll;
                 gd.state.scroll = (x + dx, y + dy);
             }
+            input::Event::Axis(device, axis, value) => {
+                if value == 0. {
+                    gd.state.axes.remove(&(device, axis));
+                } else {
+                    gd.state.axes.insert((device, axis), value);
+                }
+            }
         }
 
         gd
@@ -0,0 +1,116 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! The engine talks to the windowing system only through a `Backend`,
+//! `GlfwBackend` is the one that opens real windows and `NullBackend`
+//! plays a script so the engine can run without a display.
+
+use libc;
+
+use input::EventGroup;
+use {Window, InputHandle, IoState, Monitor, WindowConfig, DeviceId};
+
+/// The GL context of a window, it is moved to the thread that draws
+pub trait Context: Send {
+    fn make_current(&mut self);
+
+    /// Show the frame, a backend that supports vsync should apply any
+    /// change to it here since it has to be made on the thread that draws
+    fn swap_buffers(&mut self);
+}
+
+pub trait Backend {
+    /// Open a window, the window is not fullscreen
+    fn window(&mut self, size: (u32, u32)) -> Option<Window>;
+
+    /// Open a fullscreen window on the primary monitor
+    fn primary(&mut self, size: (u32, u32)) -> Option<Window>;
+
+    /// Open a window on a head mounted display
+    fn hmd(&mut self) -> Option<Window> { None }
+
+    fn get_primary_resolution(&mut self) -> (u32, u32);
+
+    fn get_primary_position(&mut self) -> (i32, i32);
+
+    /// Block until there is at least one event
+    fn wait(&mut self);
+
+    /// Collect the events that happened since the last poll
+    fn poll(&mut self);
+
+    /// The next event for the window, `EventGroup::Nop` once there are
+    /// none left
+    fn next_event(&mut self, handle: &InputHandle) -> EventGroup;
+
+    /// Check if the window was closed, a window that does not exist
+    /// counts as closed
+    fn should_close(&mut self, handle: &InputHandle) -> bool;
+
+    fn set_title(&mut self, handle: &InputHandle, title: String);
+
+    /// Make the window match the cursor, title and display settings of the
+    /// `IoState`, only the settings that changed are sent to the window
+    fn apply_io_state(&mut self, handle: &InputHandle, io: &IoState);
+
+    /// Fill in the parts of the `IoState` that describe the display, this
+    /// is done before the first frame so the game starts with the window
+    /// as it was created
+    fn read_io_state(&mut self, handle: &InputHandle, io: &mut IoState);
+
+    /// The monitors that are connected, the first one is the primary
+    fn monitors(&mut self) -> Vec<Monitor>;
+
+    /// Open another window for `config`, see `IoState::windows`
+    fn open_window(&mut self, config: &WindowConfig) -> Option<Window> {
+        let window = self.window(config.size);
+        if let Some(ref window) = window {
+            self.configure_window(&window.handle(), config);
+            if let Some(position) = config.position {
+                self.set_window_position(window, position);
+            }
+        }
+        window
+    }
+
+    /// Update the title and size of an extra window
+    fn configure_window(&mut self, handle: &InputHandle, config: &WindowConfig);
+
+    fn set_vsync(&mut self, handle: &InputHandle, vsync: bool);
+
+    /// Destroy a window, the render that draws to it has to be
+    /// dropped first
+    fn close_window(&mut self, handle: &InputHandle);
+
+    fn framebuffer_size(&self, handle: &InputHandle) -> (u32, u32);
+
+    fn set_window_position(&mut self, window: &Window, pos: (i32, i32));
+
+    fn get_framebuffer_size(&mut self, window: &Window) -> (i32, i32) {
+        let (w, h) = self.framebuffer_size(&window.handle());
+        (w as i32, h as i32)
+    }
+
+    /// Set how far a stick or trigger has to move before it is reported,
+    /// between 0 and 1
+    fn set_dead_zone(&mut self, zone: f64);
+
+    fn dead_zone(&self) -> f64;
+
+    /// The name the driver gives to a connected pad
+    fn gamepad_name(&self, _: DeviceId) -> Option<String> { None }
+
+    /// Look up a GL function, this is null if the backend has no GL
+    fn get_proc_address(&self, name: &str) -> *const libc::c_void;
+}
@@ -0,0 +1,177 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::num::Float;
+
+use input::{Button, Event, Modifiers};
+
+/// The index of a joystick as GLFW numbers them, starting at zero
+pub type DeviceId = usize;
+
+/// The number of joysticks that GLFW can report
+pub const MAX_DEVICES: usize = 16;
+
+/// A value from a pad below this is treated as if the pad was at rest
+pub const DEFAULT_DEAD_ZONE: f64 = 0.2;
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, RustcDecodable, RustcEncodable, Copy)]
+pub enum Axis {
+    /// -1 is left, 1 is right
+    LeftX,
+    /// -1 is up, 1 is down, the same way the mouse moves
+    LeftY,
+    RightX,
+    RightY,
+    /// 0 is released, 1 is fully pulled
+    LeftTrigger,
+    RightTrigger
+}
+
+const AXES: [Axis; 6] = [
+    Axis::LeftX,
+    Axis::LeftY,
+    Axis::RightX,
+    Axis::RightY,
+    Axis::LeftTrigger,
+    Axis::RightTrigger
+];
+
+/// Zero any value inside of the dead zone and rescale the rest
+/// so that the full range can still be reached
+pub fn dead_zone(value: f64, zone: f64) -> f64 {
+    let magnitude = value.abs();
+    if magnitude <= zone {
+        0.
+    } else {
+        value.signum() * ((magnitude - zone) / (1. - zone)).min(1.)
+    }
+}
+
+/// Like `dead_zone` but for both axes of a stick at once, this keeps
+/// the stick from snapping to the x or y axis when it is pushed gently
+pub fn stick_dead_zone(x: f64, y: f64, zone: f64) -> (f64, f64) {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= zone {
+        (0., 0.)
+    } else {
+        let scale = ((magnitude - zone) / (1. - zone)).min(1.) / magnitude;
+        (x * scale, y * scale)
+    }
+}
+
+/// The layout used by the Xbox 360 driver, which most pads copy.
+/// Some drivers report the d-pad as buttons 11 to 14 instead of
+/// as axes 6 and 7, both are accepted.
+fn button(index: usize, device: DeviceId) -> Option<Button> {
+    Some(match index {
+        0 => Button::GamepadA(device),
+        1 => Button::GamepadB(device),
+        2 => Button::GamepadX(device),
+        3 => Button::GamepadY(device),
+        4 => Button::GamepadLeftBumper(device),
+        5 => Button::GamepadRightBumper(device),
+        6 => Button::GamepadBack(device),
+        7 => Button::GamepadStart(device),
+        8 => Button::GamepadGuide(device),
+        9 => Button::GamepadLeftStick(device),
+        10 => Button::GamepadRightStick(device),
+        11 => Button::GamepadLeft(device),
+        12 => Button::GamepadRight(device),
+        13 => Button::GamepadUp(device),
+        14 => Button::GamepadDown(device),
+        _ => return None
+    })
+}
+
+/// The last reported state of a pad, used to turn the values that
+/// are polled from GLFW into events
+#[derive(Clone, Debug)]
+pub struct Gamepad {
+    device: DeviceId,
+    axes: [f64; 6],
+    buttons: Vec<Button>
+}
+
+impl Gamepad {
+    pub fn new(device: DeviceId) -> Gamepad {
+        Gamepad {
+            device: device,
+            axes: [0.; 6],
+            buttons: Vec::new()
+        }
+    }
+
+    pub fn device(&self) -> DeviceId { self.device }
+
+    /// Compare the raw values read from the device with the last
+    /// ones and push an event for everything that changed
+    pub fn update(&mut self, axes: &[f32], buttons: &[bool], zone: f64, out: &mut Vec<Event>) {
+        let raw = |i: usize| axes.get(i).map(|&v| v as f64).unwrap_or(0.);
+        // triggers rest at -1
+        let trigger = |i: usize| axes.get(i).map(|&v| (v as f64 + 1.) / 2.).unwrap_or(0.);
+
+        let (lx, ly) = stick_dead_zone(raw(0), raw(1), zone);
+        let (rx, ry) = stick_dead_zone(raw(3), raw(4), zone);
+        let next = [lx, ly, rx, ry, dead_zone(trigger(2), zone), dead_zone(trigger(5), zone)];
+        for (i, &axis) in AXES.iter().enumerate() {
+            if next[i] != self.axes[i] {
+                self.axes[i] = next[i];
+                out.push(Event::Axis(self.device, axis, next[i]));
+            }
+        }
+
+        let device = self.device;
+        let mut down: Vec<Button> = buttons.iter().enumerate()
+            .filter(|&(_, &pressed)| pressed)
+            .filter_map(|(i, _)| button(i, device))
+            .collect();
+        let (hat_x, hat_y) = (raw(6), raw(7));
+        let hat = [(hat_x < -0.5, Button::GamepadLeft(device)),
+                   (hat_x > 0.5, Button::GamepadRight(device)),
+                   (hat_y < -0.5, Button::GamepadUp(device)),
+                   (hat_y > 0.5, Button::GamepadDown(device))];
+        for &(pressed, b) in hat.iter() {
+            if pressed && !down.contains(&b) {
+                down.push(b);
+            }
+        }
+
+        self.set_buttons(down, out);
+    }
+
+    /// The pad was unplugged, release everything that was held
+    pub fn disconnect(&mut self, out: &mut Vec<Event>) {
+        for (i, &axis) in AXES.iter().enumerate() {
+            if self.axes[i] != 0. {
+                self.axes[i] = 0.;
+                out.push(Event::Axis(self.device, axis, 0.));
+            }
+        }
+        self.set_buttons(Vec::new(), out);
+    }
+
+    fn set_buttons(&mut self, down: Vec<Button>, out: &mut Vec<Event>) {
+        for b in self.buttons.iter() {
+            if !down.contains(b) {
+                out.push(Event::ButtonUp(*b, Modifiers::none()));
+            }
+        }
+        for b in down.iter() {
+            if !self.buttons.contains(b) {
+                out.push(Event::ButtonDown(*b, Modifiers::none()));
+            }
+        }
+        self.buttons = down;
+    }
+}
@@ -0,0 +1,573 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::num::Float;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+
+use glfw::{self, Glfw, RenderContext};
+use glfw::Context as GlfwContext;
+use glfw::WindowMode::{Windowed, FullScreen};
+use collect::TrieMap;
+use libc;
+use nice_glfw;
+use ovr;
+
+use input;
+use backend::{Backend, Context};
+use {Window, WindowOSSpec, InputHandle, IoState, Monitor, WindowConfig};
+use {CursorImage, VideoMode, Event, Gamepad, DeviceId, MAX_DEVICES, DEFAULT_DEAD_ZONE};
+
+struct WindowHandle {
+    window: glfw::Window,
+    forced_event: Option<glfw::WindowEvent>,
+    receiver: Receiver<(f64, glfw::WindowEvent)>,
+    queued: Vec<input::EventGroup>,
+    title: String,
+    cursor_mode: glfw::CursorMode,
+    cursor_image: Option<CursorImage>,
+    /// the last cursor position, used to find the relative motion
+    cursor: Option<(f64, f64)>,
+    /// the `(fullscreen, monitor, resolution)` that was last applied
+    display: (bool, Option<usize>, Option<(u32, u32)>),
+    /// read by the `WindowContext` when it swaps buffers
+    vsync: Arc<AtomicBool>
+}
+
+/// Opens its windows with GLFW
+pub struct GlfwBackend {
+    glfw: Glfw,
+    ovr: Option<ovr::Ovr>,
+    windows: TrieMap<WindowHandle>,
+    window_id: usize,
+    gamepads: Vec<Option<Gamepad>>,
+    gamepad_events: Vec<Event>,
+    dead_zone: f64
+}
+
+const JOYSTICKS: [glfw::JoystickId; MAX_DEVICES] = [
+    glfw::JoystickId::Joystick1,
+    glfw::JoystickId::Joystick2,
+    glfw::JoystickId::Joystick3,
+    glfw::JoystickId::Joystick4,
+    glfw::JoystickId::Joystick5,
+    glfw::JoystickId::Joystick6,
+    glfw::JoystickId::Joystick7,
+    glfw::JoystickId::Joystick8,
+    glfw::JoystickId::Joystick9,
+    glfw::JoystickId::Joystick10,
+    glfw::JoystickId::Joystick11,
+    glfw::JoystickId::Joystick12,
+    glfw::JoystickId::Joystick13,
+    glfw::JoystickId::Joystick14,
+    glfw::JoystickId::Joystick15,
+    glfw::JoystickId::Joystick16
+];
+
+fn create_window_context(glfw :&mut Glfw, width: u32, height: u32, name: &str, mode: glfw::WindowMode)
+        -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+
+    nice_glfw::WindowBuilder::new(glfw)
+        .try_modern_context_hints()
+        .size(width, height)
+        .title(name)
+        .mode(mode)
+        .create()
+}
+
+/// The GL context of a GLFW window
+struct WindowContext {
+    render: RenderContext,
+    vsync: Arc<AtomicBool>,
+    vsync_applied: Option<bool>
+}
+
+impl Context for WindowContext {
+    fn make_current(&mut self) {
+        self.render.make_current()
+    }
+
+    fn swap_buffers(&mut self) {
+        let vsync = self.vsync.load(Ordering::Relaxed);
+        if self.vsync_applied != Some(vsync) {
+            unsafe { glfw::ffi::glfwSwapInterval(if vsync { 1 } else { 0 }); }
+            self.vsync_applied = Some(vsync);
+        }
+        self.render.swap_buffers()
+    }
+}
+
+impl GlfwBackend {
+    pub fn new(glfw: Glfw) -> GlfwBackend {
+        GlfwBackend {
+            glfw: glfw,
+            ovr: None,
+            windows: TrieMap::new(),
+            window_id: 0,
+            gamepads: (0..MAX_DEVICES).map(|_| None).collect(),
+            gamepad_events: Vec::new(),
+            dead_zone: DEFAULT_DEAD_ZONE
+        }
+    }
+
+    fn add_window(&mut self,
+                  mut window: glfw::Window,
+                  recv: Receiver<(f64, glfw::WindowEvent)>,
+                  fullscreen: bool,
+                  hmd: Option<Arc<ovr::Hmd>>) -> Window {
+        let id = self.window_id;
+        self.window_id += 1;
+
+        window.set_all_polling(true);
+        window.show();
+
+        let (w, h) = window.get_framebuffer_size();
+        let version = window.get_context_version();
+        let vsync = Arc::new(AtomicBool::new(true));
+        let context = WindowContext {
+            render: window.render_context(),
+            vsync: vsync.clone(),
+            vsync_applied: None
+        };
+
+        self.windows.insert(id, {
+            WindowHandle {
+                window: window,
+                forced_event: Some(glfw::WindowEvent::FramebufferSize(w, h)),
+                receiver: recv,
+                queued: Vec::new(),
+                title: "snowmew".to_string(),
+                cursor_mode: glfw::CursorMode::Normal,
+                cursor_image: None,
+                cursor: None,
+                display: (fullscreen, None, None),
+                vsync: vsync
+            }
+        });
+
+        Window {
+            handle: InputHandle { handle: id },
+            context: Box::new(context) as Box<Context+Send>,
+            version: (version.major, version.minor),
+            hmd: hmd,
+            os_spec: WindowOSSpec::new(&self.glfw)
+        }
+    }
+
+    #[cfg(target_os="linux")]
+    fn create_hmd_window(&mut self, hmd: &ovr::HmdDescription) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+        let window = self.glfw.with_connected_monitors(|glfw, monitors| {
+            for m in monitors.iter() {
+                let (x, y) = m.get_pos();
+                if x == hmd.window_position.x &&
+                   y == hmd.window_position.y {
+                    let (width, height) = (hmd.resolution.x, hmd.resolution.y);
+                    let win_opt = create_window_context(glfw, width as u32, height as u32, "Snowmew FullScreen", FullScreen(m));
+                    let (window, events) = match win_opt {
+                        Some((window, events)) => (window, events),
+                        None => return None
+                    };
+
+                    return Some((window, events));
+                }
+            }
+            None
+        });
+
+        if window.is_none() {
+            // fallback if we could not guess at the screen
+            let (width, height) = (hmd.resolution.x, hmd.resolution.y);
+            let win_opt = self.glfw.create_window(width as u32, height as u32, "Snowmew", Windowed);
+            let (mut window, events) = match win_opt {
+                Some((window, events)) => (window, events),
+                None => return None
+            };
+
+            // move viewport
+            let (dx, dy) = (hmd.window_position.x, hmd.window_position.y);
+            window.set_pos(dx as i32, dy as i32);
+
+            Some((window, events))
+        } else {
+            window
+        }
+    }
+
+    #[cfg(target_os="macos")]
+    fn create_hmd_window(&mut self, hmd: &ovr::HmdDescription) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
+        self.glfw.with_connected_monitors(|glfw, monitors| {
+            for m in monitors.iter() {
+                if !m.get_name()[].contains("Rift") {
+                    continue;
+                }
+
+                let (width, height) = (hmd.resolution.x, hmd.resolution.y);
+                let win_opt = create_window_context(glfw, width as u32, height as u32, "Snowmew FullScreen", FullScreen(m));
+                let (window, events) = match win_opt {
+                    Some((window, events)) => (window, events),
+                    None => return None
+                };
+
+                return Some((window, events));
+            }
+            None
+        })
+    }
+
+    /// GLFW does not send events for joysticks, so every connected
+    /// pad is read and compared with its last state
+    fn poll_gamepads(&mut self) {
+        for (device, &id) in JOYSTICKS.iter().enumerate() {
+            let joystick = self.glfw.get_joystick(id);
+            if joystick.is_present() {
+                let axes = joystick.get_axes();
+                let buttons: Vec<bool> = joystick.get_buttons().iter().map(|&b| b != 0).collect();
+                if self.gamepads[device].is_none() {
+                    self.gamepads[device] = Some(Gamepad::new(device));
+                }
+                let pad = self.gamepads[device].as_mut().unwrap();
+                pad.update(&axes[], &buttons[], self.dead_zone, &mut self.gamepad_events);
+            } else if let Some(mut pad) = self.gamepads[device].take() {
+                pad.disconnect(&mut self.gamepad_events);
+            }
+        }
+    }
+
+    fn setup_ovr(&mut self) -> bool {
+        if self.ovr.is_some() &&
+           self.ovr.as_ref().unwrap().detect() > 0 {
+            return true;
+        }
+
+        if self.ovr.is_none() {
+            self.ovr = ovr::Ovr::init();
+        }
+
+        self.ovr.is_some() && self.ovr.as_ref().unwrap().detect() > 0
+    }
+}
+
+impl Backend for GlfwBackend {
+    fn window(&mut self, size: (u32, u32)) -> Option<Window> {
+        let (width, height) = size;
+        let win_opt = create_window_context(&mut self.glfw, width, height, "Snowmew", Windowed);
+        let (window, events) = match win_opt {
+            Some((window, events)) => (window, events),
+            None => return None
+        };
+        self.glfw.set_swap_interval(1);
+
+        Some(self.add_window(window, events, false, None))
+    }
+
+    fn primary(&mut self, size: (u32, u32)) -> Option<Window> {
+        let screen = {
+            self.glfw.with_primary_monitor(|glfw, display| {
+                let display = display.unwrap();
+                let (width, height) = size;
+                create_window_context(glfw, width, height, "Snowmew FullScreen", FullScreen(display))
+            })
+        };
+
+        match screen {
+            None => None,
+            Some((window, events)) => Some(self.add_window(window, events, true, None))
+        }
+    }
+
+    fn hmd(&mut self) -> Option<Window> {
+        if !self.setup_ovr() {
+            return None;
+        }
+
+        let (window, events, hmd) = {
+            let hmd = match self.ovr.as_ref().unwrap().first_hmd() {
+                Some(hmd) => hmd,
+                None => return None
+            };
+            let hmdinfo = hmd.get_description();
+
+            let (window, events) = match self.create_hmd_window(&hmdinfo) {
+                Some((window, events)) => (window, events),
+                None => return None
+            };
+            (window, events, hmd)
+        };
+
+        Some(self.add_window(window, events, true, Some(Arc::new(hmd))))
+    }
+
+    fn get_primary_resolution(&mut self) -> (u32, u32) {
+        self.glfw.with_primary_monitor(|_, display| {
+            let display = display.expect("Could not get primnay display");
+            let vm = display.get_video_mode().expect("Could not get video mode");
+            (vm.width, vm.height)
+        })
+    }
+
+    fn get_primary_position(&mut self) -> (i32, i32) {
+        self.glfw.with_primary_monitor(|_, display| {
+            let display = display.expect("Could not get primnay display");
+            display.get_pos()
+        })
+    }
+
+    fn wait(&mut self) {
+        self.glfw.wait_events();
+        self.poll_gamepads();
+    }
+
+    fn poll(&mut self) {
+        self.glfw.poll_events();
+        self.poll_gamepads();
+    }
+
+    fn set_dead_zone(&mut self, zone: f64) {
+        self.dead_zone = zone.max(0.).min(0.99);
+    }
+
+    fn dead_zone(&self) -> f64 { self.dead_zone }
+
+    fn gamepad_name(&self, device: DeviceId) -> Option<String> {
+        match JOYSTICKS.get(device) {
+            Some(&id) => {
+                let joystick = self.glfw.get_joystick(id);
+                if joystick.is_present() { Some(joystick.get_name()) } else { None }
+            }
+            None => None
+        }
+    }
+
+    /// Gamepads do not belong to a window so their events are returned
+    /// to whoever asks first.
+    fn next_event(&mut self, handle: &InputHandle) -> input::EventGroup {
+        if !self.gamepad_events.is_empty() {
+            return input::EventGroup::Game(self.gamepad_events.remove(0));
+        }
+
+        let evt = self.windows.get_mut(&handle.handle)
+        .map(|rx| {
+            // this is a hack to inject the correct size into the event buffer
+            match rx.forced_event.take() {
+                Some(evt) => return input::Event::from_glfw(evt),
+                None => ()
+            };
+            if !rx.queued.is_empty() {
+                return rx.queued.remove(0);
+            }
+            for (_, evt) in glfw::flush_messages(&rx.receiver) {
+                if let glfw::WindowEvent::CursorPos(x, y) = evt {
+                    if let Some((last_x, last_y)) = rx.cursor {
+                        rx.queued.push(input::EventGroup::Game(input::Event::Motion(x - last_x, y - last_y)));
+                    }
+                    rx.cursor = Some((x, y));
+                }
+                let evt = input::Event::from_glfw(evt);
+                if evt != input::EventGroup::Nop {
+                    return evt;
+                }
+            }
+            input::EventGroup::Nop
+        });
+
+        match evt {
+            Some(e) => e,
+            _ => input::EventGroup::Nop
+        }
+    }
+
+    fn should_close(&mut self, handle: &InputHandle) -> bool {
+        let should_close = self.windows.get_mut(&handle.handle)
+            .map(|win| win.window.should_close());
+
+        if let Some(x) = should_close {
+            x
+        } else {
+            true
+        }
+    }
+
+    fn set_title(&mut self, handle: &InputHandle, title: String) {
+        self.windows.get_mut(&handle.handle)
+            .map(|win| {
+                if title != win.title {
+                    win.window.set_title(&title[]);
+                    win.title = title.clone();
+                }
+            });
+    }
+
+    fn apply_io_state(&mut self, handle: &InputHandle, io: &IoState) {
+        self.set_title(handle, io.window_title.clone());
+
+        let win = match self.windows.get_mut(&handle.handle) {
+            Some(win) => win,
+            None => return
+        };
+
+        let mode = if io.capture_mouse {
+            glfw::CursorMode::Disabled
+        } else if io.show_mouse {
+            glfw::CursorMode::Normal
+        } else {
+            glfw::CursorMode::Hidden
+        };
+        if mode != win.cursor_mode {
+            win.window.set_cursor_mode(mode);
+            win.cursor_mode = mode;
+            // the cursor jumps when it is captured or released
+            win.cursor = None;
+        }
+
+        // an invalid image is skipped and the previous cursor is kept
+        if io.cursor_image != win.cursor_image {
+            match io.cursor_image {
+                Some(ref image) if !image.is_valid() => (),
+                ref image => win.window.set_cursor(image.as_ref().map(|i| i.to_glfw()))
+            }
+            win.cursor_image = io.cursor_image.clone();
+        }
+
+        win.vsync.store(io.vsync, Ordering::Relaxed);
+
+        let display = (io.fullscreen, io.monitor, io.resolution);
+        if display != win.display {
+            win.display = display;
+            let window = &mut win.window;
+            self.glfw.with_connected_monitors(|_, monitors| {
+                if io.fullscreen {
+                    let monitor = io.monitor.and_then(|i| monitors.get(i)).or(monitors.first());
+                    if let Some(monitor) = monitor {
+                        let mode = monitor.get_video_mode();
+                        let (width, height) = match (io.resolution, mode) {
+                            (Some(size), _) => size,
+                            (None, Some(mode)) => (mode.width, mode.height),
+                            (None, None) => io.size
+                        };
+                        let rate = mode.map(|m| m.refresh_rate);
+                        window.set_monitor(FullScreen(monitor), 0, 0, width, height, rate);
+                    }
+                } else {
+                    let (x, y) = io.position;
+                    let (width, height) = io.resolution.unwrap_or(io.size);
+                    window.set_monitor(Windowed, x, y, width, height, None);
+                }
+            });
+        }
+    }
+
+    fn read_io_state(&mut self, handle: &InputHandle, io: &mut IoState) {
+        io.monitors = self.monitors();
+        if let Some(win) = self.windows.get(&handle.handle) {
+            let (fullscreen, monitor, resolution) = win.display;
+            io.fullscreen = fullscreen;
+            io.monitor = monitor;
+            io.resolution = resolution;
+            io.vsync = win.vsync.load(Ordering::Relaxed);
+        }
+    }
+
+    fn monitors(&mut self) -> Vec<Monitor> {
+        self.glfw.with_connected_monitors(|_, monitors| {
+            monitors.iter().map(|m| {
+                Monitor {
+                    name: m.get_name(),
+                    position: m.get_pos(),
+                    physical_size: m.get_physical_size(),
+                    mode: m.get_video_mode().map(|v| VideoMode::from_glfw(&v)),
+                    modes: m.get_video_modes().iter().map(|v| VideoMode::from_glfw(v)).collect()
+                }
+            }).collect()
+        })
+    }
+
+    fn configure_window(&mut self, handle: &InputHandle, config: &WindowConfig) {
+        self.set_title(handle, config.title.clone());
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            let size = Some(config.size);
+            if win.display.2 != size {
+                let (width, height) = config.size;
+                win.window.set_size(width as i32, height as i32);
+                win.display.2 = size;
+            }
+        }
+    }
+
+    fn set_vsync(&mut self, handle: &InputHandle, vsync: bool) {
+        if let Some(win) = self.windows.get(&handle.handle) {
+            win.vsync.store(vsync, Ordering::Relaxed);
+        }
+    }
+
+    fn close_window(&mut self, handle: &InputHandle) {
+        self.windows.remove(&handle.handle);
+    }
+
+    fn framebuffer_size(&self, handle: &InputHandle) -> (u32, u32) {
+        match self.windows.get(&handle.handle) {
+            Some(win) => {
+                let (w, h) = win.window.get_framebuffer_size();
+                (w as u32, h as u32)
+            }
+            None => (0, 0)
+        }
+    }
+
+    fn set_window_position(&mut self, window: &Window, pos: (i32, i32)) {
+        let (w, h) = pos;
+        match self.windows.get_mut(&window.handle.handle) {
+            Some(win) => win.window.set_pos(w, h),
+            None => ()
+        }
+    }
+
+    fn get_framebuffer_size(&mut self, window: &Window) -> (i32, i32) {
+        match self.windows.get_mut(&window.handle.handle) {
+            Some(win) => win.window.get_framebuffer_size(),
+            None => (0, 0)
+        }
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const libc::c_void {
+        self.glfw.get_proc_address_raw(name)
+    }
+}
+
+impl CursorImage {
+    /// The image must be valid, see `is_valid`
+    fn to_glfw(&self) -> glfw::Cursor {
+        let pixels = self.pixels.chunks(4).map(|p| {
+            (p[0] as u32) | (p[1] as u32) << 8 | (p[2] as u32) << 16 | (p[3] as u32) << 24
+        }).collect();
+        let image = glfw::PixelImage {
+            width: self.width,
+            height: self.height,
+            pixels: pixels
+        };
+        let (x, y) = self.hotspot;
+        glfw::Cursor::create(image, x, y)
+    }
+}
+
+impl VideoMode {
+    fn from_glfw(mode: &glfw::VidMode) -> VideoMode {
+        VideoMode {
+            width: mode.width,
+            height: mode.height,
+            refresh_rate: mode.refresh_rate,
+            bits: (mode.red_bits, mode.green_bits, mode.blue_bits)
+        }
+    }
+}
@@ -14,6 +14,8 @@
 
 use glfw;
 
+use gamepad::{DeviceId, Axis};
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, RustcDecodable, RustcEncodable, Copy)]
 pub enum Button {
     KeyboardSpace,
@@ -143,7 +145,41 @@ pub enum Button {
     MouseExt1,
     MouseExt2,
     MouseExt3,
-    MouseExt4
+    MouseExt4,
+    GamepadA(DeviceId),
+    GamepadB(DeviceId),
+    GamepadX(DeviceId),
+    GamepadY(DeviceId),
+    GamepadLeftBumper(DeviceId),
+    GamepadRightBumper(DeviceId),
+    GamepadBack(DeviceId),
+    GamepadStart(DeviceId),
+    GamepadGuide(DeviceId),
+    GamepadLeftStick(DeviceId),
+    GamepadRightStick(DeviceId),
+    GamepadUp(DeviceId),
+    GamepadDown(DeviceId),
+    GamepadLeft(DeviceId),
+    GamepadRight(DeviceId),
+    /// Held while a finger is on the screen, see `Event::Touch`
+    Touch(PointerId)
+}
+
+/// Identifies one of the pointers that is moving over the window. The
+/// mouse is always `MOUSE_POINTER`, each finger on a touch screen gets a
+/// new id when it touches down.
+pub type PointerId = u32;
+
+pub const MOUSE_POINTER: PointerId = 0;
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, RustcDecodable, RustcEncodable, Copy)]
+pub enum TouchPhase {
+    Begin,
+    Move,
+    End,
+    /// the touch was taken away by the system, treat it like `End`
+    /// but do not act on it
+    Cancel
 }
 
 fn from_glfw_key(key: glfw::Key) -> Button {
@@ -284,12 +320,59 @@ pub fn from_glfw_mouse_button(key: glfw::MouseButton) -> Button {
     };
 }
 
-#[derive(Clone, Debug, PartialEq, Copy)]
+/// The modifier keys that were held when a button changed
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, RustcDecodable, RustcEncodable, Copy)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub super_key: bool
+}
+
+impl Modifiers {
+    pub fn none() -> Modifiers {
+        Modifiers {
+            shift: false,
+            control: false,
+            alt: false,
+            super_key: false
+        }
+    }
+
+    pub fn from_glfw(mods: glfw::Modifiers) -> Modifiers {
+        Modifiers {
+            shift: mods.contains(glfw::Shift),
+            control: mods.contains(glfw::Control),
+            alt: mods.contains(glfw::Alt),
+            super_key: mods.contains(glfw::Super)
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Modifiers::none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable, RustcEncodable, Copy)]
 pub enum Event {
-    ButtonDown(Button),
-    ButtonUp(Button),
-    Move(f64, f64),
+    ButtonDown(Button, Modifiers),
+    ButtonUp(Button, Modifiers),
+    /// A key is being held and the OS is repeating it
+    ButtonRepeat(Button, Modifiers),
+    /// A character of text that was typed, this follows the keyboard
+    /// layout and input method rather than the key that was pressed
+    Char(char),
+    Move(PointerId, f64, f64),
+    /// How far the mouse moved since the last `Move`, unlike `Move` this
+    /// does not stop at the edge of the screen while the mouse is captured
+    Motion(f64, f64),
     Scroll(f64, f64),
+    /// A finger touched, moved on or left the screen at a position. GLFW
+    /// does not report touches, they come from other backends or from
+    /// scripted events.
+    Touch(PointerId, TouchPhase, f64, f64),
+    /// The position of a gamepad axis after the dead zone was applied
+    Axis(DeviceId, Axis, f64),
     Cadance(f64)
 }
 
@@ -310,20 +393,26 @@ pub enum EventGroup {
 impl Event {
     pub fn from_glfw(evt: glfw::WindowEvent) -> EventGroup {
         match evt {
-            glfw::WindowEvent::MouseButton(button, glfw::Action::Press, _) => {
-                EventGroup::Game(Event::ButtonDown(from_glfw_mouse_button(button)))
+            glfw::WindowEvent::MouseButton(button, glfw::Action::Press, mods) => {
+                EventGroup::Game(Event::ButtonDown(from_glfw_mouse_button(button), Modifiers::from_glfw(mods)))
+            }
+            glfw::WindowEvent::MouseButton(button, glfw::Action::Release, mods) => {
+                EventGroup::Game(Event::ButtonUp(from_glfw_mouse_button(button), Modifiers::from_glfw(mods)))
             }
-            glfw::WindowEvent::MouseButton(button, glfw::Action::Release, _) => {
-                EventGroup::Game(Event::ButtonUp(from_glfw_mouse_button(button)))
+            glfw::WindowEvent::Key(button, _, glfw::Action::Press, mods) => {
+                EventGroup::Game(Event::ButtonDown(from_glfw_key(button), Modifiers::from_glfw(mods)))
             }
-            glfw::WindowEvent::Key(button, _, glfw::Action::Press, _) => {
-                EventGroup::Game(Event::ButtonDown(from_glfw_key(button)))
+            glfw::WindowEvent::Key(button, _, glfw::Action::Release, mods) => {
+                EventGroup::Game(Event::ButtonUp(from_glfw_key(button), Modifiers::from_glfw(mods)))
             }
-            glfw::WindowEvent::Key(button, _, glfw::Action::Release, _) => {
-                EventGroup::Game(Event::ButtonUp(from_glfw_key(button)))
+            glfw::WindowEvent::Key(button, _, glfw::Action::Repeat, mods) => {
+                EventGroup::Game(Event::ButtonRepeat(from_glfw_key(button), Modifiers::from_glfw(mods)))
+            }
+            glfw::WindowEvent::Char(c) => {
+                EventGroup::Game(Event::Char(c))
             }
             glfw::WindowEvent::CursorPos(x, y) => {
-                EventGroup::Game(Event::Move(x, y))
+                EventGroup::Game(Event::Move(MOUSE_POINTER, x, y))
             }
             glfw::WindowEvent::Scroll(x, y) => {
                 EventGroup::Game(Event::Scroll(x, y))
@@ -337,10 +426,8 @@ impl Event {
             glfw::WindowEvent::FramebufferSize(x, y) => {
                 EventGroup::Window(WindowEvent::Size(x as u32, y as u32))
             }
-            x => {
-                println!("unhandled {:?}", x);
-                EventGroup::Nop
-            }
+            // the rest are not used by the engine
+            _ => EventGroup::Nop
         }
     }
 }
@@ -24,329 +24,82 @@ extern crate nice_glfw;
 extern crate ovr;
 extern crate collect;
 extern crate libc;
+extern crate "snowmew-timer" as timer;
 
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
-use std::sync::mpsc::Receiver;
+#[cfg(target_os="linux")]
+use std::ptr;
 #[cfg(target_os="linux")]
 use libc::c_void;
 
-use glfw::{Glfw, Context, RenderContext};
-use glfw::WindowMode::{Windowed, FullScreen};
-use collect::TrieMap;
+use glfw::Glfw;
+use timer::FrameStats;
 
 pub use input::{
     Button,
     Event,
+    Modifiers,
+    PointerId,
+    MOUSE_POINTER,
+    TouchPhase,
     WindowEvent,
     EventGroup
 };
+pub use gamepad::{
+    Axis,
+    DeviceId,
+    Gamepad,
+    dead_zone,
+    stick_dead_zone,
+    DEFAULT_DEAD_ZONE,
+    MAX_DEVICES
+};
+pub use backend::{Backend, Context};
+pub use glfw_backend::GlfwBackend;
+pub use null::NullBackend;
 
 mod input;
+mod gamepad;
+mod backend;
+mod glfw_backend;
+mod null;
 
 
 pub type WindowId = usize;
 
-struct WindowHandle {
-    window: glfw::Window,
-    forced_event: Option<glfw::WindowEvent>,
-    receiver: Receiver<(f64, glfw::WindowEvent)>,
-    title: String
-}
-
+/// Owns the `Backend` the engine uses, all of the backend's methods
+/// can be called on the manager
 pub struct IOManager {
-    glfw: Glfw,
-    ovr: Option<ovr::Ovr>,
-    windows: TrieMap<WindowHandle>,
-    window_id: usize
-}
-
-fn create_window_context(glfw :&mut Glfw, width: u32, height: u32, name: &str, mode: glfw::WindowMode)
-        -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
-
-    nice_glfw::WindowBuilder::new(glfw)
-        .try_modern_context_hints()
-        .size(width, height)
-        .title(name)
-        .mode(mode)
-        .create()
+    backend: Box<Backend>
 }
 
 impl IOManager {
+    /// Use GLFW for the windows and input
     pub fn new(glfw: glfw::Glfw) -> IOManager {
-        IOManager {
-            glfw: glfw,
-            ovr: None,
-            windows: TrieMap::new(),
-            window_id: 0
-        }
-    }
-
-    fn add_window(&mut self, window: glfw::Window, recv: Receiver<(f64, glfw::WindowEvent)>) -> InputHandle {
-        let id = self.window_id;
-        self.window_id += 1;
-
-        let (w, h) = window.get_framebuffer_size();
-
-        self.windows.insert(id, {
-            WindowHandle {
-                window: window,
-                forced_event: Some(glfw::WindowEvent::FramebufferSize(w, h)),
-                receiver: recv,
-                title: "snowmew".to_string()
-            }
-        });
-
-        InputHandle{ handle: id }
-    }
-
-    pub fn window(&mut self, size: (u32, u32)) -> Option<Window> {
-        let (width, height) = size;
-        let win_opt = create_window_context(&mut self.glfw, width, height, "Snowmew", Windowed);
-        let (mut window, events) = match win_opt {
-            Some((window, events)) => (window, events),
-            None => return None
-        };
-        self.glfw.set_swap_interval(1);
-
-        window.set_all_polling(true);
-        window.show();
-        let version = window.get_context_version();
-        let rc = window.render_context();
-        let handle = self.add_window(window, events);
-
-        Some(Window {
-            handle: handle,
-            render: rc,
-            version: (version.major, version.minor),
-            hmd: None,
-            os_spec: WindowOSSpec::new(&self.glfw)
-        })
-    }
-
-    pub fn primary(&mut self, size: (u32, u32)) -> Option<Window> {
-        let screen = {
-            self.glfw.with_primary_monitor(|glfw, display| {
-                let display = display.unwrap();
-                let (width, height) = size;
-                create_window_context(glfw, width, height, "Snowmew FullScreen", FullScreen(display))
-            })
-        };
-
-        match screen {
-            None => None,
-            Some((mut window, events)) => {
-                window.set_all_polling(true);
-                window.show();
-                let version = window.get_context_version();
-                let rc = window.render_context();
-                let handle = self.add_window(window, events);
-
-                Some(Window {
-                    handle: handle,
-                    render: rc,
-                    version: (version.major, version.minor),
-                    hmd: None,
-                    os_spec: WindowOSSpec::new(&self.glfw)
-                })
-            }
-        }
-
-    }
-
-    pub fn get_primary_resolution(&mut self) -> (u32, u32) {
-        self.glfw.with_primary_monitor(|_, display| {
-            let display = display.expect("Could not get primnay display");
-            let vm = display.get_video_mode().expect("Could not get video mode");
-            (vm.width, vm.height)
-        })
-    }
-
-    pub fn get_primary_position(&mut self) -> (i32, i32) {
-        self.glfw.with_primary_monitor(|_, display| {
-            let display = display.expect("Could not get primnay display");
-            display.get_pos()
-        })
-    }
-
-    #[cfg(target_os="linux")]
-    fn create_hmd_window(&mut self, hmd: &ovr::HmdDescription) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
-        let window = self.glfw.with_connected_monitors(|glfw, monitors| {
-            for m in monitors.iter() {
-                let (x, y) = m.get_pos();
-                if x == hmd.window_position.x && 
-                   y == hmd.window_position.y {
-                    let (width, height) = (hmd.resolution.x, hmd.resolution.y);
-                    let win_opt = create_window_context(glfw, width as u32, height as u32, "Snowmew FullScreen", FullScreen(m));
-                    let (window, events) = match win_opt {
-                        Some((window, events)) => (window, events),
-                        None => return None
-                    };
-
-                    return Some((window, events));
-                }
-            }
-            None
-        });
-
-        if window.is_none() {
-            // fallback if we could not guess at the screen
-            let (width, height) = (hmd.resolution.x, hmd.resolution.y);
-            let win_opt = self.glfw.create_window(width as u32, height as u32, "Snowmew", Windowed);
-            let (mut window, events) = match win_opt {
-                Some((window, events)) => (window, events),
-                None => return None
-            };
-
-            // move viewport
-            let (dx, dy) = (hmd.window_position.x, hmd.window_position.y);
-            window.set_pos(dx as i32, dy as i32);
-
-            Some((window, events))
-        } else {
-            window
-        }
-    }
-
-    #[cfg(target_os="macos")]
-    fn create_hmd_window(&mut self, hmd: &ovr::HmdDescription) -> Option<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>)> {
-        self.glfw.with_connected_monitors(|glfw, monitors| {
-            for m in monitors.iter() {
-                if !m.get_name()[].contains("Rift") {
-                    continue;
-                }
-
-                let (width, height) = (hmd.resolution.x, hmd.resolution.y);
-                let win_opt = create_window_context(glfw, width as u32, height as u32, "Snowmew FullScreen", FullScreen(m));
-                let (window, events) = match win_opt {
-                    Some((window, events)) => (window, events),
-                    None => return None
-                };
-
-                return Some((window, events));
-            }
-            None
-        })
-    }
-
-    pub fn hmd(&mut self) -> Option<Window> {
-        if !self.setup_ovr() {
-            return None;
-        }
-
-        let (window, events, rc, hmd) = {
-            let hmd = match self.ovr.as_ref().unwrap().first_hmd() {
-                Some(hmd) => hmd,
-                None => return None
-            };
-            let hmdinfo = hmd.get_description();
-
-            let (mut window, events) = match self.create_hmd_window(&hmdinfo) {
-                Some((window, events)) => (window, events),
-                None => return None
-            };
-
-            window.set_all_polling(true);
-            window.show();
-
-            let rc = window.render_context();
-            (window, events, rc, hmd)
-        };
-
-        let version = window.get_context_version();
-        let handle = self.add_window(window, events);
-
-        Some(Window {
-            handle: handle,
-            render: rc,
-            version: (version.major, version.minor),
-            hmd: Some(Arc::new(hmd)),
-            os_spec: WindowOSSpec::new(&self.glfw)
-        })
-    }
-
-    pub fn wait(&mut self) {
-        self.glfw.wait_events();
+        IOManager::with_backend(Box::new(GlfwBackend::new(glfw)))
     }
 
-    pub fn poll(&mut self) {
-        self.glfw.poll_events();
-    }
-
-    pub fn next_event(&mut self, handle: &InputHandle) -> input::EventGroup {
-        let evt = self.windows.get_mut(&handle.handle)
-        .map(|rx| {
-            // this is a hack to inject the correct size into the event buffer
-            match rx.forced_event.take() {
-                Some(evt) => return input::Event::from_glfw(evt),
-                None => ()
-            };
-            for (_, evt) in glfw::flush_messages(&rx.receiver) {
-                let evt = input::Event::from_glfw(evt);
-                if evt != input::EventGroup::Nop {
-                    return evt;
-                }
-            }
-            input::EventGroup::Nop
-        });
-
-        match evt {
-            Some(e) => e,
-            _ => input::EventGroup::Nop
-        }
-    }
-
-    pub fn should_close(&mut self, handle: &InputHandle) -> bool {
-        let should_close = self.windows.get_mut(&handle.handle)
-            .map(|win| win.window.should_close());
-
-        if let Some(x) = should_close {
-            x
-        } else {
-            true
+    pub fn with_backend(backend: Box<Backend>) -> IOManager {
+        IOManager {
+            backend: backend
         }
     }
 
-    pub fn set_title(&mut self, handle: &InputHandle, title: String) {
-        self.windows.get_mut(&handle.handle)
-            .map(|win| {
-                if title != win.title {
-                    win.window.set_title(&title[]);
-                    win.title = title.clone();
-                }
-            });
+    /// Run without a display, see `NullBackend`
+    pub fn null(backend: NullBackend) -> IOManager {
+        IOManager::with_backend(Box::new(backend))
     }
+}
 
-    fn setup_ovr(&mut self) -> bool {
-        if self.ovr.is_some() &&
-           self.ovr.as_ref().unwrap().detect() > 0 {
-            return true;
-        }
-
-        if self.ovr.is_none() {
-            self.ovr = ovr::Ovr::init();
-        }
-
-        self.ovr.is_some() && self.ovr.as_ref().unwrap().detect() > 0
-    }
+impl Deref for IOManager {
+    type Target = Backend;
 
-    pub fn set_window_position(&mut self, window: &Window, pos: (i32, i32)) {
-        let (w, h) = pos;
-        match self.windows.get_mut(&window.handle.handle) {
-            Some(win) => win.window.set_pos(w, h),
-            None => ()
-        }
-    }
-
-    pub fn get_framebuffer_size(&mut self, window: &Window) -> (i32, i32) {
-        match self.windows.get_mut(&window.handle.handle) {
-            Some(win) => win.window.get_framebuffer_size(),
-            None => (0, 0)
-        }
-    }
+    fn deref(&self) -> &Backend { &*self.backend }
+}
 
-    pub fn get_proc_address(&self, name: &str) -> *const ::libc::c_void {
-        self.glfw.get_proc_address_raw(name)
-    }
+impl DerefMut for IOManager {
+    fn deref_mut(&mut self) -> &mut Backend { &mut *self.backend }
 }
 
 #[derive(Clone, Copy)]
@@ -354,12 +107,22 @@ pub struct InputHandle {
     handle: usize,
 }
 
+impl InputHandle {
+    /// Used by a `Backend` to name its windows
+    pub fn new(id: usize) -> InputHandle {
+        InputHandle { handle: id }
+    }
+
+    pub fn id(&self) -> usize { self.handle }
+}
+
 #[cfg(target_os="macos")]
 struct WindowOSSpec;
 
 #[cfg(target_os="macos")]
 impl WindowOSSpec {
     fn new(_: &Glfw) -> WindowOSSpec {WindowOSSpec}
+    fn none() -> WindowOSSpec {WindowOSSpec}
 }
 
 #[cfg(target_os="linux")]
@@ -374,25 +137,43 @@ impl WindowOSSpec {
             display: glfw.get_x11_display()
         }
     }
+
+    fn none() -> WindowOSSpec {
+        WindowOSSpec {
+            display: ptr::null_mut()
+        }
+    }
 }
 
 unsafe impl Send for WindowOSSpec {}
 
 pub struct Window {
     handle: InputHandle,
-    render: RenderContext,
+    context: Box<Context+Send>,
     version: (u64, u64),
     hmd: Option<Arc<ovr::Hmd>>,
     os_spec: WindowOSSpec
 }
 
 impl Window {
+    /// A window for a `Backend` that is not part of this crate, it has
+    /// no hmd and no X11 display
+    pub fn new(handle: InputHandle, context: Box<Context+Send>, version: (u64, u64)) -> Window {
+        Window {
+            handle: handle,
+            context: context,
+            version: version,
+            hmd: None,
+            os_spec: WindowOSSpec::none()
+        }
+    }
+
     pub fn swap_buffers(&mut self) {
-        self.render.swap_buffers()
+        self.context.swap_buffers()
     }
 
     pub fn make_context_current(&mut self) {
-        self.render.make_current()
+        self.context.make_current()
     }
 
     pub fn get_context_version(&self) -> (u64, u64) {
@@ -411,21 +192,113 @@ impl Window {
         self.hmd.as_ref().expect("no hmd device found!").clone()
     }
 
-    /// Wrapper for `glfwGetGLXContext`
+    /// Wrapper for `glfwGetGLXContext`, this is null if the window was
+    /// not made by GLFW
     #[cfg(target_os="linux")]
     pub fn get_x11_display(&self) -> *mut c_void {
         self.os_spec.display
     }
 }
 
+/// An RGBA image to draw in place of the system cursor
+#[derive(Clone, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub struct CursorImage {
+    pub width: u32,
+    pub height: u32,
+    /// the pixel of the image that points at the cursor position
+    pub hotspot: (u32, u32),
+    /// four bytes per pixel row by row starting at the top left
+    pub pixels: Vec<u8>
+}
+
+impl CursorImage {
+    /// true if there are four bytes for every pixel
+    pub fn is_valid(&self) -> bool {
+        self.pixels.len() as u64 == self.width as u64 * self.height as u64 * 4
+    }
+}
+
+
+/// A resolution that a monitor supports
+#[derive(Copy, Clone, PartialEq, Eq, Debug, RustcEncodable, RustcDecodable)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u32,
+    /// the red, green and blue bits of each pixel
+    pub bits: (u32, u32, u32)
+}
+
+
+#[derive(Clone, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub struct Monitor {
+    pub name: String,
+    /// the top left of the monitor on the desktop
+    pub position: (i32, i32),
+    /// the size in millimeters
+    pub physical_size: (i32, i32),
+    /// the mode the monitor is in
+    pub mode: Option<VideoMode>,
+    pub modes: Vec<VideoMode>
+}
+
+/// A window that is opened next to the main window
+#[derive(Clone, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub struct WindowConfig {
+    /// names the window for `Renderable::set_view`, each window needs
+    /// its own id
+    pub id: usize,
+    pub title: String,
+    pub size: (u32, u32),
+    /// the position of the window when it is opened
+    pub position: Option<(i32, i32)>
+}
+
+impl WindowConfig {
+    pub fn new(id: usize, title: &str, size: (u32, u32)) -> WindowConfig {
+        WindowConfig {
+            id: id,
+            title: title.to_string(),
+            size: size,
+            position: None
+        }
+    }
+}
+
 #[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct IoState {
     pub render_size: (u32, u32),
     pub size: (u32, u32),
     pub position: (i32, i32),
+    /// draw the cursor while it is over the window
     pub show_mouse: bool,
+    /// hide the cursor and keep it in the window, use `Event::Motion` to
+    /// follow the mouse while it is captured
+    pub capture_mouse: bool,
+    /// draw this in place of the system cursor
+    pub cursor_image: Option<CursorImage>,
     pub mouse_over: bool,
-    pub window_title: String
+    pub window_title: String,
+    pub fullscreen: bool,
+    /// the index in `monitors` of the monitor to go fullscreen on,
+    /// the primary monitor is used if this is not set
+    pub monitor: Option<usize>,
+    /// the size of the window, or the video mode when fullscreen. If this
+    /// is not set a fullscreen window uses the monitor's current mode.
+    pub resolution: Option<(u32, u32)>,
+    pub vsync: bool,
+    /// the connected monitors, this is filled in by the engine
+    pub monitors: Vec<Monitor>,
+    /// extra windows, each one draws the `View` with the same id from
+    /// the render data. Removing a window closes it, and a window the
+    /// player closes or that could not be opened is removed.
+    pub windows: Vec<WindowConfig>,
+    /// how fast game time passes, see `LoopPolicy::time_scale`. The
+    /// engine reads this after every frame so the game can slow down or
+    /// pause itself.
+    pub time_scale: f64,
+    /// the timing of the frames, this is filled in by the engine
+    pub frame_stats: FrameStats
 }
 
 impl IoState {
@@ -435,8 +308,18 @@ impl IoState {
             size: (800, 600),
             position: (0, 0),
             show_mouse: true,
+            capture_mouse: false,
+            cursor_image: None,
             mouse_over: false,
-            window_title: "snowmew".to_string()
+            window_title: "snowmew".to_string(),
+            fullscreen: false,
+            monitor: None,
+            resolution: None,
+            vsync: true,
+            monitors: Vec::new(),
+            windows: Vec::new(),
+            time_scale: 1.,
+            frame_stats: FrameStats::new()
         }
     }
 
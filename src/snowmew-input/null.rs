@@ -0,0 +1,234 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::ptr;
+use std::num::Float;
+use std::collections::BTreeMap;
+
+use libc;
+
+use input::{EventGroup, WindowEvent};
+use backend::{Backend, Context};
+use {Window, WindowOSSpec, InputHandle, IoState, Monitor, WindowConfig, DEFAULT_DEAD_ZONE};
+
+/// A context with nothing to draw to
+struct NullContext;
+
+impl Context for NullContext {
+    fn make_current(&mut self) {}
+    fn swap_buffers(&mut self) {}
+}
+
+struct NullWindow {
+    size: (u32, u32),
+    title: String,
+    queued: Vec<EventGroup>,
+    vsync: bool,
+    closed: bool
+}
+
+/// A backend without a display. Its windows are only bookkeeping, the
+/// events come from a script that hands out one frame of events on
+/// each poll. They are sent to the first window that is open.
+pub struct NullBackend {
+    frames: Vec<Vec<EventGroup>>,
+    frame: usize,
+    windows: BTreeMap<usize, NullWindow>,
+    window_id: usize,
+    resolution: (u32, u32),
+    close_when_done: bool,
+    dead_zone: f64
+}
+
+impl NullBackend {
+    /// A backend with an empty script whose windows close once the
+    /// script has been played
+    pub fn new() -> NullBackend {
+        NullBackend {
+            frames: Vec::new(),
+            frame: 0,
+            windows: BTreeMap::new(),
+            window_id: 0,
+            resolution: (800, 600),
+            close_when_done: true,
+            dead_zone: DEFAULT_DEAD_ZONE
+        }
+    }
+
+    /// Add a frame of events to the end of the script
+    pub fn push_frame(&mut self, events: Vec<EventGroup>) {
+        self.frames.push(events);
+    }
+
+    /// Add one frame to the script for each entry of `frames`
+    pub fn script(mut self, frames: Vec<Vec<EventGroup>>) -> NullBackend {
+        self.frames.extend(frames.into_iter());
+        self
+    }
+
+    /// Keep the windows open after the script was played, the engine
+    /// then runs until it is stopped some other way
+    pub fn keep_open(mut self) -> NullBackend {
+        self.close_when_done = false;
+        self
+    }
+
+    /// The resolution of the pretend primary monitor
+    pub fn resolution(mut self, resolution: (u32, u32)) -> NullBackend {
+        self.resolution = resolution;
+        self
+    }
+
+    /// The number of frames that have been played
+    pub fn frame(&self) -> usize { self.frame }
+
+    pub fn is_finished(&self) -> bool { self.frame >= self.frames.len() }
+
+    /// The title that was last set on the window
+    pub fn title(&self, handle: &InputHandle) -> Option<&str> {
+        self.windows.get(&handle.handle).map(|w| &w.title[])
+    }
+
+    /// Simulate the player closing the window
+    pub fn close(&mut self, handle: &InputHandle) {
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            win.closed = true;
+        }
+    }
+
+    fn add_window(&mut self, size: (u32, u32)) -> Window {
+        let id = self.window_id;
+        self.window_id += 1;
+
+        let (w, h) = size;
+        self.windows.insert(id, NullWindow {
+            size: size,
+            title: "snowmew".to_string(),
+            queued: vec![EventGroup::Window(WindowEvent::Size(w, h))],
+            vsync: true,
+            closed: false
+        });
+
+        Window {
+            handle: InputHandle { handle: id },
+            context: Box::new(NullContext) as Box<Context+Send>,
+            version: (0, 0),
+            hmd: None,
+            os_spec: WindowOSSpec::none()
+        }
+    }
+}
+
+impl Backend for NullBackend {
+    fn window(&mut self, size: (u32, u32)) -> Option<Window> {
+        Some(self.add_window(size))
+    }
+
+    fn primary(&mut self, _: (u32, u32)) -> Option<Window> {
+        let resolution = self.resolution;
+        Some(self.add_window(resolution))
+    }
+
+    fn get_primary_resolution(&mut self) -> (u32, u32) { self.resolution }
+
+    fn get_primary_position(&mut self) -> (i32, i32) { (0, 0) }
+
+    /// There is nothing to wait for, this is the same as `poll`
+    fn wait(&mut self) {
+        self.poll();
+    }
+
+    fn poll(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        let events = self.frames[self.frame].clone();
+        self.frame += 1;
+        if let Some(win) = self.windows.values_mut().find(|w| !w.closed) {
+            win.queued.extend(events.into_iter());
+        }
+    }
+
+    fn next_event(&mut self, handle: &InputHandle) -> EventGroup {
+        match self.windows.get_mut(&handle.handle) {
+            Some(win) if !win.queued.is_empty() => win.queued.remove(0),
+            _ => EventGroup::Nop
+        }
+    }
+
+    fn should_close(&mut self, handle: &InputHandle) -> bool {
+        let done = self.close_when_done && self.is_finished();
+        match self.windows.get(&handle.handle) {
+            Some(win) => win.closed || (done && win.queued.is_empty()),
+            None => true
+        }
+    }
+
+    fn set_title(&mut self, handle: &InputHandle, title: String) {
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            win.title = title;
+        }
+    }
+
+    fn apply_io_state(&mut self, handle: &InputHandle, io: &IoState) {
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            win.title = io.window_title.clone();
+            win.vsync = io.vsync;
+        }
+    }
+
+    fn read_io_state(&mut self, handle: &InputHandle, io: &mut IoState) {
+        io.monitors = self.monitors();
+        if let Some(win) = self.windows.get(&handle.handle) {
+            io.vsync = win.vsync;
+        }
+    }
+
+    /// There are no monitors
+    fn monitors(&mut self) -> Vec<Monitor> { Vec::new() }
+
+    fn configure_window(&mut self, handle: &InputHandle, config: &WindowConfig) {
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            win.title = config.title.clone();
+            win.size = config.size;
+        }
+    }
+
+    fn set_vsync(&mut self, handle: &InputHandle, vsync: bool) {
+        if let Some(win) = self.windows.get_mut(&handle.handle) {
+            win.vsync = vsync;
+        }
+    }
+
+    fn close_window(&mut self, handle: &InputHandle) {
+        self.windows.remove(&handle.handle);
+    }
+
+    fn framebuffer_size(&self, handle: &InputHandle) -> (u32, u32) {
+        self.windows.get(&handle.handle).map(|w| w.size).unwrap_or((0, 0))
+    }
+
+    /// The windows have no position
+    fn set_window_position(&mut self, _: &Window, _: (i32, i32)) {}
+
+    fn set_dead_zone(&mut self, zone: f64) {
+        self.dead_zone = zone.max(0.).min(0.99);
+    }
+
+    fn dead_zone(&self) -> f64 { self.dead_zone }
+
+    fn get_proc_address(&self, _: &str) -> *const libc::c_void {
+        ptr::null()
+    }
+}
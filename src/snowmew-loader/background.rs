@@ -0,0 +1,180 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread::Thread;
+
+use snowmew::Entity;
+use graphics::Texture;
+
+use error::{LoadError, LoadErrorKind, LoadResult, Mode};
+use mesh::Mesh;
+use texture;
+use Obj;
+
+/// The entities created for an asset that was loaded in the background
+#[derive(Clone, Debug)]
+pub enum Loaded {
+    /// the entity of each object in the obj
+    Obj(HashMap<String, Entity>),
+    Texture(Entity)
+}
+
+/// How much of the queued work is finished
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Progress {
+    /// files that have been read
+    pub done: usize,
+    /// files that are known about, this grows as material libraries
+    /// and textures are found
+    pub total: usize,
+    /// assets that are queued or have not been merged yet
+    pub pending: usize
+}
+
+impl Progress {
+    /// The finished portion of the work between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending == 0
+    }
+}
+
+pub enum Job {
+    Obj(Path, Mode),
+    Texture(Path)
+}
+
+impl Job {
+    fn path(&self) -> Path {
+        match *self {
+            Job::Obj(ref path, _) => path.clone(),
+            Job::Texture(ref path) => path.clone()
+        }
+    }
+}
+
+/// An asset that has been read and decoded but is not part of the
+/// game state yet
+pub enum Decoded {
+    Obj(Obj, Vec<Mesh>),
+    Texture(Texture)
+}
+
+pub enum Message {
+    /// `(path, done, total)` of an asset that is being read
+    Progress(Path, usize, usize),
+    Done(Path, LoadResult<Decoded>),
+    /// a worker can no longer take jobs, the jobs that were queued
+    /// will never finish
+    Stopped
+}
+
+/// Worker threads that read and decode assets
+pub struct Pool {
+    jobs: Sender<Job>,
+    messages: Receiver<Message>
+}
+
+impl Pool {
+    pub fn new(threads: usize) -> Pool {
+        let (jobs, recv) = channel();
+        let (send, messages) = channel();
+        let recv = Arc::new(Mutex::new(recv));
+
+        for _ in range(0, threads) {
+            let (recv, send) = (recv.clone(), send.clone());
+            Thread::spawn(move || worker(recv, send));
+        }
+
+        Pool {
+            jobs: jobs,
+            messages: messages
+        }
+    }
+
+    pub fn send(&self, job: Job) {
+        self.jobs.send(job).unwrap();
+    }
+
+    /// Every message that is waiting, this does not block
+    pub fn ready(&self) -> Vec<Message> {
+        let mut out = Vec::new();
+        while let Ok(m) = self.messages.try_recv() {
+            out.push(m);
+        }
+        out
+    }
+}
+
+fn worker(jobs: Arc<Mutex<Receiver<Job>>>, out: Sender<Message>) {
+    loop {
+        // the lock is only held while waiting for a job
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            // a thread panicked while it held the lock
+            Err(_) => {
+                let _ = out.send(Message::Stopped);
+                return;
+            }
+        };
+        let job = match job {
+            Ok(job) => job,
+            // the manager was dropped
+            Err(_) => return
+        };
+
+        // a panic while decoding only fails this job
+        let path = job.path();
+        let result = {
+            let out = out.clone();
+            Thread::scoped(move || run(job, out)).join()
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(_) => Err(LoadError::new(&path, None, LoadErrorKind::Crashed))
+        };
+
+        if out.send(Message::Done(path, result)).is_err() {
+            return;
+        }
+    }
+}
+
+fn run(job: Job, out: Sender<Message>) -> LoadResult<Decoded> {
+    match job {
+        Job::Obj(path, mode) => {
+            let result = {
+                let p = path.clone();
+                Obj::load_reporting(&path, mode, &mut |done, total| {
+                    let _ = out.send(Message::Progress(p.clone(), done, total));
+                })
+            };
+            result.map(|obj| {
+                let meshes = obj.meshes();
+                Decoded::Obj(obj, meshes)
+            })
+        }
+        Job::Texture(path) => texture::load_texture(&path).map(|t| Decoded::Texture(t))
+    }
+}
@@ -0,0 +1,125 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::old_io::IoError;
+use std::error::Error;
+use std::fmt;
+
+/// What went wrong while loading an asset
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    /// a file could not be read
+    Io(IoError),
+    /// an image could not be decoded
+    Image(String),
+    /// the file was read but its contents are not valid
+    Invalid(String),
+    /// a group uses a material that is not in any material library
+    UnknownMaterial(String),
+    /// a group does not select a material
+    NoMaterial,
+    /// an object has no faces
+    EmptyObject(String),
+    /// the thread that was loading the file panicked
+    Crashed
+}
+
+/// An error together with the file, and if known the line, that caused it
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: Path,
+    pub line: Option<usize>,
+    pub kind: LoadErrorKind
+}
+
+pub type LoadResult<T> = Result<T, LoadError>;
+
+/// How the loader reacts to a broken asset
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mode {
+    /// fail on the first error
+    Strict,
+    /// replace anything that is missing with a default material or a
+    /// checkerboard texture and keep a warning for each replacement
+    Lenient
+}
+
+impl LoadError {
+    pub fn new(path: &Path, line: Option<usize>, kind: LoadErrorKind) -> LoadError {
+        LoadError {
+            path: path.clone(),
+            line: line,
+            kind: kind
+        }
+    }
+
+    pub fn io(path: &Path, err: IoError) -> LoadError {
+        LoadError::new(path, None, LoadErrorKind::Io(err))
+    }
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadErrorKind::Io(ref e) => write!(f, "{}", e),
+            LoadErrorKind::Image(ref e) => write!(f, "failed to decode image: {}", e),
+            LoadErrorKind::Invalid(ref e) => write!(f, "{}", e),
+            LoadErrorKind::UnknownMaterial(ref m) => write!(f, "unknown material `{}`", m),
+            LoadErrorKind::NoMaterial => write!(f, "no material selected"),
+            LoadErrorKind::EmptyObject(ref o) => write!(f, "object `{}` has no faces", o),
+            LoadErrorKind::Crashed => write!(f, "the loader crashed")
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.kind),
+            None => write!(f, "{}: {}", self.path.display(), self.kind)
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match self.kind {
+            LoadErrorKind::Io(_) => "failed to read file",
+            LoadErrorKind::Image(_) => "failed to decode image",
+            LoadErrorKind::Invalid(_) => "invalid file",
+            LoadErrorKind::UnknownMaterial(_) => "unknown material",
+            LoadErrorKind::NoMaterial => "no material selected",
+            LoadErrorKind::EmptyObject(_) => "empty object",
+            LoadErrorKind::Crashed => "loader crashed"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self.kind {
+            LoadErrorKind::Io(ref e) => Some(e as &Error),
+            _ => None
+        }
+    }
+}
+
+/// Find the first line of `text` that is the statement `keyword` and
+/// ends with `arg`, lines are counted from one
+pub fn find_line(text: &str, keywords: &[&str], arg: &str) -> Option<usize> {
+    text.lines().position(|line| {
+        let words: Vec<&str> = line.split(|c: char| c.is_whitespace()).filter(|s| !s.is_empty()).collect();
+        words.len() >= 2 &&
+        keywords.iter().any(|&k| k == words[0]) &&
+        line.trim().ends_with(arg)
+    }).map(|l| l + 1)
+}
@@ -0,0 +1,865 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! A glTF 2.0 importer. Both the json `.gltf` form, with external or
+//! embedded buffers, and the binary `.glb` container are supported.
+//!
+//! Meshes, materials, textures, the node hierarchy, cameras and
+//! `KHR_lights_punctual` lights are imported. Skins and animations are
+//! ignored since there is nothing in the engine to drive them yet.
+
+use std::old_io::{File, IoResult, IoError, InvalidInput};
+use std::collections::HashMap;
+use std::default::Default;
+use std::num::Float;
+use std::f32::consts::PI;
+use std::{str, mem};
+
+use rustc_serialize::json::Json;
+use rustc_serialize::base64::FromBase64;
+use cgmath::{Vector, Vector3, EuclideanVector, Matrix3, Quaternion, Decomposed, ToQuaternion};
+use image::{self, GenericImage};
+
+use snowmew::Entity;
+use graphics::{Graphics, Texture, Format, Sampler, Wrap, Filter, VertexBuffer, Geometry, PbrMaterial};
+use graphics::light::{Light, Point, Spot, Directional};
+use graphics::geometry::{VertexGeoTexNorm, VertexGeoTexNormTan, generate_tangents};
+use position::Positions;
+use error::{LoadError, LoadErrorKind, LoadResult};
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_JSON: u32 = 0x4E4F534A;
+const GLB_BIN: u32 = 0x004E4942;
+
+const MODE_TRIANGLES: u64 = 4;
+
+fn invalid(desc: &'static str, detail: Option<String>) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: desc,
+        detail: detail
+    }
+}
+
+/// Problems with the contents of the file are reported as `InvalidInput`
+fn load_error(path: &Path, err: IoError) -> LoadError {
+    match err.kind {
+        InvalidInput => {
+            let desc = match err.detail {
+                Some(detail) => format!("{}: {}", err.desc, detail),
+                None => err.desc.to_string()
+            };
+            LoadError::new(path, None, LoadErrorKind::Invalid(desc))
+        }
+        _ => LoadError::io(path, err)
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    data[at] as u32 |
+    (data[at + 1] as u32) << 8 |
+    (data[at + 2] as u32) << 16 |
+    (data[at + 3] as u32) << 24
+}
+
+fn index(j: &Json, key: &str) -> Option<usize> {
+    j.find(key).and_then(|v| v.as_u64()).map(|v| v as usize)
+}
+
+fn number(j: &Json, key: &str) -> Option<f32> {
+    j.find(key).and_then(|v| v.as_f64()).map(|v| v as f32)
+}
+
+fn floats(j: &Json, key: &str) -> Option<Vec<f32>> {
+    j.find(key)
+     .and_then(|v| v.as_array())
+     .map(|a| a.iter().map(|v| v.as_f64().unwrap_or(0.) as f32).collect())
+}
+
+/// An array of exactly `len` numbers, `default` if the key is missing
+fn vector(j: &Json, key: &'static str, default: &[f32]) -> IoResult<Vec<f32>> {
+    match floats(j, key) {
+        Some(v) => {
+            if v.len() != default.len() {
+                return Err(invalid("array has the wrong length", Some(key.to_string())));
+            }
+            Ok(v)
+        }
+        None => Ok(default.to_vec())
+    }
+}
+
+/// Decode the `%xx` escapes used in relative uris
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = str::from_utf8(&bytes[i+1..i+3]).ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out[]).into_owned()
+}
+
+/// Smooth vertex normals for meshes that do not supply their own
+fn generate_normals(position: &[[f32; 3]], index: &[u32]) -> Vec<[f32; 3]> {
+    let mut normal: Vec<Vector3<f32>> = position.iter().map(|_| Vector3::new(0., 0., 0.)).collect();
+    for tri in index.chunks(3) {
+        if tri.len() != 3 {
+            break;
+        }
+        let p: Vec<Vector3<f32>> = tri.iter().map(|&i| {
+            let p = position[i as usize];
+            Vector3::new(p[0], p[1], p[2])
+        }).collect();
+        // the cross product is weighted by the area of the triangle
+        let n = p[1].sub_v(&p[0]).cross(&p[2].sub_v(&p[0]));
+        for &i in tri.iter() {
+            normal[i as usize].add_self_v(&n);
+        }
+    }
+    normal.iter().map(|n| {
+        if n.length2() > 0. {
+            let n = n.normalize();
+            [n.x, n.y, n.z]
+        } else {
+            [0., 1., 0.]
+        }
+    }).collect()
+}
+
+/// Everything the importer created for a file
+pub struct GltfScene {
+    /// the entities created for the nodes of the default scene
+    pub roots: Vec<Entity>,
+    /// every node that was given a name
+    pub names: HashMap<String, Entity>,
+    /// nodes that hold a camera, pass the node to `set_camera` to look
+    /// through it
+    pub cameras: Vec<GltfCamera>,
+    /// lights created from `KHR_lights_punctual`
+    pub lights: Vec<Entity>
+}
+
+/// The lens of a camera, distances are in scene units
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// `yfov` is in radians, without an aspect ratio the one of the
+    /// window should be used, without `zfar` the projection is infinite
+    Perspective { yfov: f32, aspect_ratio: Option<f32>, znear: f32, zfar: Option<f32> },
+    /// `xmag` and `ymag` are half the width and height of the view
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 }
+}
+
+/// A node that holds a camera, the camera looks down the node's -z axis
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GltfCamera {
+    pub node: Entity,
+    pub projection: Projection
+}
+
+/// The shared resources that nodes reference by index
+struct Resources {
+    materials: Vec<Entity>,
+    default_material: Option<Entity>,
+    meshes: Vec<Vec<(Entity, Option<usize>)>>,
+    cameras: Vec<Projection>,
+    lights: Vec<Option<Light>>
+}
+
+pub struct Gltf {
+    path: Path,
+    json: Json,
+    buffers: Vec<Vec<u8>>
+}
+
+impl Gltf {
+    pub fn load(path: &Path) -> LoadResult<Gltf> {
+        Gltf::read(path).map_err(|e| load_error(path, e))
+    }
+
+    fn read(path: &Path) -> IoResult<Gltf> {
+        let data = try!(File::open(path).read_to_end());
+        if data.len() >= 12 && read_u32(&data[], 0) == GLB_MAGIC {
+            Gltf::load_glb(path, &data[])
+        } else {
+            let text = try!(str::from_utf8(&data[])
+                .map_err(|_| invalid("gltf is not valid utf-8", None)));
+            let json = try!(Json::from_str(text)
+                .map_err(|e| invalid("failed to parse gltf", Some(format!("{:?}", e)))));
+            Gltf::new(path, json, None)
+        }
+    }
+
+    fn load_glb(path: &Path, data: &[u8]) -> IoResult<Gltf> {
+        let length = read_u32(data, 8) as usize;
+        if length > data.len() {
+            return Err(invalid("glb file is truncated", None));
+        }
+
+        let mut json = None;
+        let mut bin = None;
+        let mut at = 12;
+        while at + 8 <= length {
+            let len = read_u32(data, at) as usize;
+            let kind = read_u32(data, at + 4);
+            let start = at + 8;
+            if start + len > length {
+                return Err(invalid("glb chunk is truncated", None));
+            }
+            let chunk = &data[start..start + len];
+            match kind {
+                GLB_JSON => {
+                    let text = try!(str::from_utf8(chunk)
+                        .map_err(|_| invalid("glb json chunk is not valid utf-8", None)));
+                    json = Some(try!(Json::from_str(text)
+                        .map_err(|e| invalid("failed to parse glb json chunk", Some(format!("{:?}", e))))));
+                }
+                // only the first binary chunk is defined by the spec
+                GLB_BIN if bin.is_none() => bin = Some(chunk.to_vec()),
+                _ => ()
+            }
+            // chunks are padded to four bytes
+            at = start + ((len + 3) & !3);
+        }
+
+        match json {
+            Some(json) => Gltf::new(path, json, bin),
+            None => Err(invalid("glb has no json chunk", None))
+        }
+    }
+
+    fn new(path: &Path, json: Json, mut bin: Option<Vec<u8>>) -> IoResult<Gltf> {
+        let mut buffers = Vec::new();
+        if let Some(list) = json.find("buffers").and_then(|b| b.as_array()) {
+            for b in list.iter() {
+                let data = match b.find("uri").and_then(|u| u.as_string()) {
+                    Some(uri) => try!(Gltf::read_uri(path, uri)),
+                    None => match bin.take() {
+                        Some(bin) => bin,
+                        None => return Err(invalid("buffer has no uri", None))
+                    }
+                };
+                buffers.push(data);
+            }
+        }
+
+        Ok(Gltf {
+            path: path.clone(),
+            json: json,
+            buffers: buffers
+        })
+    }
+
+    /// Read a `data:` uri or a file relative to the gltf
+    fn read_uri(path: &Path, uri: &str) -> IoResult<Vec<u8>> {
+        if uri.starts_with("data:") {
+            match uri.find(',') {
+                Some(i) if uri[..i].ends_with(";base64") => {
+                    uri[i+1..].from_base64()
+                        .map_err(|_| invalid("invalid base64 in data uri", None))
+                }
+                _ => Err(invalid("only base64 data uris are supported", Some(uri.to_string())))
+            }
+        } else {
+            let mut p = path.clone();
+            p.pop();
+            File::open(&p.join(decode_uri(uri))).read_to_end()
+        }
+    }
+
+    fn get(&self, kind: &str, idx: usize) -> IoResult<&Json> {
+        self.json.find(kind)
+            .and_then(|l| l.as_array())
+            .and_then(|l| l.get(idx))
+            .ok_or_else(|| invalid("index out of range", Some(format!("{}[{}]", kind, idx))))
+    }
+
+    fn count(&self, kind: &str) -> usize {
+        self.json.find(kind)
+            .and_then(|l| l.as_array())
+            .map(|l| l.len())
+            .unwrap_or(0)
+    }
+
+    /// Find the bytes of a buffer view
+    fn view(&self, idx: usize) -> IoResult<&[u8]> {
+        let view = try!(self.get("bufferViews", idx));
+        let buffer = try!(index(view, "buffer")
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| invalid("buffer view has an invalid buffer", None)));
+        let offset = index(view, "byteOffset").unwrap_or(0);
+        let len = index(view, "byteLength").unwrap_or(0);
+        if offset + len > buffer.len() {
+            return Err(invalid("buffer view is out of range", None));
+        }
+        Ok(&buffer[offset..offset + len])
+    }
+
+    /// Read each component of an accessor as its raw integer bits,
+    /// returns the values and the number of components per element
+    fn accessor(&self, idx: usize) -> IoResult<(Vec<u32>, usize, u64, bool)> {
+        let acc = try!(self.get("accessors", idx));
+        let count = index(acc, "count").unwrap_or(0);
+        let comps = match acc.find("type").and_then(|t| t.as_string()) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid("unknown accessor type", None))
+        };
+        let kind = acc.find("componentType").and_then(|t| t.as_u64()).unwrap_or(0);
+        let size = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(invalid("unknown accessor component type", None))
+        };
+        let normalized = acc.find("normalized").and_then(|n| n.as_boolean()).unwrap_or(false);
+
+        // an accessor without a view is all zeros
+        let view_idx = match index(acc, "bufferView") {
+            Some(v) => v,
+            None => return Ok(((0..count * comps).map(|_| 0).collect(), comps, kind, normalized))
+        };
+
+        let data = try!(self.view(view_idx));
+        let stride = try!(self.get("bufferViews", view_idx))
+            .find("byteStride")
+            .and_then(|s| s.as_u64())
+            .map(|s| s as usize)
+            .unwrap_or(comps * size);
+        let offset = index(acc, "byteOffset").unwrap_or(0);
+
+        let mut out = Vec::with_capacity(count * comps);
+        for i in (0..count) {
+            for c in (0..comps) {
+                let at = offset + i * stride + c * size;
+                if at + size > data.len() {
+                    return Err(invalid("accessor is out of range", Some(format!("accessors[{}]", idx))));
+                }
+                out.push(match size {
+                    1 => data[at] as u32,
+                    2 => data[at] as u32 | (data[at + 1] as u32) << 8,
+                    _ => read_u32(data, at)
+                });
+            }
+        }
+        Ok((out, comps, kind, normalized))
+    }
+
+    /// Read an accessor whose elements have at least `min` components
+    fn elements(&self, idx: usize, min: usize) -> IoResult<(Vec<f32>, usize)> {
+        let (values, comps) = try!(self.floats(idx));
+        if comps < min {
+            return Err(invalid("accessor has too few components", Some(format!("accessors[{}]", idx))));
+        }
+        Ok((values, comps))
+    }
+
+    /// Read an accessor as floats, integer types are converted and
+    /// normalized if the accessor asks for it
+    fn floats(&self, idx: usize) -> IoResult<(Vec<f32>, usize)> {
+        let (raw, comps, kind, normalized) = try!(self.accessor(idx));
+        let values = raw.into_iter().map(|v| {
+            let (v, max) = match kind {
+                5120 => (v as u8 as i8 as f32, 127.),
+                5121 => (v as f32, 255.),
+                5122 => (v as u16 as i16 as f32, 32767.),
+                5123 => (v as f32, 65535.),
+                5125 => (v as f32, 1.),
+                _ => return unsafe { mem::transmute::<u32, f32>(v) }
+            };
+            if normalized { (v / max).max(-1.) } else { v }
+        }).collect();
+        Ok((values, comps))
+    }
+
+    fn indices(&self, idx: usize) -> IoResult<Vec<u32>> {
+        let (raw, comps, kind, _) = try!(self.accessor(idx));
+        if comps != 1 || kind == 5126 {
+            return Err(invalid("indices must be unsigned integer scalars", None));
+        }
+        Ok(raw)
+    }
+
+    /// Decode an image, colour images are stored as sRGB
+    fn image(&self, idx: usize, srgb: bool) -> IoResult<Texture> {
+        let image = try!(self.get("images", idx));
+        let img = match (image.find("uri").and_then(|u| u.as_string()), index(image, "bufferView")) {
+            (Some(uri), _) if !uri.starts_with("data:") => {
+                let mut p = self.path.clone();
+                p.pop();
+                image::open(&p.join(decode_uri(uri)))
+            }
+            (Some(uri), _) => image::load_from_memory(&try!(Gltf::read_uri(&self.path, uri))[]),
+            (None, Some(view)) => image::load_from_memory(try!(self.view(view))),
+            (None, None) => return Err(invalid("image has no source", None))
+        };
+        let img = try!(img.map_err(|e| invalid("failed to decode image", Some(format!("{:?}", e)))))
+                          .to_rgba();
+        let (w, h) = img.dimensions();
+        let format = if srgb { Format::SRGBA8 } else { Format::RGBA8 };
+        let mut texture = Texture::with_format(w, h, format, img.into_raw());
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    /// Convert a sampler, only `wrapS` is used since textures have a
+    /// single wrap mode
+    fn sampler(&self, idx: Option<usize>) -> IoResult<Sampler> {
+        let sampler = match idx {
+            Some(idx) => try!(self.get("samplers", idx)),
+            None => return Ok(Default::default())
+        };
+        let wrap = match index(sampler, "wrapS") {
+            Some(33071) => Wrap::Clamp,
+            Some(33648) => Wrap::Mirror,
+            _ => Wrap::Repeat
+        };
+        let filter = match (index(sampler, "magFilter"), index(sampler, "minFilter")) {
+            (Some(9728), None) | (_, Some(9728)) | (_, Some(9984)) => Filter::Nearest,
+            (_, Some(9729)) | (_, Some(9985)) => Filter::Bilinear,
+            (_, Some(9986)) | (_, Some(9987)) => Filter::Trilinear,
+            _ => Sampler::default().filter
+        };
+        Ok(Sampler { wrap: wrap, filter: filter })
+    }
+
+    /// Import a texture reference, images are shared between textures
+    /// that use the same sampler and colour space so each one is only
+    /// created once
+    fn texture<G: Graphics>(&self, gd: &mut G, info: Option<&Json>, srgb: bool,
+                            images: &mut HashMap<(usize, Option<usize>, bool), Entity>)
+            -> IoResult<Option<Entity>> {
+        let tex = match info.and_then(|i| index(i, "index")) {
+            Some(t) => try!(self.get("textures", t)),
+            None => return Ok(None)
+        };
+        let source = match index(tex, "source") {
+            Some(s) => s,
+            None => return Ok(None)
+        };
+        let sampler = index(tex, "sampler");
+        if let Some(&id) = images.get(&(source, sampler, srgb)) {
+            return Ok(Some(id));
+        }
+        let mut image = try!(self.image(source, srgb));
+        image.set_sampler(try!(self.sampler(sampler)));
+        let id = gd.new_texture(image);
+        images.insert((source, sampler, srgb), id);
+        Ok(Some(id))
+    }
+
+    fn material<G: Graphics>(&self, gd: &mut G, m: &Json,
+                             images: &mut HashMap<(usize, Option<usize>, bool), Entity>) -> IoResult<Entity> {
+        let mut mat = PbrMaterial::new();
+
+        if let Some(pbr) = m.find("pbrMetallicRoughness") {
+            if let Some(c) = floats(pbr, "baseColorFactor") {
+                if c.len() == 4 {
+                    mat.set_base_color([c[0], c[1], c[2]]);
+                    mat.set_alpha(c[3]);
+                }
+            }
+            if let Some(v) = number(pbr, "metallicFactor") { mat.set_metallic(v); }
+            if let Some(v) = number(pbr, "roughnessFactor") { mat.set_roughness(v); }
+            if let Some(t) = try!(self.texture(gd, pbr.find("baseColorTexture"), true, images)) {
+                mat.set_map_base_color(t);
+            }
+            if let Some(t) = try!(self.texture(gd, pbr.find("metallicRoughnessTexture"), false, images)) {
+                mat.set_map_metallic_roughness(t);
+            }
+        }
+
+        if let Some(c) = floats(m, "emissiveFactor") {
+            if c.len() == 3 {
+                mat.set_emissive([c[0], c[1], c[2]]);
+            }
+        }
+        if let Some(t) = try!(self.texture(gd, m.find("emissiveTexture"), true, images)) {
+            mat.set_map_emissive(t);
+        }
+        if let Some(info) = m.find("normalTexture") {
+            if let Some(v) = number(info, "scale") { mat.set_normal_scale(v); }
+        }
+        if let Some(t) = try!(self.texture(gd, m.find("normalTexture"), false, images)) {
+            mat.set_map_normal(t);
+        }
+        if let Some(info) = m.find("occlusionTexture") {
+            if let Some(v) = number(info, "strength") { mat.set_occlusion_strength(v); }
+        }
+        if let Some(t) = try!(self.texture(gd, m.find("occlusionTexture"), false, images)) {
+            mat.set_map_occlusion(t);
+        }
+
+        // masked materials keep their alpha for the cutoff test, a cutoff
+        // of zero is raised so the material is still masked
+        match m.find("alphaMode").and_then(|a| a.as_string()) {
+            Some("BLEND") => (),
+            Some("MASK") => {
+                let cutoff = number(m, "alphaCutoff").unwrap_or(0.5);
+                mat.set_alpha_cutoff(cutoff.max(1. / 255.));
+            }
+            _ => mat.set_alpha(1.)
+        }
+
+        Ok(gd.new_pbr_material(mat))
+    }
+
+    /// Create the vertex buffer and geometry for a single primitive
+    fn primitive<G: Graphics>(&self, gd: &mut G, p: &Json) -> IoResult<Entity> {
+        if p.find("mode").and_then(|m| m.as_u64()).unwrap_or(MODE_TRIANGLES) != MODE_TRIANGLES {
+            return Err(invalid("only triangle lists are supported", None));
+        }
+        let attr = try!(p.find("attributes").ok_or_else(|| invalid("primitive has no attributes", None)));
+        let position = match index(attr, "POSITION") {
+            Some(a) => try!(self.elements(a, 3)),
+            None => return Err(invalid("primitive has no positions", None))
+        };
+        let position: Vec<[f32; 3]> = position.0.chunks(position.1)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+
+        let indices = match index(p, "indices") {
+            Some(i) => try!(self.indices(i)),
+            None => (0..position.len() as u32).collect()
+        };
+        if indices.iter().any(|&i| i as usize >= position.len()) {
+            return Err(invalid("primitive index is out of range", None));
+        }
+
+        let normal: Vec<[f32; 3]> = match index(attr, "NORMAL") {
+            Some(a) => {
+                let (n, c) = try!(self.elements(a, 3));
+                n.chunks(c).map(|n| [n[0], n[1], n[2]]).collect()
+            }
+            None => generate_normals(&position[], &indices[])
+        };
+        let texture: Vec<[f32; 2]> = match index(attr, "TEXCOORD_0") {
+            Some(a) => {
+                let (t, c) = try!(self.elements(a, 2));
+                t.chunks(c).map(|t| [t[0], t[1]]).collect()
+            }
+            None => position.iter().map(|_| [0., 0.]).collect()
+        };
+        if normal.len() != position.len() || texture.len() != position.len() {
+            return Err(invalid("primitive attributes have different lengths", None));
+        }
+
+        let vertices: Vec<VertexGeoTexNorm> = position.iter()
+            .zip(texture.iter())
+            .zip(normal.iter())
+            .map(|((&p, &t), &n)| {
+                VertexGeoTexNorm {
+                    position: p,
+                    texture: t,
+                    normal: n
+                }
+            }).collect();
+
+        let vertices: Vec<VertexGeoTexNormTan> = match index(attr, "TANGENT") {
+            Some(a) => {
+                let (t, c) = try!(self.elements(a, 3));
+                if t.len() / c != vertices.len() {
+                    return Err(invalid("primitive attributes have different lengths", None));
+                }
+                vertices.iter().zip(t.chunks(c)).map(|(v, t)| {
+                    VertexGeoTexNormTan {
+                        position: v.position,
+                        texture: v.texture,
+                        normal: v.normal,
+                        tangent: [t[0], t[1], t[2], if c > 3 { t[3] } else { 1. }]
+                    }
+                }).collect()
+            }
+            None => generate_tangents(&vertices[], &indices[])
+        };
+
+        let len = indices.len();
+        let vb = gd.new_vertex_buffer(
+            VertexBuffer::new_position_texture_normal_tangent(vertices, indices)
+        );
+        Ok(gd.new_geometry(Geometry::triangles(vb, 0, len)))
+    }
+
+    /// Lights of an unknown type are skipped
+    fn light(j: &Json) -> IoResult<Option<Light>> {
+        let color = try!(vector(j, "color", &[1., 1., 1.]));
+        let color = Vector3::new(color[0], color[1], color[2]);
+        let intensity = number(j, "intensity").unwrap_or(1.);
+
+        // glTF lights shine down -z
+        Ok(match j.find("type").and_then(|t| t.as_string()) {
+            Some("directional") => {
+                Some(Light::Directional(Directional::new(Vector3::new(0., 0., 1.), color, intensity)))
+            }
+            Some("point") => Some(Light::Point(Point::new(color, intensity))),
+            Some("spot") => {
+                let angle = j.find("spot")
+                    .and_then(|s| number(s, "outerConeAngle"))
+                    .unwrap_or(PI / 4.);
+                Some(Light::Spot(Spot::new(Vector3::new(0., 0., -1.), color, intensity, angle)))
+            }
+            _ => None
+        })
+    }
+
+    fn camera(j: &Json) -> IoResult<Projection> {
+        let missing = |&: key: &'static str| invalid("camera is missing a value", Some(key.to_string()));
+        match j.find("type").and_then(|t| t.as_string()) {
+            Some("perspective") => {
+                let p = try!(j.find("perspective").ok_or_else(|| missing("perspective")));
+                Ok(Projection::Perspective {
+                    yfov: try!(number(p, "yfov").ok_or_else(|| missing("yfov"))),
+                    aspect_ratio: number(p, "aspectRatio"),
+                    znear: try!(number(p, "znear").ok_or_else(|| missing("znear"))),
+                    zfar: number(p, "zfar")
+                })
+            }
+            Some("orthographic") => {
+                let o = try!(j.find("orthographic").ok_or_else(|| missing("orthographic")));
+                Ok(Projection::Orthographic {
+                    xmag: try!(number(o, "xmag").ok_or_else(|| missing("xmag"))),
+                    ymag: try!(number(o, "ymag").ok_or_else(|| missing("ymag"))),
+                    znear: try!(number(o, "znear").ok_or_else(|| missing("znear"))),
+                    zfar: try!(number(o, "zfar").ok_or_else(|| missing("zfar")))
+                })
+            }
+            _ => Err(invalid("unknown camera type", None))
+        }
+    }
+
+    /// The engine only has uniform scale, a node that is stretched
+    /// along one axis is an error
+    fn transform(node: &Json) -> IoResult<Decomposed<f32, Vector3<f32>, Quaternion<f32>>> {
+        let uniform = |&: sx: f32, sy: f32, sz: f32| {
+            let (lo, hi) = (sx.min(sy).min(sz), sx.max(sy).max(sz));
+            if hi - lo > hi.abs() * 1e-4 {
+                Err(invalid("non-uniform scale is not supported", None))
+            } else {
+                Ok(hi)
+            }
+        };
+
+        match floats(node, "matrix") {
+            Some(ref m) if m.len() == 16 => {
+                let x = Vector3::new(m[0], m[1], m[2]);
+                let y = Vector3::new(m[4], m[5], m[6]);
+                let z = Vector3::new(m[8], m[9], m[10]);
+                let (sx, sy, sz) = (x.length(), y.length(), z.length());
+                let scale = try!(uniform(sx, sy, sz));
+                let rot = Matrix3::from_cols(x.div_s(sx), y.div_s(sy), z.div_s(sz));
+                Ok(Decomposed {
+                    scale: scale,
+                    rot: rot.to_quaternion(),
+                    disp: Vector3::new(m[12], m[13], m[14])
+                })
+            }
+            Some(_) => Err(invalid("array has the wrong length", Some("matrix".to_string()))),
+            None => {
+                let t = try!(vector(node, "translation", &[0., 0., 0.]));
+                let r = try!(vector(node, "rotation", &[0., 0., 0., 1.]));
+                let s = try!(vector(node, "scale", &[1., 1., 1.]));
+                Ok(Decomposed {
+                    scale: try!(uniform(s[0], s[1], s[2])),
+                    rot: Quaternion::new(r[3], r[0], r[1], r[2]),
+                    disp: Vector3::new(t[0], t[1], t[2])
+                })
+            }
+        }
+    }
+
+    fn node<G: Graphics+Positions>(&self, gd: &mut G, res: &Resources, idx: usize,
+                                   parent: Option<Entity>, delta_parent: Option<Entity>,
+                                   out: &mut GltfScene, depth: usize) -> IoResult<Entity> {
+        if depth > self.count("nodes") {
+            return Err(invalid("node hierarchy contains a cycle", None));
+        }
+
+        let node = try!(self.get("nodes", idx));
+        let transform = try!(Gltf::transform(node));
+        let id = gd.new_object(parent);
+        gd.set_delta(id, delta_parent, transform);
+
+        if let Some(name) = node.find("name").and_then(|n| n.as_string()) {
+            out.names.insert(name.to_string(), id);
+        }
+
+        if let Some(mesh) = index(node, "mesh") {
+            let prims = try!(res.meshes.get(mesh).ok_or_else(|| invalid("node has an invalid mesh", None)));
+            let draw = |&: m: Option<usize>| {
+                m.and_then(|m| res.materials.get(m).map(|&m| m))
+                 .or(res.default_material)
+                 .unwrap()
+            };
+
+            // a mesh with several primitives gets a child per primitive
+            if prims.len() == 1 {
+                gd.set_draw(id, prims[0].0, draw(prims[0].1));
+            } else {
+                for &(geo, mat) in prims.iter() {
+                    let child = gd.new_object(Some(id));
+                    gd.set_delta(child, Some(id), Decomposed {
+                        scale: 1.,
+                        rot: Quaternion::identity(),
+                        disp: Vector3::new(0., 0., 0.)
+                    });
+                    gd.set_draw(child, geo, draw(mat));
+                }
+            }
+        }
+
+        if let Some(camera) = index(node, "camera") {
+            let projection = try!(res.cameras.get(camera).ok_or_else(|| invalid("node has an invalid camera", None)));
+            out.cameras.push(GltfCamera {
+                node: id,
+                projection: *projection
+            });
+        }
+
+        let light = node.find("extensions")
+            .and_then(|e| e.find("KHR_lights_punctual"))
+            .and_then(|l| index(l, "light"))
+            .and_then(|l| res.lights.get(l))
+            .and_then(|l| l.as_ref());
+        if let Some(light) = light {
+            let l = gd.new_light(light.clone());
+            gd.set_delta(l, Some(id), Decomposed {
+                scale: 1.,
+                rot: Quaternion::identity(),
+                disp: Vector3::new(0., 0., 0.)
+            });
+            out.lights.push(l);
+        }
+
+        if let Some(children) = node.find("children").and_then(|c| c.as_array()) {
+            for c in children.iter() {
+                let c = try!(c.as_u64().ok_or_else(|| invalid("invalid child index", None)));
+                try!(self.node(gd, res, c as usize, Some(id), Some(id), out, depth + 1));
+            }
+        }
+
+        Ok(id)
+    }
+
+    /// The nodes of the default scene, if the file has no scenes every
+    /// node that is not a child of another is used
+    fn root_nodes(&self) -> Vec<usize> {
+        let scene = index(&self.json, "scene").unwrap_or(0);
+        let scene = self.json.find("scenes")
+            .and_then(|s| s.as_array())
+            .and_then(|s| s.get(scene));
+
+        match scene.and_then(|s| s.find("nodes")).and_then(|n| n.as_array()) {
+            Some(nodes) => nodes.iter().filter_map(|n| n.as_u64()).map(|n| n as usize).collect(),
+            None => {
+                let count = self.count("nodes");
+                let mut child: Vec<bool> = (0..count).map(|_| false).collect();
+                for i in (0..count) {
+                    let node = self.get("nodes", i).ok();
+                    let children = node.and_then(|n| n.find("children")).and_then(|c| c.as_array());
+                    for c in children.iter().flat_map(|c| c.iter()) {
+                        if let Some(c) = c.as_u64() {
+                            if (c as usize) < count {
+                                child[c as usize] = true;
+                            }
+                        }
+                    }
+                }
+                (0..count).filter(|&i| !child[i]).collect()
+            }
+        }
+    }
+
+    /// Import the default scene. The top level nodes are created as
+    /// children of `parent`, their positions are not relative to it.
+    pub fn import<G: Graphics+Positions>(&self, gd: &mut G, parent: Option<Entity>) -> LoadResult<GltfScene> {
+        self.write(gd, parent).map_err(|e| load_error(&self.path, e))
+    }
+
+    fn write<G: Graphics+Positions>(&self, gd: &mut G, parent: Option<Entity>) -> IoResult<GltfScene> {
+        let mut images = HashMap::new();
+
+        let mut materials = Vec::new();
+        for i in (0..self.count("materials")) {
+            let m = try!(self.get("materials", i));
+            materials.push(try!(self.material(gd, m, &mut images)));
+        }
+
+        let mut meshes = Vec::new();
+        let mut needs_default = false;
+        for i in (0..self.count("meshes")) {
+            let mesh = try!(self.get("meshes", i));
+            let mut prims = Vec::new();
+            if let Some(list) = mesh.find("primitives").and_then(|p| p.as_array()) {
+                for p in list.iter() {
+                    let mat = index(p, "material");
+                    needs_default |= mat.map(|m| m >= materials.len()).unwrap_or(true);
+                    prims.push((try!(self.primitive(gd, p)), mat));
+                }
+            }
+            meshes.push(prims);
+        }
+
+        let lights: IoResult<Vec<Option<Light>>> = self.json.find("extensions")
+            .and_then(|e| e.find("KHR_lights_punctual"))
+            .and_then(|l| l.find("lights"))
+            .and_then(|l| l.as_array())
+            .map(|l| l.iter().map(|l| Gltf::light(l)).collect())
+            .unwrap_or(Ok(Vec::new()));
+        let lights = try!(lights);
+
+        let mut cameras = Vec::new();
+        for i in (0..self.count("cameras")) {
+            cameras.push(try!(Gltf::camera(try!(self.get("cameras", i)))));
+        }
+
+        let res = Resources {
+            materials: materials,
+            default_material: if needs_default {
+                Some(gd.new_pbr_material(PbrMaterial::new()))
+            } else {
+                None
+            },
+            meshes: meshes,
+            cameras: cameras,
+            lights: lights
+        };
+
+        let mut out = GltfScene {
+            roots: Vec::new(),
+            names: HashMap::new(),
+            cameras: Vec::new(),
+            lights: Vec::new()
+        };
+        for n in self.root_nodes().into_iter() {
+            let id = try!(self.node(gd, &res, n, parent, None, &mut out, 0));
+            out.roots.push(id);
+        }
+        Ok(out)
+    }
+}
@@ -17,23 +17,28 @@
 #![feature(collections)]
 #![feature(io)]
 #![feature(path)]
+#![feature(libc)]
+#![feature(os)]
+#![feature(std_misc)]
 
 extern crate collections;
 extern crate cgmath;
 extern crate genmesh;
 extern crate image;
+extern crate libc;
 extern crate "obj" as obj;
+extern crate "rustc-serialize" as rustc_serialize;
 extern crate "snowmew-core" as snowmew;
 extern crate "snowmew-graphics" as graphics;
+extern crate "snowmew-position" as position;
 
-use std::old_io::{BufferedReader, File, IoResult};
+use std::old_io::{BufReader, File};
 use std::collections::HashMap;
 
-use snowmew::common::Common;
 use snowmew::Entity;
-use graphics::{Graphics};
-use graphics::geometry::VertexGeoTexNorm;
-
+use graphics::{Graphics, Texture};
+use graphics::geometry::{VertexGeoTexNorm, generate_tangents};
+use position::Positions;
 
 use genmesh::{
     Triangulate,
@@ -43,103 +48,394 @@ use genmesh::{
     Indexer
 };
 
+pub use gltf::{Gltf, GltfScene, GltfCamera, Projection};
+pub use error::{LoadError, LoadErrorKind, LoadResult, Mode};
+pub use manager::AssetManager;
+pub use watch::Watcher;
+pub use background::{Loaded, Progress};
+pub use texture::{load_texture, load_cube, load_equirect, Usage};
+
+use error::find_line;
+
+use mesh::Mesh;
+
+mod error;
 mod texture;
+mod mesh;
+mod manager;
+mod watch;
+mod background;
+pub mod gltf;
 
 pub struct Obj {
     path: Path,
+    mode: Mode,
     materials: Vec<obj::Material>,
-    obj: obj::Obj<String>
+    library: HashMap<String, Path>,
+    surface: HashMap<String, SurfaceMaps>,
+    textures: HashMap<String, Texture>,
+    obj: obj::Obj<String>,
+    warnings: Vec<LoadError>
 }
 
-impl Obj {
-    pub fn load(path: &Path) -> IoResult<Obj> {
-        File::open(path).map(|f| {
-            let mut f = BufferedReader::new(f);
-            let obj = obj::Obj::load(&mut f);
-
-            let mut materials = Vec::new();
-
-            for m in obj.materials().iter() {
-                let mut p = path.clone();
-                p.pop();
-                p.push(&m[]);
-                let file = File::open(&p).ok().expect("failed to open material");
-                let mut f = BufferedReader::new(file);
-                let m = obj::Mtl::load(&mut f);
-
-                for m in m.materials.into_iter() {
-                    materials.push(m);
-                }
+/// The surface detail maps of a material, these are parsed separately
+/// from the rest of the MTL file
+#[derive(Clone, Default)]
+struct SurfaceMaps {
+    bump: Option<String>,
+    bump_scale: Option<f32>,
+    norm: Option<String>
+}
+
+/// The most arguments each option of a map statement takes, the
+/// numeric options may leave out the trailing ones
+fn map_option_args(option: &str) -> usize {
+    match option {
+        "-o" | "-s" | "-t" => 3,
+        "-mm" => 2,
+        _ => 1
+    }
+}
+
+/// Find the file name and the `-bm` option of a map statement, the
+/// file name is everything after the last option so it may hold spaces
+fn parse_map_args(args: &[&str]) -> (Option<String>, Option<f32>) {
+    let mut scale = None;
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with("-") {
+        let option = args[i];
+        i += 1;
+        if option == "-bm" {
+            scale = args.get(i).and_then(|s| s.parse().ok());
+        }
+        let mut taken = 0;
+        while taken < map_option_args(option) && i < args.len() {
+            // an optional numeric argument that is missing
+            if taken > 0 && args[i].parse::<f32>().is_err() {
+                break;
             }
+            i += 1;
+            taken += 1;
+        }
+    }
 
-            Obj {
-                path: path.clone(),
-                materials: materials,
-                obj: obj
+    let file = args[i..].connect(" ");
+    (if file.is_empty() { None } else { Some(file) }, scale)
+}
+
+fn parse_surface_maps(text: &str) -> HashMap<String, SurfaceMaps> {
+    let mut out = HashMap::new();
+    let mut current = None;
+
+    for line in text.lines() {
+        let words: Vec<&str> = line.trim().split(' ').filter(|s| !s.is_empty()).collect();
+        if words.len() < 2 {
+            continue;
+        }
+
+        match words[0] {
+            "newmtl" => {
+                current = Some(words[1].to_string());
+                out.insert(words[1].to_string(), SurfaceMaps::default());
+            }
+            "map_bump" | "map_Bump" | "bump" => {
+                if let Some(m) = current.as_ref().and_then(|c| out.get_mut(c)) {
+                    let (file, scale) = parse_map_args(&words[1..]);
+                    m.bump = file;
+                    m.bump_scale = scale;
+                }
             }
-        })
+            "norm" => {
+                if let Some(m) = current.as_ref().and_then(|c| out.get_mut(c)) {
+                    m.norm = parse_map_args(&words[1..]).0;
+                }
+            }
+            _ => ()
+        }
     }
+    out
+}
 
-    fn write_textures<G: Graphics>(&self, db: &mut G) -> HashMap<String, Entity> {
-        let mut map = HashMap::new();
-        for m in self.materials.iter() {
-            let text = [&m.map_ka, &m.map_kd, &m.map_ks, &m.map_ke];
-            for t in text.iter() {
-                match *t {
-                    &None => (),
-                    &Some(ref t) => {
-                        let insert = map.get(t).is_none();
-                        if insert {
-                            let mut path = self.path.clone();
-                            drop(path.pop());
-                            let text = texture::load_texture(&path.join(&Path::new(t.clone())));
-                            let id = db.new_texture(text);
-                            map.insert(t.clone(), id);
+/// Every statement in a material library that references an image
+const MAP_STATEMENTS: [&'static str; 9] = ["map_Ka", "map_Kd", "map_Ks", "map_Ke", "map_d",
+                                           "map_bump", "map_Bump", "bump", "norm"];
+
+impl Obj {
+    /// Load an obj along with its materials and textures, the first
+    /// problem found is returned as an error
+    pub fn load(path: &Path) -> LoadResult<Obj> {
+        Obj::load_with(path, Mode::Strict)
+    }
+
+    /// Load an obj, in `Lenient` mode anything that is missing is replaced
+    /// by a default and reported through `warnings`
+    pub fn load_with(path: &Path, mode: Mode) -> LoadResult<Obj> {
+        Obj::load_reporting(path, mode, &mut |_, _| ())
+    }
+
+    /// Load an obj, `report` is called with the number of files that have
+    /// been read and the number that are known about so far. A texture
+    /// that is shared by several materials counts once for each.
+    pub fn load_reporting(path: &Path, mode: Mode, report: &mut FnMut(usize, usize)) -> LoadResult<Obj> {
+        let text = try!(File::open(path).read_to_string().map_err(|e| LoadError::io(path, e)));
+        let obj = obj::Obj::load(&mut BufReader::new(text.as_bytes()));
+
+        let mut loaded = Obj {
+            path: path.clone(),
+            mode: mode,
+            materials: Vec::new(),
+            library: HashMap::new(),
+            surface: HashMap::new(),
+            textures: HashMap::new(),
+            obj: obj,
+            warnings: Vec::new()
+        };
+
+        // each material remembers the library it came from so that
+        // texture errors can point at the right line
+        let mut libraries = Vec::new();
+        let mut source = Vec::new();
+        let names: Vec<String> = loaded.obj.materials().iter().map(|m| m.clone()).collect();
+        let (mut done, mut total) = (1, 1 + names.len());
+        report(done, total);
+        for name in names.iter() {
+            let p = loaded.relative(&name[]);
+            match File::open(&p).read_to_string() {
+                Ok(lib) => {
+                    let m = obj::Mtl::load(&mut BufReader::new(lib.as_bytes()));
+                    for m in m.materials.into_iter() {
+                        loaded.library.insert(m.name.clone(), p.clone());
+                        loaded.materials.push(m);
+                        source.push(libraries.len());
+                    }
+                    loaded.surface.extend(parse_surface_maps(&lib[]).into_iter());
+                    libraries.push((p, lib));
+                }
+                Err(e) => {
+                    let line = find_line(&text[], &["mtllib"], &name[]);
+                    try!(loaded.fail(LoadError::new(path, line, LoadErrorKind::Io(e))));
+                }
+            }
+            done += 1;
+            report(done, total);
+        }
+
+        total += range(0, source.len()).fold(0, |n, i| n + loaded.texture_names(i).len());
+        report(done, total);
+
+        for (i, &lib) in source.iter().enumerate() {
+            let (ref lib_path, ref lib_text) = libraries[lib];
+            for (key, file, usage) in loaded.texture_names(i).into_iter() {
+                if loaded.textures.contains_key(&key) {
+                    done += 1;
+                    report(done, total);
+                    continue;
+                }
+                let p = loaded.relative(&file[]);
+                let texture = match texture::load(&p, usage) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        let line = find_line(&lib_text[], &MAP_STATEMENTS, &file[]);
+                        try!(loaded.fail(LoadError::new(lib_path, line, e.kind)));
+                        // a missing alpha map leaves the material opaque
+                        if usage == Usage::Alpha {
+                            Texture::new(1, 1, 1, vec![255])
+                        } else {
+                            texture::checkerboard()
                         }
                     }
+                };
+                loaded.textures.insert(key, texture);
+                done += 1;
+                report(done, total);
+            }
+        }
+
+        for e in loaded.check_groups(&text[]).into_iter() {
+            try!(loaded.fail(e));
+        }
+
+        Ok(loaded)
+    }
+
+    /// Problems that were worked around while loading in `Lenient` mode
+    pub fn warnings(&self) -> &[LoadError] {
+        &self.warnings[]
+    }
+
+    fn fail(&mut self, err: LoadError) -> LoadResult<()> {
+        match self.mode {
+            Mode::Strict => Err(err),
+            Mode::Lenient => {
+                self.warnings.push(err);
+                Ok(())
+            }
+        }
+    }
+
+    /// Find a file named in the obj or a material library
+    fn relative(&self, name: &str) -> Path {
+        let mut path = self.path.clone();
+        path.pop();
+        path.join(name)
+    }
+
+    /// A key that identifies a texture across files, `key` is one of the
+    /// keys of `textures`
+    fn texture_path(&self, key: &str) -> String {
+        let (usage, file) = Usage::from_key(key);
+        usage.key(&format!("{}", self.relative(file).display())[])
+    }
+
+    /// A key that identifies a material across files
+    fn material_path(&self, name: &str) -> String {
+        match self.library.get(name) {
+            Some(lib) => format!("{}#{}", lib.display(), name),
+            None => format!("{}#{}", self.path.display(), name)
+        }
+    }
+
+    /// The image file a texture was read from
+    fn texture_file(&self, key: &str) -> Path {
+        self.relative(Usage::from_key(key).1)
+    }
+
+    /// The obj and every material library it names
+    fn files(&self) -> Vec<Path> {
+        let mut out = vec![self.path.clone()];
+        for name in self.obj.materials().iter() {
+            out.push(self.relative(&name[]));
+        }
+        out
+    }
+
+    /// The textures used by a material as `(key, file, usage)`, each
+    /// usage is stored separately since the same image may be used as
+    /// both a colour map and an alpha or normal map
+    fn texture_names(&self, material: usize) -> Vec<(String, String, Usage)> {
+        let m = &self.materials[material];
+        let mut out = Vec::new();
+        for t in [&m.map_ka, &m.map_kd, &m.map_ks, &m.map_ke].iter() {
+            if let Some(ref t) = **t {
+                out.push((Usage::Color.key(&t[]), t.clone(), Usage::Color));
+            }
+        }
+        if let Some(ref t) = m.map_d {
+            out.push((Usage::Alpha.key(&t[]), t.clone(), Usage::Alpha));
+        }
+        if let Some(surface) = self.surface.get(&m.name) {
+            for t in [&surface.bump, &surface.norm].iter() {
+                if let Some(ref t) = **t {
+                    out.push((Usage::Linear.key(&t[]), t.clone(), Usage::Linear));
                 }
             }
         }
-        map
+        out
     }
 
-    fn write_materials<G: Graphics>(&self, db: &mut G, text: &HashMap<String, Entity>)
-            -> HashMap<String, snowmew::Entity> {
+    /// Check that every object has faces, and that each group selects a
+    /// material that was loaded
+    fn check_groups(&self, text: &str) -> Vec<LoadError> {
+        let mut errors = Vec::new();
+        for o in self.obj.object_iter() {
+            if o.group_iter().all(|g| g.indices().is_empty()) {
+                let line = find_line(text, &["o"], &o.name[]);
+                errors.push(LoadError::new(&self.path, line, LoadErrorKind::EmptyObject(o.name.clone())));
+            }
+            for g in o.group_iter() {
+                match g.material {
+                    None => {
+                        let line = find_line(text, &["g"], &g.name[]);
+                        errors.push(LoadError::new(&self.path, line, LoadErrorKind::NoMaterial));
+                    }
+                    Some(ref m) if !self.materials.iter().any(|x| x.name == *m) => {
+                        let line = find_line(text, &["usemtl"], &m[]);
+                        errors.push(LoadError::new(&self.path, line,
+                                                   LoadErrorKind::UnknownMaterial(m.clone())));
+                    }
+                    Some(_) => ()
+                }
+            }
+        }
+        errors
+    }
 
-        let mut name_to_id = HashMap::new();
+    fn write_textures<G: Graphics>(&self, db: &mut G) -> HashMap<String, Entity> {
+        self.textures.iter().map(|(key, t)| {
+            (key.clone(), db.new_texture(t.clone()))
+        }).collect()
+    }
 
-        let lookup = |&: name| {
-            *text.get(name).expect("texture not found")
+    /// Build a material, `text` maps the keys of `textures` to entities
+    fn material(&self, m: &obj::Material, text: &HashMap<String, Entity>) -> graphics::Material {
+        let lookup = |&: usage: Usage, name: &String| {
+            text.get(&usage.key(&name[])).map(|&id| id)
         };
 
-        for m in self.materials.iter() {
-            let mut mat = graphics::Material::new();
-            if m.ka.is_some() { mat.set_ka(*m.ka.as_ref().unwrap()); }
-            if m.kd.is_some() { mat.set_kd(*m.kd.as_ref().unwrap()); }
-            if m.ks.is_some() { mat.set_ks(*m.ks.as_ref().unwrap()); }
+        let mut mat = graphics::Material::new();
+        if m.ka.is_some() { mat.set_ka(*m.ka.as_ref().unwrap()); }
+        if m.kd.is_some() { mat.set_kd(*m.kd.as_ref().unwrap()); }
+        if m.ks.is_some() { mat.set_ks(*m.ks.as_ref().unwrap()); }
         if m.ke.is_some() { mat.set_ke(*m.ke.as_ref().unwrap()); }
-            if m.ni.is_some() { mat.set_ni(*m.ni.as_ref().unwrap()); }
-            if m.ns.is_some() { mat.set_ns(*m.ns.as_ref().unwrap()); }
-            if m.map_ka.is_some() { mat.set_map_ka(lookup(m.map_ka.as_ref().unwrap())); }
-            if m.map_kd.is_some() { mat.set_map_kd(lookup(m.map_kd.as_ref().unwrap())); }
-            if m.map_ks.is_some() { mat.set_map_ks(lookup(m.map_ks.as_ref().unwrap())); }
-            if m.map_ke.is_some() { mat.set_map_ke(lookup(m.map_ke.as_ref().unwrap())); }
-            let id = db.new_material(mat);
-            name_to_id.insert(m.name.clone(), id);
+        if m.ni.is_some() { mat.set_ni(*m.ni.as_ref().unwrap()); }
+        if m.ns.is_some() { mat.set_ns(*m.ns.as_ref().unwrap()); }
+        if let Some(t) = m.map_ka.as_ref().and_then(|t| lookup(Usage::Color, t)) { mat.set_map_ka(t); }
+        if let Some(t) = m.map_kd.as_ref().and_then(|t| lookup(Usage::Color, t)) { mat.set_map_kd(t); }
+        if let Some(t) = m.map_ks.as_ref().and_then(|t| lookup(Usage::Color, t)) { mat.set_map_ks(t); }
+        if let Some(t) = m.map_ke.as_ref().and_then(|t| lookup(Usage::Color, t)) { mat.set_map_ke(t); }
+        if let Some(t) = m.map_d.as_ref().and_then(|t| lookup(Usage::Alpha, t)) {
+            mat.set_map_d(t);
         }
+        if let Some(surface) = self.surface.get(&m.name) {
+            if let Some(t) = surface.bump.as_ref().and_then(|t| lookup(Usage::Linear, t)) { mat.set_map_bump(t); }
+            if let Some(t) = surface.norm.as_ref().and_then(|t| lookup(Usage::Linear, t)) { mat.set_map_norm(t); }
+            if surface.bump_scale.is_some() { mat.set_bump_scale(*surface.bump_scale.as_ref().unwrap()); }
+        }
+        if m.tr.is_some() { mat.set_tr(*m.tr.as_ref().unwrap()); }
+        match (m.d, m.tr) {
+            (Some(d), _) => mat.set_d(d),
+            // some exporters only write the inverse of the dissolve
+            (None, Some(tr)) => mat.set_d(1. - tr),
+            (None, None) => ()
+        }
+        mat
+    }
 
-        name_to_id
+    fn write_materials<G: Graphics>(&self, db: &mut G, text: &HashMap<String, Entity>)
+            -> HashMap<String, snowmew::Entity> {
+        self.materials.iter().map(|m| {
+            (m.name.clone(), db.new_material(self.material(m, text)))
+        }).collect()
     }
 
-    pub fn import<G: Graphics>(&self, gd: &mut G) -> HashMap<String, Entity> {
+    /// Create the textures, materials and an entity for each object. Every
+    /// object gets one vertex buffer, the faces of each material are a range
+    /// of that buffer drawn by a child of the object's entity. Groups that
+    /// use the same material are drawn together. Groups with a missing
+    /// material are given a plain white one, this only happens for files
+    /// loaded in `Lenient` mode.
+    pub fn import<G: Graphics+Positions>(&self, gd: &mut G) -> HashMap<String, Entity> {
         let textures = self.write_textures(gd);
         let materials = self.write_materials(gd, &textures);
+        let mut default_material = None;
+
+        self.meshes().into_iter().map(|mesh| {
+            let name = mesh.name.clone();
+            let placed = mesh::place(gd, mesh, &materials, &mut default_material);
+            (name, placed.parent)
+        }).collect()
+    }
 
-        self.obj.object_iter().map(|obj| {
-            let g = obj.group_iter().next().unwrap(); // expect one group only
+    /// Build the vertices of every object that has faces
+    fn meshes(&self) -> Vec<Mesh> {
+        let mut out = Vec::new();
+
+        for obj in self.obj.object_iter() {
             let mut vertices = Vec::new();
-            let indices: Vec<u32> = {
-                 let mut indexer = LruIndexer::new(64, |_, v| {
+            let mut indices: Vec<u32> = Vec::new();
+            let mut groups = Vec::new();
+            {
+                let mut indexer = LruIndexer::new(64, |_, v| {
                     let (p, t, n): (usize, Option<usize>, Option<usize>) = v;
                     let vert = match (t, n) {
                         (Some(t), Some(n)) => {
@@ -174,28 +470,42 @@ impl Obj {
                     vertices.push(vert)
                 });
 
-                g.indices().iter()
-                    .map(|x| *x)
-                    .triangulate()
-                    .vertex(|v| indexer.index(v) as u32)
-                    .vertices()
-                    .collect()
-            };
-
-            let len = indices.len();
-            let vbo = gd.new_vertex_buffer(
-                graphics::VertexBuffer::new_position_texture_normal(vertices, indices)
-            );
-
-            let geo = gd.new_geometry(
-                graphics::Geometry::triangles(vbo, 0, len)
-            );
-
-            let mat_name = g.material.clone().expect("no material");
-            let mat = materials.get(&mat_name).expect("unresolved mat");
-            let o = gd.new_object(None);
-            gd.set_draw(o, geo, *mat);
-            (obj.name.clone(), o)
-        }).collect()
+                // the faces of each material in the order the materials
+                // are first used
+                let mut by_material: Vec<(Option<String>, Vec<u32>)> = Vec::new();
+                for g in obj.group_iter() {
+                    let faces: Vec<u32> = g.indices().iter()
+                        .map(|x| *x)
+                        .triangulate()
+                        .vertex(|v| indexer.index(v) as u32)
+                        .vertices()
+                        .collect();
+                    if faces.is_empty() {
+                        continue;
+                    }
+                    match by_material.iter().position(|&(ref m, _)| *m == g.material) {
+                        Some(i) => by_material[i].1.push_all(&faces[]),
+                        None => by_material.push((g.material.clone(), faces))
+                    }
+                }
+
+                for (material, faces) in by_material.into_iter() {
+                    groups.push((indices.len(), faces.len(), material));
+                    indices.extend(faces.into_iter());
+                }
+            }
+
+            if groups.is_empty() {
+                continue;
+            }
+
+            out.push(Mesh {
+                name: obj.name.clone(),
+                vertices: generate_tangents(&vertices[], &indices[]),
+                indices: indices,
+                groups: groups
+            });
+        }
+        out
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,514 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::collections::HashMap;
+use std::os;
+
+use snowmew::Entity;
+use snowmew::common::Delete;
+use graphics::Graphics;
+use position::Positions;
+
+use error::{LoadError, LoadErrorKind, LoadResult, Mode};
+use mesh::{self, Mesh, Placed};
+use background::{Pool, Job, Decoded, Message, Loaded, Progress};
+use watch::Watcher;
+use texture::{self, Usage};
+use Obj;
+
+/// An entity that is used by one or more assets
+#[derive(Clone)]
+struct Shared {
+    id: Entity,
+    refs: usize
+}
+
+/// An obj file and the shared resources it holds a reference to
+#[derive(Clone)]
+struct Model {
+    objects: HashMap<String, Placed>,
+    default_material: Option<Entity>,
+    materials: Vec<String>,
+    textures: Vec<String>,
+    /// the obj and its material libraries
+    files: Vec<Path>,
+    refs: usize
+}
+
+/// An asset that is being loaded in the background
+struct Pending {
+    path: Path,
+    /// the number of times it was requested
+    requests: usize,
+    done: usize,
+    total: usize
+}
+
+impl Model {
+    fn names(&self) -> HashMap<String, Entity> {
+        self.objects.iter().map(|(name, p)| (name.clone(), p.parent)).collect()
+    }
+}
+
+/// Keeps track of what has been loaded so that files are only imported
+/// once. Models, materials and textures are reference counted and are
+/// deleted from the game state when the last user releases them.
+pub struct AssetManager {
+    mode: Mode,
+    models: HashMap<String, Model>,
+    materials: HashMap<String, Shared>,
+    textures: HashMap<String, Shared>,
+    watcher: Option<Watcher>,
+    pool: Option<Pool>,
+    pending: HashMap<String, Pending>,
+    /// decoded assets that are waiting for `merge`
+    finished: Vec<(Path, LoadResult<Decoded>)>
+}
+
+/// Every way a texture may be used
+const USAGES: [Usage; 3] = [Usage::Color, Usage::Linear, Usage::Alpha];
+
+fn key(path: &Path) -> String {
+    format!("{}", path.display())
+}
+
+/// Find or create a shared entity and take a reference to it
+fn acquire<F: FnOnce() -> Entity>(table: &mut HashMap<String, Shared>, key: &String, create: F) -> Entity {
+    if let Some(s) = table.get_mut(key) {
+        s.refs += 1;
+        return s.id;
+    }
+    let id = create();
+    table.insert(key.clone(), Shared { id: id, refs: 1 });
+    id
+}
+
+/// Drop a reference, the entity is deleted when it was the last one
+fn release<G: Delete>(table: &mut HashMap<String, Shared>, gd: &mut G, key: &String) {
+    let unused = match table.get_mut(key) {
+        Some(s) => {
+            s.refs -= 1;
+            s.refs == 0
+        }
+        None => false
+    };
+    if unused {
+        let s = table.remove(key).unwrap();
+        gd.delete(s.id);
+    }
+}
+
+impl AssetManager {
+    pub fn new() -> AssetManager {
+        AssetManager::with_mode(Mode::Strict)
+    }
+
+    /// Create a manager that loads every file with `mode`
+    pub fn with_mode(mode: Mode) -> AssetManager {
+        AssetManager {
+            mode: mode,
+            models: HashMap::new(),
+            materials: HashMap::new(),
+            textures: HashMap::new(),
+            watcher: None,
+            pool: None,
+            pending: HashMap::new(),
+            finished: Vec::new()
+        }
+    }
+
+    /// Import an obj, returning the entity of each object. If the file
+    /// was imported before the same entities are returned. Materials and
+    /// textures that are already loaded are reused.
+    pub fn load_obj<G: Graphics+Positions>(&mut self, gd: &mut G, path: &Path)
+            -> LoadResult<HashMap<String, Entity>> {
+        let path_key = key(path);
+        if let Some(model) = self.models.get_mut(&path_key) {
+            model.refs += 1;
+            return Ok(model.names());
+        }
+
+        let obj = try!(Obj::load_with(path, self.mode));
+        let meshes = obj.meshes();
+        Ok(self.add_obj(gd, path, &obj, meshes, 1))
+    }
+
+    /// Place the objects of a loaded obj and start tracking it
+    fn add_obj<G: Graphics+Positions>(&mut self, gd: &mut G, path: &Path, obj: &Obj,
+                                      meshes: Vec<Mesh>, refs: usize) -> HashMap<String, Entity> {
+        let mut model = Model {
+            objects: HashMap::new(),
+            default_material: None,
+            materials: Vec::new(),
+            textures: Vec::new(),
+            files: Vec::new(),
+            refs: refs
+        };
+
+        let materials = self.acquire_materials(gd, obj, &mut model);
+        for mesh in meshes.into_iter() {
+            let name = mesh.name.clone();
+            let placed = mesh::place(gd, mesh, &materials, &mut model.default_material);
+            model.objects.insert(name, placed);
+        }
+        model.files = obj.files();
+
+        if let Some(ref mut w) = self.watcher {
+            for f in model.files.iter() {
+                w.watch(f);
+            }
+        }
+
+        let names = model.names();
+        self.models.insert(key(path), model);
+        names
+    }
+
+    /// Take a reference to each texture and material of `obj` that the
+    /// model does not already hold, creating any that are not loaded.
+    /// Returns the entity of every material by name.
+    fn acquire_materials<G: Graphics>(&mut self, gd: &mut G, obj: &Obj, model: &mut Model)
+            -> HashMap<String, Entity> {
+        let mut textures = HashMap::new();
+        for (name, t) in obj.textures.iter() {
+            let k = obj.texture_path(&name[]);
+            let id = if model.textures.contains(&k) {
+                self.textures[k].id
+            } else {
+                model.textures.push(k.clone());
+                if let Some(ref mut w) = self.watcher {
+                    w.watch(&obj.texture_file(&name[]));
+                }
+                acquire(&mut self.textures, &k, || gd.new_texture(t.clone()))
+            };
+            textures.insert(name.clone(), id);
+        }
+
+        let mut materials = HashMap::new();
+        for m in obj.materials.iter() {
+            let k = obj.material_path(&m.name[]);
+            let id = if model.materials.contains(&k) {
+                self.materials[k].id
+            } else {
+                model.materials.push(k.clone());
+                acquire(&mut self.materials, &k, || gd.new_material(obj.material(m, &textures)))
+            };
+            materials.insert(m.name.clone(), id);
+        }
+        materials
+    }
+
+    /// Load a texture that is not part of a model
+    pub fn load_texture<G: Graphics>(&mut self, gd: &mut G, path: &Path) -> LoadResult<Entity> {
+        let k = key(path);
+        if let Some(s) = self.textures.get_mut(&k) {
+            s.refs += 1;
+            return Ok(s.id);
+        }
+        let t = try!(texture::load_texture(path));
+        if let Some(ref mut w) = self.watcher {
+            w.watch(path);
+        }
+        Ok(acquire(&mut self.textures, &k, || gd.new_texture(t)))
+    }
+
+    /// Release a model that was loaded with `load_obj`. When the last
+    /// reference is gone its objects, geometry and vertex buffers are
+    /// deleted along with any materials and textures nothing else uses.
+    /// Returns false if the model was not loaded.
+    pub fn release_obj<G: Graphics+Positions+Delete>(&mut self, gd: &mut G, path: &Path) -> bool {
+        let path_key = key(path);
+        let unused = match self.models.get_mut(&path_key) {
+            Some(model) => {
+                model.refs -= 1;
+                model.refs == 0
+            }
+            None => return false
+        };
+
+        if unused {
+            let model = self.models.remove(&path_key).unwrap();
+            for p in model.objects.values() {
+                for &id in p.entities().iter() {
+                    gd.delete(id);
+                }
+            }
+            if let Some(id) = model.default_material {
+                gd.delete(id);
+            }
+            for k in model.materials.iter() {
+                release(&mut self.materials, gd, k);
+            }
+            for k in model.textures.iter() {
+                release(&mut self.textures, gd, k);
+            }
+        }
+        true
+    }
+
+    /// Release a texture that was loaded with `load_texture`
+    pub fn release_texture<G: Graphics+Delete>(&mut self, gd: &mut G, path: &Path) -> bool {
+        let k = key(path);
+        if !self.textures.contains_key(&k) {
+            return false;
+        }
+        release(&mut self.textures, gd, &k);
+        true
+    }
+
+    /// The number of users of a model or texture, the users of each way
+    /// a texture is used are added together
+    pub fn references(&self, path: &Path) -> usize {
+        let k = key(path);
+        match self.models.get(&k) {
+            Some(m) => m.refs,
+            None => USAGES.iter().fold(0, |sum, usage| {
+                sum + self.textures.get(&usage.key(&k[])).map(|t| t.refs).unwrap_or(0)
+            })
+        }
+    }
+
+    /// The entity of a texture if it is loaded for `usage`, textures that
+    /// are not part of a model are loaded as `Usage::Linear`
+    pub fn texture(&self, path: &Path, usage: Usage) -> Option<Entity> {
+        self.textures.get(&usage.key(&key(path)[])).map(|t| t.id)
+    }
+
+    /// Watch every file that is loaded, now and later, for changes.
+    /// Changed files are reloaded by `update`.
+    pub fn watch(&mut self, mut watcher: Watcher) {
+        for m in self.models.values() {
+            for f in m.files.iter() {
+                watcher.watch(f);
+            }
+        }
+        for k in self.textures.keys() {
+            watcher.watch(&Path::new(Usage::from_key(&k[]).1));
+        }
+        self.watcher = Some(watcher);
+    }
+
+    /// Reload any watched file that has changed, the errors of files that
+    /// could not be reloaded are returned and their old contents are kept
+    pub fn update<G: Graphics+Positions+Delete>(&mut self, gd: &mut G) -> Vec<LoadError> {
+        let changed = match self.watcher {
+            Some(ref mut w) => w.changed(),
+            None => return Vec::new()
+        };
+
+        changed.iter().filter_map(|p| self.reload(gd, p).err()).collect()
+    }
+
+    /// Reload a file, replacing the data of the entities that were created
+    /// from it. Every entity keeps its id so nothing that refers to them
+    /// needs to change. Returns false if nothing uses the file.
+    pub fn reload<G: Graphics+Positions+Delete>(&mut self, gd: &mut G, path: &Path) -> LoadResult<bool> {
+        let k = key(path);
+        let mut used = false;
+
+        for &usage in USAGES.iter() {
+            if let Some(id) = self.textures.get(&usage.key(&k[])).map(|s| s.id) {
+                gd.set_texture(id, try!(texture::load(path, usage)));
+                used = true;
+            }
+        }
+
+        // an obj is reloaded when it, or one of its libraries, changes
+        let models: Vec<String> = self.models.iter()
+            .filter(|&(_, m)| m.files.iter().any(|f| f == path))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for m in models.iter() {
+            try!(self.reload_obj(gd, m));
+            used = true;
+        }
+        Ok(used)
+    }
+
+    fn reload_obj<G: Graphics+Positions+Delete>(&mut self, gd: &mut G, path_key: &String) -> LoadResult<()> {
+        let obj = try!(Obj::load_with(&Path::new(&path_key[]), self.mode));
+        let mut model = self.models.remove(path_key).unwrap();
+
+        // materials that already exist are replaced, new ones are added
+        let materials = self.acquire_materials(gd, &obj, &mut model);
+        let textures: HashMap<String, Entity> = obj.textures.keys().map(|name| {
+            (name.clone(), self.textures[obj.texture_path(&name[])].id)
+        }).collect();
+        for m in obj.materials.iter() {
+            gd.set_material(materials[m.name], obj.material(m, &textures));
+        }
+
+        let mut seen = Vec::new();
+        for mesh in obj.meshes().into_iter() {
+            let name = mesh.name.clone();
+            match model.objects.get_mut(&name) {
+                Some(placed) => mesh::replace(gd, placed, mesh, &materials, &mut model.default_material),
+                None => {
+                    let placed = mesh::place(gd, mesh, &materials, &mut model.default_material);
+                    model.objects.insert(name.clone(), placed);
+                }
+            }
+            seen.push(name);
+        }
+
+        // objects that were removed from the file are deleted
+        let removed: Vec<String> = model.objects.keys()
+            .filter(|n| !seen.contains(n))
+            .map(|n| n.clone())
+            .collect();
+        for n in removed.iter() {
+            for &id in model.objects.remove(n).unwrap().entities().iter() {
+                gd.delete(id);
+            }
+        }
+
+        // drop the materials and textures the file no longer uses
+        let materials: Vec<String> = obj.materials.iter().map(|m| obj.material_path(&m.name[])).collect();
+        let textures: Vec<String> = obj.textures.keys().map(|t| obj.texture_path(&t[])).collect();
+        for k in model.materials.iter().filter(|k| !materials.contains(k)) {
+            release(&mut self.materials, gd, k);
+        }
+        for k in model.textures.iter().filter(|k| !textures.contains(k)) {
+            release(&mut self.textures, gd, k);
+        }
+        model.materials = materials;
+        model.textures = textures;
+
+        model.files = obj.files();
+        if let Some(ref mut w) = self.watcher {
+            for f in model.files.iter() {
+                w.watch(f);
+            }
+        }
+        self.models.insert(path_key.clone(), model);
+        Ok(())
+    }
+
+    /// Start loading an obj on a worker thread. It is added to the game
+    /// state by the first call to `merge` after it has been decoded.
+    pub fn request_obj(&mut self, path: &Path) {
+        let mode = self.mode;
+        self.request(path, Job::Obj(path.clone(), mode));
+    }
+
+    /// Start loading a texture on a worker thread, see `request_obj`
+    pub fn request_texture(&mut self, path: &Path) {
+        self.request(path, Job::Texture(path.clone()));
+    }
+
+    fn request(&mut self, path: &Path, job: Job) {
+        let k = key(path);
+        if let Some(p) = self.pending.get_mut(&k) {
+            p.requests += 1;
+            return;
+        }
+        self.pending.insert(k, Pending { path: path.clone(), requests: 1, done: 0, total: 1 });
+
+        if self.pool.is_none() {
+            self.pool = Some(Pool::new(os::num_cpus()));
+        }
+        self.pool.as_ref().unwrap().send(job);
+    }
+
+    /// How far along the requested assets are
+    pub fn progress(&mut self) -> Progress {
+        self.poll_progress();
+        self.pending.values().fold(
+            Progress { done: 0, total: 0, pending: 0 },
+            |p, a| Progress { done: p.done + a.done, total: p.total + a.total, pending: p.pending + 1 }
+        )
+    }
+
+    fn poll_progress(&mut self) {
+        let messages = match self.pool {
+            Some(ref p) => p.ready(),
+            None => return
+        };
+        for m in messages.into_iter() {
+            self.receive(m);
+        }
+    }
+
+    fn receive(&mut self, m: Message) {
+        match m {
+            Message::Progress(path, done, total) => {
+                if let Some(p) = self.pending.get_mut(&key(&path)) {
+                    p.done = done;
+                    p.total = total;
+                }
+            }
+            Message::Done(path, result) => {
+                // it may already have been failed by a stopped worker
+                if !self.finished.iter().any(|&(ref f, _)| *f == path) {
+                    self.finished.push((path, result));
+                }
+            }
+            // fail everything that is still queued, the next request
+            // starts a new pool
+            Message::Stopped => {
+                self.pool = None;
+                let lost: Vec<Path> = self.pending.values()
+                    .map(|p| p.path.clone())
+                    .filter(|p| !self.finished.iter().any(|&(ref f, _)| f == p))
+                    .collect();
+                for path in lost.into_iter() {
+                    let err = LoadError::new(&path, None, LoadErrorKind::Crashed);
+                    self.finished.push((path, Err(err)));
+                }
+            }
+        }
+    }
+
+    /// Add every requested asset that has finished decoding to the game
+    /// state. This never blocks, so it can be called from `Game::step`
+    /// while a loading screen is shown or a level is streamed in. Each
+    /// asset holds one reference for every time it was requested.
+    pub fn merge<G: Graphics+Positions>(&mut self, gd: &mut G) -> Vec<(Path, LoadResult<Loaded>)> {
+        self.poll_progress();
+
+        let finished: Vec<(Path, LoadResult<Decoded>)> = self.finished.drain().collect();
+        finished.into_iter().map(|(path, result)| {
+            let refs = self.pending.remove(&key(&path)).map(|p| p.requests).unwrap_or(1);
+            let loaded = result.map(|d| self.add_decoded(gd, &path, d, refs));
+            (path, loaded)
+        }).collect()
+    }
+
+    fn add_decoded<G: Graphics+Positions>(&mut self, gd: &mut G, path: &Path,
+                                          decoded: Decoded, refs: usize) -> Loaded {
+        let k = key(path);
+        match decoded {
+            Decoded::Obj(obj, meshes) => {
+                // it may have been loaded while this copy was decoded
+                if let Some(model) = self.models.get_mut(&k) {
+                    model.refs += refs;
+                    return Loaded::Obj(model.names());
+                }
+                Loaded::Obj(self.add_obj(gd, path, &obj, meshes, refs))
+            }
+            Decoded::Texture(t) => {
+                if let Some(s) = self.textures.get_mut(&k) {
+                    s.refs += refs;
+                    return Loaded::Texture(s.id);
+                }
+                if let Some(ref mut w) = self.watcher {
+                    w.watch(path);
+                }
+                let id = gd.new_texture(t);
+                self.textures.insert(k, Shared { id: id, refs: refs });
+                Loaded::Texture(id)
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::collections::HashMap;
+use std::cmp;
+
+use cgmath::Transform;
+use snowmew::Entity;
+use snowmew::common::Delete;
+use graphics::{Graphics, Geometry, VertexBuffer, Material};
+use graphics::geometry::VertexGeoTexNormTan;
+use position::Positions;
+
+/// The vertices of an object, each group is a range of the index buffer
+/// drawn with a single material
+pub struct Mesh {
+    pub name: String,
+    pub vertices: Vec<VertexGeoTexNormTan>,
+    pub indices: Vec<u32>,
+    /// `(offset, count, material)` of each group
+    pub groups: Vec<(usize, usize, Option<String>)>
+}
+
+/// The entities created for an object
+#[derive(Clone)]
+pub struct Placed {
+    pub parent: Entity,
+    pub vertex_buffer: Entity,
+    /// the drawable child and the geometry of each group
+    pub groups: Vec<(Entity, Entity)>
+}
+
+impl Placed {
+    /// every entity that belongs to the object, children first
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut out = Vec::new();
+        for &(child, geo) in self.groups.iter() {
+            out.push_all(&[child, geo]);
+        }
+        out.push_all(&[self.vertex_buffer, self.parent]);
+        out
+    }
+}
+
+/// Find the material of a group, groups without a known material share
+/// a plain white one that is created the first time it is needed
+pub fn group_material<G: Graphics>(gd: &mut G,
+                                   materials: &HashMap<String, Entity>,
+                                   default: &mut Option<Entity>,
+                                   name: &Option<String>) -> Entity {
+    match name.as_ref().and_then(|m| materials.get(m)) {
+        Some(&mat) => mat,
+        None => {
+            if default.is_none() {
+                *default = Some(gd.new_material(Material::new()));
+            }
+            default.unwrap()
+        }
+    }
+}
+
+fn add_group<G: Graphics+Positions>(gd: &mut G, parent: Entity, geo: Geometry, mat: Entity) -> (Entity, Entity) {
+    let geo = gd.new_geometry(geo);
+    let child = gd.new_object(Some(parent));
+    gd.set_delta(child, Some(parent), Transform::identity());
+    gd.set_draw(child, geo, mat);
+    (child, geo)
+}
+
+/// Create the vertex buffer, parent and group children of an object
+pub fn place<G: Graphics+Positions>(gd: &mut G,
+                                    mesh: Mesh,
+                                    materials: &HashMap<String, Entity>,
+                                    default: &mut Option<Entity>) -> Placed {
+    let vbo = gd.new_vertex_buffer(
+        VertexBuffer::new_position_texture_normal_tangent(mesh.vertices, mesh.indices)
+    );
+    let parent = gd.new_object(None);
+    gd.set_to_identity(parent);
+
+    let groups = mesh.groups.iter().map(|&(offset, len, ref mat)| {
+        let mat = group_material(gd, materials, default, mat);
+        add_group(gd, parent, Geometry::triangles(vbo, offset, len), mat)
+    }).collect();
+
+    Placed {
+        parent: parent,
+        vertex_buffer: vbo,
+        groups: groups
+    }
+}
+
+/// Replace the contents of an object that was already placed. The parent,
+/// the vertex buffer and the groups that still exist keep their entities,
+/// groups are matched by the order their materials are first used in.
+pub fn replace<G: Graphics+Positions+Delete>(gd: &mut G,
+                                             placed: &mut Placed,
+                                             mesh: Mesh,
+                                             materials: &HashMap<String, Entity>,
+                                             default: &mut Option<Entity>) {
+    gd.set_vertex_buffer(placed.vertex_buffer,
+        VertexBuffer::new_position_texture_normal_tangent(mesh.vertices, mesh.indices)
+    );
+
+    for (i, &(offset, len, ref mat)) in mesh.groups.iter().enumerate() {
+        let mat = group_material(gd, materials, default, mat);
+        let geo = Geometry::triangles(placed.vertex_buffer, offset, len);
+        if i < placed.groups.len() {
+            let (child, g) = placed.groups[i];
+            gd.set_geometry(g, geo);
+            gd.set_draw(child, g, mat);
+        } else {
+            let group = add_group(gd, placed.parent, geo, mat);
+            placed.groups.push(group);
+        }
+    }
+
+    for &(child, geo) in placed.groups[cmp::min(mesh.groups.len(), placed.groups.len())..].iter() {
+        gd.delete(child);
+        gd.delete(geo);
+    }
+    placed.groups.truncate(mesh.groups.len());
+}
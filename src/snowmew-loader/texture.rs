@@ -12,13 +12,282 @@
 //   See the License for the specific language governing permissions and
 //   limitations under the License.
 
-use image::{self, GenericImage};
-use graphics::Texture;
+use std::old_io::File;
+use std::num::Float;
+use std::{str, mem};
 
-pub fn load_texture(path: &Path) -> Texture {
-    let img = image::open(path).ok().expect("Failed to load image.")
-                               .to_rgba();
+use image::{self, GenericImage, DynamicImage};
+use graphics::{Texture, Format, Sampler, Wrap, Filter};
+use graphics::texture::{CUBE_FACES, cube_from_equirect};
+
+use error::{LoadError, LoadErrorKind, LoadResult};
+
+/// How the pixels of a texture are used, this decides the format it is
+/// loaded as
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Usage {
+    /// colours that were stored with the sRGB curve, like a diffuse map
+    Color,
+    /// values that are used as they are, like a normal or bump map
+    Linear,
+    /// a single channel of coverage, see `load_alpha`
+    Alpha
+}
+
+impl Usage {
+    /// The key a texture is shared under, a file is loaded once for each
+    /// way it is used
+    pub fn key(&self, file: &str) -> String {
+        match *self {
+            Usage::Color => format!("srgb:{}", file),
+            Usage::Linear => file.to_string(),
+            Usage::Alpha => format!("alpha:{}", file)
+        }
+    }
+
+    /// Split a key made by `key` into the usage and the file
+    pub fn from_key(key: &str) -> (Usage, &str) {
+        if key.starts_with("srgb:") {
+            (Usage::Color, &key[5..])
+        } else if key.starts_with("alpha:") {
+            (Usage::Alpha, &key[6..])
+        } else {
+            (Usage::Linear, key)
+        }
+    }
+}
+
+fn open(path: &Path) -> LoadResult<DynamicImage> {
+    image::open(path).map_err(|e| {
+        LoadError::new(path, None, LoadErrorKind::Image(format!("{:?}", e)))
+    })
+}
+
+fn is_hdr(path: &Path) -> bool {
+    path.extension_str().map(|e| e.eq_ignore_ascii_case("hdr")).unwrap_or(false)
+}
+
+/// Read an image as four 8 bit channels, or as four float channels if
+/// it is a Radiance `.hdr` image
+fn open_rgba(path: &Path, format: Format) -> LoadResult<Texture> {
+    if is_hdr(path) {
+        return load_hdr(path);
+    }
+    let img = try!(open(path)).to_rgba();
     let (w, h) = img.dimensions();
-    let data = img.into_raw();
-    Texture::new(w, h, 4, data)
+    Ok(Texture::with_format(w, h, format, img.into_raw()))
+}
+
+/// Load a texture whose values are used as they are, a `.hdr` image is
+/// loaded as float
+pub fn load_texture(path: &Path) -> LoadResult<Texture> {
+    let mut texture = try!(open_rgba(path, Format::RGBA8));
+    texture.generate_mipmaps();
+    Ok(texture)
+}
+
+/// Load a colour texture, 8 bit images are assumed to be sRGB
+pub fn load_color(path: &Path) -> LoadResult<Texture> {
+    let mut texture = try!(open_rgba(path, Format::SRGBA8));
+    texture.generate_mipmaps();
+    Ok(texture)
+}
+
+pub fn load(path: &Path, usage: Usage) -> LoadResult<Texture> {
+    match usage {
+        Usage::Color => load_color(path),
+        Usage::Linear => load_texture(path),
+        Usage::Alpha => load_alpha(path)
+    }
+}
+
+/// Decode the pixels of a Radiance RGBE image to floats
+fn rgbe(pixel: &[u8]) -> [f32; 3] {
+    if pixel[3] == 0 {
+        return [0., 0., 0.];
+    }
+    let scale = 2f32.powi(pixel[3] as i32 - 136);
+    [pixel[0] as f32 * scale, pixel[1] as f32 * scale, pixel[2] as f32 * scale]
+}
+
+/// Read one scanline of `width` RGBE pixels starting at `at`, returns the
+/// pixels and where the next scanline starts
+fn hdr_scanline(data: &[u8], at: usize, width: usize) -> Option<(Vec<u8>, usize)> {
+    let mut line: Vec<u8> = (0..width * 4).map(|_| 0).collect();
+
+    // scanlines that are not run length encoded are stored flat
+    let encoded = width >= 8 && width < 32768 && at + 4 <= data.len() &&
+                  data[at] == 2 && data[at + 1] == 2 &&
+                  ((data[at + 2] as usize) << 8 | data[at + 3] as usize) == width;
+    if !encoded {
+        if at + width * 4 > data.len() {
+            return None;
+        }
+        line.clone_from_slice(&data[at..at + width * 4]);
+        return Some((line, at + width * 4));
+    }
+
+    // each channel is stored on its own as runs and literals
+    let mut at = at + 4;
+    for c in (0..4) {
+        let mut x = 0;
+        while x < width {
+            if at >= data.len() {
+                return None;
+            }
+            let count = data[at] as usize;
+            at += 1;
+            if count > 128 {
+                let count = count - 128;
+                if at >= data.len() || x + count > width {
+                    return None;
+                }
+                for i in (0..count) {
+                    line[(x + i) * 4 + c] = data[at];
+                }
+                at += 1;
+                x += count;
+            } else {
+                if count == 0 || at + count > data.len() || x + count > width {
+                    return None;
+                }
+                for i in (0..count) {
+                    line[(x + i) * 4 + c] = data[at + i];
+                }
+                at += count;
+                x += count;
+            }
+        }
+    }
+    Some((line, at))
+}
+
+/// Load a Radiance `.hdr` image as a float texture
+pub fn load_hdr(path: &Path) -> LoadResult<Texture> {
+    let data = try!(File::open(path).read_to_end().map_err(|e| LoadError::io(path, e)));
+    let invalid = |&: msg: &str| {
+        LoadError::new(path, None, LoadErrorKind::Image(format!("invalid hdr image: {}", msg)))
+    };
+
+    // the header ends with a blank line, then comes the size
+    let mut lines = Vec::new();
+    let mut at = 0;
+    while lines.len() < 64 {
+        let end = match data[at..].iter().position(|&b| b == b'\n') {
+            Some(end) => at + end,
+            None => return Err(invalid("the header is not finished"))
+        };
+        let line = str::from_utf8(&data[at..end]).unwrap_or("");
+        at = end + 1;
+        let size = lines.iter().any(|l: &String| l.is_empty());
+        lines.push(line.trim().to_string());
+        if size {
+            break;
+        }
+    }
+    if !lines.first().map(|l| l.starts_with("#?")).unwrap_or(false) {
+        return Err(invalid("missing the #? signature"));
+    }
+    if lines.iter().any(|l| l.starts_with("FORMAT=") && &l[] != "FORMAT=32-bit_rle_rgbe") {
+        return Err(invalid("only the rgbe format is supported"));
+    }
+
+    let size: Vec<&str> = lines.last().unwrap().split(' ').filter(|s| !s.is_empty()).collect();
+    if size.len() != 4 || size[0] != "-Y" || size[2] != "+X" {
+        return Err(invalid("only top to bottom, left to right images are supported"));
+    }
+    let (h, w): (usize, usize) = match (size[1].parse(), size[3].parse()) {
+        (Ok(h), Ok(w)) => (h, w),
+        _ => return Err(invalid("bad image size"))
+    };
+
+    let mut pixels = Vec::new();
+    for _ in (0..h) {
+        let (line, next) = match hdr_scanline(&data[], at, w) {
+            Some(line) => line,
+            None => return Err(invalid("the pixel data is cut short"))
+        };
+        at = next;
+        for p in line.chunks(4) {
+            let c = rgbe(p);
+            for &v in [c[0], c[1], c[2], 1.].iter() {
+                let bits: u32 = unsafe { mem::transmute(v) };
+                pixels.push_all(&[bits as u8, (bits >> 8) as u8,
+                                  (bits >> 16) as u8, (bits >> 24) as u8]);
+            }
+        }
+    }
+    Ok(Texture::with_format(w as u32, h as u32, Format::RGBA32F, pixels))
+}
+
+/// Load a cube map from six square images of the same size in the order
+/// +x, -x, +y, -y, +z, -z
+pub fn load_cube(faces: &[Path]) -> LoadResult<Texture> {
+    if faces.len() != CUBE_FACES {
+        let path = faces.first().map(|p| p.clone()).unwrap_or(Path::new("."));
+        let msg = format!("a cube map needs {} faces, found {}", CUBE_FACES, faces.len());
+        return Err(LoadError::new(&path, None, LoadErrorKind::Invalid(msg)));
+    }
+
+    let mut size = None;
+    let mut data = Vec::new();
+    for path in faces.iter() {
+        let img = try!(open(path)).to_rgba();
+        let (w, h) = img.dimensions();
+        if w != h || size.map(|s| s != w).unwrap_or(false) {
+            let msg = format!("cube faces must be square and the same size, found {}x{}", w, h);
+            return Err(LoadError::new(path, None, LoadErrorKind::Invalid(msg)));
+        }
+        size = Some(w);
+        data.push(img.into_raw());
+    }
+
+    let mut texture = Texture::cube(size.unwrap(), Format::RGBA8, data);
+    texture.generate_mipmaps();
+    Ok(texture)
+}
+
+/// Load an equirectangular image as a cube map with faces of `size`
+/// pixels, a `.hdr` image gives a float cube map
+pub fn load_equirect(path: &Path, size: u32) -> LoadResult<Texture> {
+    let src = try!(open_rgba(path, Format::SRGBA8));
+    let mut texture = cube_from_equirect(&src, size);
+    texture.generate_mipmaps();
+    Ok(texture)
+}
+
+/// Load an alpha map, the alpha channel is used if the image has one,
+/// otherwise the luminance is used. The result is a single channel texture.
+pub fn load_alpha(path: &Path) -> LoadResult<Texture> {
+    let img = try!(open(path));
+    let has_alpha = match img.color() {
+        image::ColorType::GrayA(_) | image::ColorType::RGBA(_) => true,
+        _ => false
+    };
+    let img = img.to_luma_alpha();
+    let (w, h) = img.dimensions();
+    let data = img.pixels().map(|p| if has_alpha { p.data[1] } else { p.data[0] }).collect();
+    let mut texture = Texture::new(w, h, 1, data);
+    texture.generate_mipmaps();
+    Ok(texture)
+}
+
+/// A magenta and black checkerboard, used in place of textures that
+/// could not be loaded so they are easy to spot
+pub fn checkerboard() -> Texture {
+    let size = 8;
+    let mut data = Vec::with_capacity(size * size * 4);
+    for y in (0..size) {
+        for x in (0..size) {
+            if (x + y) % 2 == 0 {
+                data.push_all(&[255, 0, 255, 255]);
+            } else {
+                data.push_all(&[0, 0, 0, 255]);
+            }
+        }
+    }
+    // the squares should stay sharp however close they are
+    let mut texture = Texture::new(size as u32, size as u32, 4, data);
+    texture.set_sampler(Sampler { wrap: Wrap::Repeat, filter: Filter::Nearest });
+    texture
 }
\ No newline at end of file
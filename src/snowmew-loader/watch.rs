@@ -0,0 +1,229 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::collections::HashMap;
+use std::old_io::fs;
+
+/// The modification time and size of a file, `None` if it is missing
+type Stamp = Option<(u64, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    fs::stat(path).ok().map(|s| (s.modified, s.size))
+}
+
+/// Reports files that have changed on disk. File system notifications are
+/// used where they are supported, otherwise every watched file is checked
+/// each time `changed` is called.
+pub struct Watcher {
+    files: HashMap<Path, Stamp>,
+    notify: Option<notify::Notify>
+}
+
+impl Watcher {
+    /// Create a watcher that uses notifications if the platform has them
+    pub fn new() -> Watcher {
+        Watcher {
+            files: HashMap::new(),
+            notify: notify::Notify::new()
+        }
+    }
+
+    /// Create a watcher that only polls, for file systems that do not
+    /// deliver notifications such as network shares
+    pub fn polling() -> Watcher {
+        Watcher {
+            files: HashMap::new(),
+            notify: None
+        }
+    }
+
+    pub fn is_polling(&self) -> bool {
+        self.notify.is_none()
+    }
+
+    /// Start watching a file, it does not need to exist yet
+    pub fn watch(&mut self, path: &Path) {
+        if self.files.contains_key(path) {
+            return;
+        }
+
+        // editors often replace a file rather then writing to it, so
+        // the directory is watched instead of the file
+        let failed = match self.notify {
+            Some(ref mut n) => {
+                let mut dir = path.clone();
+                dir.pop();
+                !n.watch_dir(&dir)
+            }
+            None => false
+        };
+        if failed {
+            self.notify = None;
+        }
+        self.files.insert(path.clone(), stamp(path));
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// The watched files that changed since the last call, this does
+    /// not block
+    pub fn changed(&mut self) -> Vec<Path> {
+        let candidates: Vec<Path> = match self.notify {
+            Some(ref mut n) => {
+                match n.read() {
+                    Some(paths) => paths,
+                    // the event queue overflowed, anything may have changed
+                    None => self.files.keys().map(|p| p.clone()).collect()
+                }
+            }
+            None => self.files.keys().map(|p| p.clone()).collect()
+        };
+
+        // the stamp filters out events that did not touch a watched
+        // file, and is the whole check when polling
+        let mut out = Vec::new();
+        for p in candidates.into_iter() {
+            let s = stamp(&p);
+            let changed = match self.files.get(&p) {
+                Some(old) => *old != s,
+                None => false
+            };
+            if changed {
+                self.files.insert(p.clone(), s);
+                out.push(p);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod notify {
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::mem;
+    use libc::{c_int, c_char, c_void, size_t, ssize_t};
+
+    extern {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+        fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+        fn close(fd: c_int) -> c_int;
+    }
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+
+    const IN_MODIFY: u32 = 0x2;
+    const IN_ATTRIB: u32 = 0x4;
+    const IN_CLOSE_WRITE: u32 = 0x8;
+    const IN_MOVED_TO: u32 = 0x80;
+    const IN_CREATE: u32 = 0x100;
+    const IN_Q_OVERFLOW: u32 = 0x4000;
+
+    #[repr(C)]
+    struct Event {
+        wd: c_int,
+        mask: u32,
+        cookie: u32,
+        len: u32
+    }
+
+    /// Directory notifications through inotify
+    pub struct Notify {
+        fd: c_int,
+        dirs: HashMap<c_int, Path>
+    }
+
+    impl Notify {
+        pub fn new() -> Option<Notify> {
+            let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            if fd < 0 {
+                None
+            } else {
+                Some(Notify {
+                    fd: fd,
+                    dirs: HashMap::new()
+                })
+            }
+        }
+
+        pub fn watch_dir(&mut self, dir: &Path) -> bool {
+            if self.dirs.values().any(|d| d == dir) {
+                return true;
+            }
+            let name = CString::from_slice(dir.as_vec());
+            let mask = IN_MODIFY | IN_ATTRIB | IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE;
+            let wd = unsafe { inotify_add_watch(self.fd, name.as_ptr(), mask) };
+            if wd < 0 {
+                return false;
+            }
+            self.dirs.insert(wd, dir.clone());
+            true
+        }
+
+        /// Read every pending event, returns `None` if events were lost
+        pub fn read(&mut self) -> Option<Vec<Path>> {
+            let mut out = Vec::new();
+            let mut buf = [0u8; 4096];
+            let header = mem::size_of::<Event>();
+
+            loop {
+                let len = unsafe {
+                    read(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len() as size_t)
+                };
+                if len <= 0 {
+                    break;
+                }
+
+                let mut at = 0;
+                while at + header <= len as usize {
+                    let event: &Event = unsafe { mem::transmute(buf[at..].as_ptr()) };
+                    if event.mask & IN_Q_OVERFLOW != 0 {
+                        return None;
+                    }
+                    let name = &buf[at + header..at + header + event.len as usize];
+                    let name: Vec<u8> = name.iter().take_while(|&&c| c != 0).map(|&c| c).collect();
+                    if let Some(dir) = self.dirs.get(&event.wd) {
+                        if !name.is_empty() {
+                            out.push(dir.join(&name[]));
+                        }
+                    }
+                    at += header + event.len as usize;
+                }
+            }
+            Some(out)
+        }
+    }
+
+    impl Drop for Notify {
+        fn drop(&mut self) {
+            unsafe { close(self.fd); }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod notify {
+    /// Notifications are not supported, every watcher polls
+    pub struct Notify;
+
+    impl Notify {
+        pub fn new() -> Option<Notify> { None }
+        pub fn watch_dir(&mut self, _: &Path) -> bool { false }
+        pub fn read(&mut self) -> Option<Vec<Path>> { Some(Vec::new()) }
+    }
+}
@@ -43,6 +43,9 @@ extern crate "snowmew-input" as input;
 extern crate collect;
 
 use std::collections::{HashMap, BTreeSet};
+use std::num::Float;
+use std::iter::repeat;
+use std::mem;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::thread::Thread;
 
@@ -61,10 +64,14 @@ use position::Positions;
 use graphics::Graphics;
 use snowmew::common::Entity;
 use sm_render::camera::Camera;
-use graphics::Material;
-use graphics::geometry::{VertexGeoTex, VertexGeoTexNorm};
+use graphics::{Material, PbrMaterial, Environment, Texture, Kind, Format, Sampler, Wrap, Filter};
+use graphics::pbr;
+use graphics::geometry::{VertexGeoTexNorm, VertexGeoTexNormTan, generate_tangents};
 use graphics::geometry::Vertex::{Geo, GeoTex, GeoNorm, GeoTexNorm, GeoTexNormTan};
-use sm_render::Renderable;
+use sm_render::{Renderable, RenderSettings, Skybox, MAX_CASCADES};
+use sm_render::shadow::{self, ShadowKind, Cascade, ATLAS_TILES};
+use sm_render::cull::{self, Frustum};
+use sm_render::camera::DrawMatrices;
 use input::{Window, GetIoState};
 use gfx::render;
 
@@ -74,6 +81,25 @@ struct SharedMatrix {
     view_mat: [[f32; 4]; 4]
 }
 
+#[derive(Copy)]
+struct SharedShadow {
+    shadow_mat: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// packed into a vec4, so there can not be more than four cascades
+    shadow_split: [f32; 4]
+}
+
+/// The maximum number of point and spot lights that are drawn
+const MAX_LIGHTS: usize = 8;
+
+#[derive(Copy)]
+struct SharedLights {
+    light_position: [[f32; 4]; MAX_LIGHTS],
+    light_direction: [[f32; 4]; MAX_LIGHTS],
+    light_point_color: [[f32; 4]; MAX_LIGHTS],
+    light_shadow: [[f32; 4]; MAX_LIGHTS],
+    shadow_tile_mat: [[[f32; 4]; 4]; ATLAS_TILES]
+}
+
 #[derive(Copy)]
 struct SharedMaterial {
     ka_color: [f32; 4],
@@ -83,16 +109,15 @@ struct SharedMaterial {
     ka_use_texture: i32,
     kd_use_texture: i32,
     ks_use_texture: i32,
+    d_use_texture: i32,
+    d: f32,
+    norm_use_texture: i32,
+    bump_use_texture: i32,
+    bump_scale: f32,
 }
 
 const VERTEX_SRC: &'static [u8] = b"
     #version 150 core
-    layout(std140)
-    uniform shadow_shared_mat {
-        mat4 shadow_proj_mat;
-        mat4 shadow_view_mat;
-    };
-
     layout(std140)
     uniform shared_mat {
         mat4 proj_mat;
@@ -104,35 +129,185 @@ const VERTEX_SRC: &'static [u8] = b"
     };
     uniform int offset;
 
-    uniform mat4 shadow_bias_mat;
-
     in vec3 position;
     in vec2 texture;
     in vec3 normal;
+    in vec4 tangent;
 
     out vec2 o_texture;
     out vec3 o_normal;
-    out vec4 o_shadow_coord;
+    out vec4 o_tangent;
+    out vec3 o_position;
+    out float o_depth;
 
     void main() {
-        gl_Position =
-            proj_mat *
-            view_mat *
-            model_mat[gl_InstanceID + offset] *
-            vec4(position, 1.0);
+        vec4 world = model_mat[gl_InstanceID + offset] * vec4(position, 1.0);
+        vec4 eye = view_mat * world;
+        gl_Position = proj_mat * eye;
         o_texture = texture;
         o_normal = normalize((model_mat[gl_InstanceID + offset] * vec4(normal, 0.)).xyz);
-
-        o_shadow_coord = shadow_bias_mat *
-                         shadow_proj_mat *
-                         shadow_view_mat *
-                         model_mat[gl_InstanceID + offset] *
-                         vec4(position, 1.0);
+        o_tangent = vec4((model_mat[gl_InstanceID + offset] * vec4(tangent.xyz, 0.)).xyz, tangent.w);
+        o_position = world.xyz;
+        o_depth = -eye.z;
     }
 ";
 
-const FRAGMENT_SRC: &'static [u8] = b"
-    #version 150 core
+/// Put the version and the array sizes that the lighting uniforms share
+/// with the Rust side in front of a fragment shader that uses
+/// `lighting_glsl!`
+fn lit_fragment_src(src: &str) -> Vec<u8> {
+    format!("#version 150 core\n\
+             #define MAX_LIGHTS {}\n\
+             #define ATLAS_TILES {}\n\
+             #define MAX_CASCADES {}\n{}",
+            MAX_LIGHTS, ATLAS_TILES, MAX_CASCADES, src).into_bytes()
+}
+
+/// The shadow and light uniforms and functions shared by the programs
+/// that shade a surface
+macro_rules! lighting_glsl {
+    () => ("
+    uniform vec4 light_normal;
+    uniform vec4 light_color;
+
+    layout(std140)
+    uniform shadow_shared_mat {
+        mat4 shadow_mat[MAX_CASCADES];
+        vec4 shadow_split;
+    };
+    // light_position.w is the range of the light
+    // light_direction.w is the cosine of the spot light's cone, or -2 for
+    // a point light
+    // light_shadow.x is the first tile in the atlas, or -1 if the light
+    // does not cast a shadow
+    layout(std140)
+    uniform lights {
+        vec4 light_position[MAX_LIGHTS];
+        vec4 light_direction[MAX_LIGHTS];
+        vec4 light_point_color[MAX_LIGHTS];
+        vec4 light_shadow[MAX_LIGHTS];
+        mat4 shadow_tile_mat[ATLAS_TILES];
+    };
+    uniform int light_count;
+    uniform sampler2DShadow shadow_atlas;
+
+    uniform int shadow_count;
+    uniform sampler2DShadow shadow0;
+    uniform sampler2DShadow shadow1;
+    uniform sampler2DShadow shadow2;
+    uniform sampler2DShadow shadow3;
+
+    in vec2 o_texture;
+    in vec3 o_normal;
+    in vec4 o_tangent;
+    in vec3 o_position;
+    in float o_depth;
+
+    out vec4 o_Color;
+
+    float pcf(sampler2DShadow shadow, vec3 shadow_coord) {
+        float shadow_sum = 0;
+
+        shadow_sum += texture(shadow, shadow_coord) * 0.25;
+
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(1, 0)) * 0.125;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1, 0)) * 0.125;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(0, 1)) * 0.125;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(0, -1)) * 0.125;
+
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1,-1)) * 0.0625;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1, 1)) * 0.0625;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2( 1,-1)) * 0.0625;
+        shadow_sum += textureOffset(shadow, shadow_coord, ivec2( 1, 1)) * 0.0625;
+
+        return shadow_sum;
+    }
+
+    float shadow_factor() {
+        // past the last cascade there is no shadow information
+        if (shadow_count == 0 || o_depth > shadow_split[shadow_count-1]) {
+            return 1.;
+        }
+
+        int cascade = shadow_count-1;
+        for (int i = shadow_count-1; i >= 0; i--) {
+            if (o_depth <= shadow_split[i]) {
+                cascade = i;
+            }
+        }
+
+        vec4 coord = shadow_mat[cascade] * vec4(o_position, 1.);
+        vec3 shadow_coord = coord.xyz / coord.w;
+        shadow_coord.z -= 0.0002;
+
+        if (cascade == 0) {
+            return pcf(shadow0, shadow_coord);
+        } else if (cascade == 1) {
+            return pcf(shadow1, shadow_coord);
+        } else if (cascade == 2) {
+            return pcf(shadow2, shadow_coord);
+        } else {
+            return pcf(shadow3, shadow_coord);
+        }
+    }
+
+    float atlas_shadow(int light, vec3 to_frag) {
+        int tile = int(light_shadow[light].x);
+        if (tile < 0) {
+            return 1.;
+        }
+
+        // point lights store a cube map as six tiles, pick the face
+        if (light_direction[light].w < -1.) {
+            vec3 a = abs(to_frag);
+            if (a.x >= a.y && a.x >= a.z) {
+                tile += to_frag.x > 0. ? 0 : 1;
+            } else if (a.y >= a.z) {
+                tile += to_frag.y > 0. ? 2 : 3;
+            } else {
+                tile += to_frag.z > 0. ? 4 : 5;
+            }
+        }
+
+        vec4 coord = shadow_tile_mat[tile] * vec4(o_position, 1.);
+        vec3 shadow_coord = coord.xyz / coord.w;
+        shadow_coord.z -= 0.0005;
+        return pcf(shadow_atlas, shadow_coord);
+    }
+
+    // the colour arriving at the fragment from local light `i`, `dir`
+    // is set to the direction towards the light
+    vec3 local_light(int i, out vec3 dir) {
+        vec3 to_frag = o_position - light_position[i].xyz;
+        float dist = length(to_frag);
+        dir = -to_frag / dist;
+
+        if (light_direction[i].w >= -1. &&
+            dot(-dir, light_direction[i].xyz) < light_direction[i].w) {
+            return vec3(0.);
+        }
+
+        float falloff = max(0., 1. - dist / light_position[i].w);
+        return light_point_color[i].rgb *
+               falloff * falloff *
+               atlas_shadow(i, to_frag);
+    }
+
+    // move a normal from tangent space into world space
+    vec3 tangent_to_world(vec3 m) {
+        vec3 n = normalize(o_normal);
+        vec3 t = o_tangent.xyz - n * dot(n, o_tangent.xyz);
+        if (dot(t, t) < 1e-6) {
+            return n;
+        }
+        t = normalize(t);
+        float w = o_tangent.w < 0. ? -1. : 1.;
+        return normalize(mat3(t, cross(n, t) * w, n) * m);
+    }
+")
+}
+
+const FRAGMENT_SRC: &'static str = concat!("
     layout(std140)
     uniform material {
         vec4 ka_color;
@@ -142,26 +317,48 @@ const FRAGMENT_SRC: &'static [u8] = b"
         int ka_use_texture;
         int kd_use_texture;
         int ks_use_texture;
+        int d_use_texture;
+        float d;
+        int norm_use_texture;
+        int bump_use_texture;
+        float bump_scale;
     };
 
     uniform sampler2D ka_texture;
     uniform sampler2D kd_texture;
     uniform sampler2D ks_texture;
+    uniform sampler2D d_texture;
+    uniform sampler2D norm_texture;
+    uniform sampler2D bump_texture;
+
+", lighting_glsl!(), "
+    vec4 local_lights(vec4 kd, vec3 normal) {
+        vec3 color = vec3(0.);
+        for (int i = 0; i < light_count; i++) {
+            vec3 dir;
+            vec3 light = local_light(i, dir);
+            color += kd.rgb * light * max(0., dot(dir, normal));
+        }
+        return vec4(color, 0.);
+    }
 
-    uniform vec4 light_normal;
-    uniform vec4 light_color;
-    uniform sampler2DShadow shadow;
-
-    in vec2 o_texture;
-    in vec3 o_normal;
-    in vec4 o_shadow_coord;
-
-    out vec4 o_Color;
+    // perturb the interpolated normal using the normal map, or if there is
+    // none the slope of the bump map
+    vec3 surface_normal() {
+        if (1 == norm_use_texture) {
+            return tangent_to_world(texture(norm_texture, o_texture).xyz * 2. - 1.);
+        } else if (1 == bump_use_texture) {
+            float h = texture(bump_texture, o_texture).r;
+            float hx = textureOffset(bump_texture, o_texture, ivec2(1, 0)).r;
+            float hy = textureOffset(bump_texture, o_texture, ivec2(0, 1)).r;
+            return tangent_to_world(vec3((h - hx) * bump_scale, (h - hy) * bump_scale, 1.));
+        }
+        return normalize(o_normal);
+    }
 
     void main() {
-        vec4 normal = vec4(o_normal, 0.);
-        vec3 shadow_coord = o_shadow_coord.xyz / o_shadow_coord.w;
-        shadow_coord.z -= 0.0002;
+        vec3 n = surface_normal();
+        vec4 normal = vec4(n, 0.);
         vec4 color;
         vec4 ka, kd, ks;
         if (1 == ka_use_texture) {
@@ -182,45 +379,256 @@ const FRAGMENT_SRC: &'static [u8] = b"
             kd = ka_color;
         }
 
-        float shadow_sum = 0;
-        float x, y;
-
-        shadow_sum += texture(shadow, shadow_coord) * 0.25;
-
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(1, 0)) * 0.125;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1, 0)) * 0.125;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(0, 1)) * 0.125;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(0, -1)) * 0.125;
+        float alpha = d;
+        if (1 == d_use_texture) {
+            alpha *= texture(d_texture, o_texture).r;
+        }
+        if (alpha < 1. / 255.) {
+            discard;
+        }
 
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1,-1)) * 0.0625;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2(-1, 1)) * 0.0625;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2( 1,-1)) * 0.0625;
-        shadow_sum += textureOffset(shadow, shadow_coord, ivec2( 1, 1)) * 0.0625;
+        float shadow_sum = shadow_factor();
 
         color = ka * 0.2;
         color += shadow_sum *
                  kd *
                  light_color *
                  max(0, dot(light_normal, normal));
-        o_Color = color;
+        color += local_lights(kd, n);
+        o_Color = vec4(color.rgb, alpha);
     }
-";
+");
 
 #[shader_param]
 struct Params {
     shadow_shared_mat: gfx::RawBufferHandle,
     shared_mat: gfx::RawBufferHandle,
 
-    shadow_bias_mat: [[f32; 4]; 4],
-
     material: gfx::RawBufferHandle,
     ka_texture: gfx::shade::TextureParam,
     kd_texture: gfx::shade::TextureParam,
     ks_texture: gfx::shade::TextureParam,
+    d_texture: gfx::shade::TextureParam,
+    norm_texture: gfx::shade::TextureParam,
+    bump_texture: gfx::shade::TextureParam,
+
+    light_normal: [f32; 4],
+    light_color: [f32; 4],
+    lights: gfx::RawBufferHandle,
+    light_count: i32,
+    shadow_atlas: gfx::shade::TextureParam,
+    shadow_count: i32,
+    shadow0: gfx::shade::TextureParam,
+    shadow1: gfx::shade::TextureParam,
+    shadow2: gfx::shade::TextureParam,
+    shadow3: gfx::shade::TextureParam,
+
+    model: gfx::RawBufferHandle,
+    offset: i32
+}
+
+const PBR_FRAGMENT_SRC: &'static str = concat!("
+    layout(std140)
+    uniform pbr_material {
+        vec4 base_color;
+        vec4 emissive;
+        float metallic;
+        float roughness;
+        float occlusion_strength;
+        float normal_scale;
+        int base_color_use_texture;
+        int metallic_roughness_use_texture;
+        int emissive_use_texture;
+        int occlusion_use_texture;
+        int normal_use_texture;
+        float alpha_cutoff;
+    };
+
+    uniform sampler2D base_color_texture;
+    uniform sampler2D metallic_roughness_texture;
+    uniform sampler2D emissive_texture;
+    uniform sampler2D occlusion_texture;
+    uniform sampler2D normal_texture;
+
+    // irradiance is stored as spherical harmonics that are already
+    // divided by pi, environment_levels is 0 if there is no map
+    layout(std140)
+    uniform environment {
+        vec4 irradiance[9];
+        int environment_levels;
+        float environment_intensity;
+    };
+    uniform sampler2D environment_map;
+    uniform vec4 eye_position;
+
+", lighting_glsl!(), "
+    const float PI = 3.14159265;
+
+    vec3 sh_irradiance(vec3 n) {
+        return irradiance[0].rgb * 0.282095 +
+               irradiance[1].rgb * 0.488603 * n.y +
+               irradiance[2].rgb * 0.488603 * n.z +
+               irradiance[3].rgb * 0.488603 * n.x +
+               irradiance[4].rgb * 1.092548 * n.x * n.y +
+               irradiance[5].rgb * 1.092548 * n.y * n.z +
+               irradiance[6].rgb * 0.315392 * (3. * n.z * n.z - 1.) +
+               irradiance[7].rgb * 1.092548 * n.x * n.z +
+               irradiance[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+    }
+
+    vec2 equirect(vec3 d) {
+        return vec2(atan(d.x, -d.z) / (2. * PI) + 0.5,
+                    acos(clamp(d.y, -1., 1.)) / PI);
+    }
+
+    // Cook-Torrance with a GGX distribution and height correlated
+    // Smith visibility, the result includes the cosine term
+    vec3 brdf(vec3 n, vec3 v, vec3 l, vec3 diffuse, vec3 f0, float a) {
+        vec3 h = normalize(v + l);
+        float nl = max(dot(n, l), 0.);
+        float nv = max(dot(n, v), 1e-4);
+        float nh = max(dot(n, h), 0.);
+        float vh = max(dot(v, h), 0.);
+
+        float a2 = a * a;
+        float dd = nh * nh * (a2 - 1.) + 1.;
+        float distribution = a2 / (PI * dd * dd);
+        float gv = nl * sqrt(nv * nv * (1. - a2) + a2);
+        float gl = nv * sqrt(nl * nl * (1. - a2) + a2);
+        float visibility = 0.5 / max(gv + gl, 1e-5);
+        vec3 fresnel = f0 + (1. - f0) * pow(1. - vh, 5.);
+
+        return (diffuse * (1. - fresnel) / PI +
+                distribution * visibility * fresnel) * nl;
+    }
+
+    // an analytic fit of the split sum lookup table
+    vec3 env_brdf(vec3 f0, float roughness, float nv) {
+        vec4 c0 = vec4(-1., -0.0275, -0.572, 0.022);
+        vec4 c1 = vec4(1., 0.0425, 1.04, -0.04);
+        vec4 r = roughness * c0 + c1;
+        float a004 = min(r.x * r.x, exp2(-9.28 * nv)) * r.x + r.y;
+        vec2 ab = vec2(-1.04, 1.04) * a004 + r.zw;
+        return f0 * ab.x + ab.y;
+    }
+
+    void main() {
+        vec4 color = base_color;
+        if (1 == base_color_use_texture) {
+            color *= texture(base_color_texture, o_texture);
+        }
+        if (color.a < 1. / 255.) {
+            discard;
+        }
+        if (alpha_cutoff > 0.) {
+            if (color.a < alpha_cutoff) {
+                discard;
+            }
+            color.a = 1.;
+        }
+
+        float m = metallic;
+        float r = roughness;
+        if (1 == metallic_roughness_use_texture) {
+            vec4 mr = texture(metallic_roughness_texture, o_texture);
+            m *= mr.b;
+            r *= mr.g;
+        }
+        r = clamp(r, 0.04, 1.);
+        float a = r * r;
+
+        vec3 n = normalize(o_normal);
+        if (1 == normal_use_texture) {
+            vec3 t = texture(normal_texture, o_texture).xyz * 2. - 1.;
+            n = tangent_to_world(vec3(t.xy * normal_scale, t.z));
+        }
+        vec3 v = normalize(eye_position.xyz - o_position);
+        vec3 diffuse = color.rgb * (1. - m);
+        vec3 f0 = mix(vec3(0.04), color.rgb, m);
+
+        vec3 light = brdf(n, v, light_normal.xyz, diffuse, f0, a) *
+                     light_color.rgb *
+                     shadow_factor();
+        for (int i = 0; i < light_count; i++) {
+            vec3 dir;
+            vec3 radiance = local_light(i, dir);
+            light += brdf(n, v, dir, diffuse, f0, a) * radiance;
+        }
+
+        // rough surfaces read from the blurrier mip levels of the map
+        vec3 refl = reflect(-v, n);
+        vec3 specular = env_brdf(f0, r, max(dot(n, v), 1e-4));
+        if (environment_levels > 0) {
+            float lod = r * float(environment_levels - 1);
+            specular *= textureLod(environment_map, equirect(refl), lod).rgb;
+        } else {
+            specular *= sh_irradiance(refl);
+        }
+        vec3 ambient = (diffuse * sh_irradiance(n) + specular) * environment_intensity;
+        if (1 == occlusion_use_texture) {
+            float ao = texture(occlusion_texture, o_texture).r;
+            ambient *= 1. + occlusion_strength * (ao - 1.);
+        }
+
+        vec3 e = emissive.rgb;
+        if (1 == emissive_use_texture) {
+            e *= texture(emissive_texture, o_texture).rgb;
+        }
+
+        o_Color = vec4(light + ambient + e, color.a);
+    }
+");
+
+#[derive(Copy)]
+struct SharedPbrMaterial {
+    base_color: [f32; 4],
+    emissive: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    normal_scale: f32,
+
+    base_color_use_texture: i32,
+    metallic_roughness_use_texture: i32,
+    emissive_use_texture: i32,
+    occlusion_use_texture: i32,
+    normal_use_texture: i32,
+    alpha_cutoff: f32,
+}
+
+#[derive(Copy)]
+struct SharedEnvironment {
+    irradiance: [[f32; 4]; pbr::SH_COEFFICIENTS],
+    environment_levels: i32,
+    environment_intensity: f32
+}
+
+#[shader_param]
+struct PbrParams {
+    shadow_shared_mat: gfx::RawBufferHandle,
+    shared_mat: gfx::RawBufferHandle,
+
+    pbr_material: gfx::RawBufferHandle,
+    base_color_texture: gfx::shade::TextureParam,
+    metallic_roughness_texture: gfx::shade::TextureParam,
+    emissive_texture: gfx::shade::TextureParam,
+    occlusion_texture: gfx::shade::TextureParam,
+    normal_texture: gfx::shade::TextureParam,
+
+    environment: gfx::RawBufferHandle,
+    environment_map: gfx::shade::TextureParam,
+    eye_position: [f32; 4],
 
     light_normal: [f32; 4],
     light_color: [f32; 4],
-    shadow: gfx::shade::TextureParam,
+    lights: gfx::RawBufferHandle,
+    light_count: i32,
+    shadow_atlas: gfx::shade::TextureParam,
+    shadow_count: i32,
+    shadow0: gfx::shade::TextureParam,
+    shadow1: gfx::shade::TextureParam,
+    shadow2: gfx::shade::TextureParam,
+    shadow3: gfx::shade::TextureParam,
 
     model: gfx::RawBufferHandle,
     offset: i32
@@ -272,51 +680,184 @@ struct ShadowParams {
     offset: i32
 }
 
+#[derive(Copy)]
+#[vertex_format]
+struct SkyVertex {
+    position: [f32; 2]
+}
+
+/// a single triangle that covers the screen
+const SKY_TRIANGLE: [SkyVertex; 3] = [SkyVertex { position: [-1., -1.] },
+                                      SkyVertex { position: [3., -1.] },
+                                      SkyVertex { position: [-1., 3.] }];
+
+static SKY_VERTEX_SRC: &'static [u8] = b"
+    #version 150 core
+    uniform mat4 sky_inverse;
+
+    in vec2 position;
+
+    out vec3 o_direction;
+
+    void main() {
+        // on the far plane, so only pixels nothing else covered pass
+        gl_Position = vec4(position, 1., 1.);
+        vec4 world = sky_inverse * gl_Position;
+        o_direction = world.xyz / world.w;
+    }
+";
+
+static SKY_CUBE_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+    uniform samplerCube sky_map;
+    uniform float sky_intensity;
+
+    in vec3 o_direction;
+
+    out vec4 o_Color;
+
+    void main() {
+        o_Color = vec4(texture(sky_map, o_direction).rgb * sky_intensity, 1.);
+    }
+";
+
+static SKY_EQUIRECT_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+    #define PI 3.1415926535897932384626433832795
+    uniform sampler2D sky_map;
+    uniform float sky_intensity;
+
+    in vec3 o_direction;
+
+    out vec4 o_Color;
+
+    void main() {
+        vec3 d = normalize(o_direction);
+        vec2 uv = vec2(atan(d.x, -d.z) / (2. * PI) + 0.5,
+                       acos(clamp(d.y, -1., 1.)) / PI);
+        // the base level avoids a seam where u wraps around
+        o_Color = vec4(textureLod(sky_map, uv, 0.).rgb * sky_intensity, 1.);
+    }
+";
+
+/// shared by the cube and equirectangular programs, `sky_map` is a
+/// different sampler type in each
+#[shader_param]
+struct SkyParams {
+    sky_inverse: [[f32; 4]; 4],
+    sky_intensity: f32,
+    sky_map: gfx::shade::TextureParam
+}
+
 struct Mesh {
     mesh: render::mesh::Mesh,
     index: device::BufferHandle<u32>
 }
 
+struct ShadowCascade {
+    frame: render::target::Frame,
+    texture: device::TextureHandle,
+    matrix: device::BufferHandle<SharedMatrix>
+}
+
+struct RenderPbrMaterial {
+    material: PbrMaterial,
+    buffer: device::BufferHandle<SharedPbrMaterial>,
+    base_color_texture: Option<Entity>,
+    metallic_roughness_texture: Option<Entity>,
+    emissive_texture: Option<Entity>,
+    occlusion_texture: Option<Entity>,
+    normal_texture: Option<Entity>,
+}
+
 struct RenderMaterial {
     material: Material,
     buffer: device::BufferHandle<SharedMaterial>,
     ka_texture: Option<Entity>,
     kd_texture: Option<Entity>,
     ks_texture: Option<Entity>,
+    d_texture: Option<Entity>,
+    norm_texture: Option<Entity>,
+    bump_texture: Option<Entity>,
+}
+
+fn texture_format(format: Format) -> gfx::tex::Format {
+    use gfx::tex::Components::{R, RG, RGB, RGBA};
+    use gfx::tex::Compression;
+    let unorm = |c| gfx::tex::Format::Unsigned(c, 8, gfx::attrib::IntSubType::Normalized);
+    let float = |c| gfx::tex::Format::Float(c, gfx::attrib::FloatSize::F32);
+    match format {
+        Format::R8 => unorm(R),
+        Format::RG8 => unorm(RG),
+        Format::RGB8 => unorm(RGB),
+        Format::RGBA8 => unorm(RGBA),
+        Format::SRGB8 => gfx::tex::Format::SRGB8,
+        Format::SRGBA8 => gfx::tex::Format::SRGB8_A8,
+        Format::R32F => float(R),
+        Format::RG32F => float(RG),
+        Format::RGB32F => float(RGB),
+        Format::RGBA32F => float(RGBA),
+        Format::Etc2RGB => gfx::tex::Format::Compressed(Compression::ETC2_RGB),
+        Format::Etc2SRGB => gfx::tex::Format::Compressed(Compression::ETC2_SRGB),
+        Format::Etc2RGBA => gfx::tex::Format::Compressed(Compression::ETC2_EAC_RGBA8)
+    }
 }
 
 pub struct RenderManagerContext {
     prog: device::Handle<u32,device::shade::ProgramInfo>,
     data: Params,
 
+    pbr_prog: device::Handle<u32,device::shade::ProgramInfo>,
+    pbr_data: PbrParams,
+    pbr_material: HashMap<Entity, RenderPbrMaterial>,
+    shared_environment: device::BufferHandle<SharedEnvironment>,
+    environment: Option<Environment>,
+    environment_map: Option<(Entity, u32, device::TextureHandle)>,
+    environment_sampler: device::SamplerHandle,
+
     shadow_data: ShadowParams,
     shadow_prog: device::Handle<u32,device::shade::ProgramInfo>,
-    shadow_frame: render::target::Frame,
-    shadow: device::TextureHandle,
+    shadow_cascades: Vec<ShadowCascade>,
     shadow_sampler: device::SamplerHandle,
-    shadow_shared_mat: device::BufferHandle<SharedMatrix>,
+    shadow_shared_mat: device::BufferHandle<SharedShadow>,
+    shadow_atlas: device::TextureHandle,
+    shadow_atlas_frame: render::target::Frame,
+    shadow_atlas_tiles: Vec<device::BufferHandle<SharedMatrix>>,
+    shadow_atlas_batches: HashMap<(Entity, usize), RefBatch<ShadowParams>>,
+    shared_lights: device::BufferHandle<SharedLights>,
     shared_mat: device::BufferHandle<SharedMatrix>,
 
     back_data: ShadowParams,
     back_prog: device::Handle<u32,device::shade::ProgramInfo>,
 
+    sky_data: SkyParams,
+    sky_cube_batch: RefBatch<SkyParams>,
+    sky_equirect_batch: RefBatch<SkyParams>,
+
     render: gfx::render::Renderer<gfx::GlCommandBuffer>,
     device: device::gl_device::GlDevice,
     context: gfx::render::batch::Context,
     frame: render::target::Frame,
     state: render::state::DrawState,
     back_state: render::state::DrawState,
+    blend_state: render::state::DrawState,
     meshes: HashMap<Entity, Mesh>,
-    textures: HashMap<Entity, device::TextureHandle>,
+    geometry: HashMap<Entity, graphics::Geometry>,
+    textures: HashMap<Entity, (device::TextureHandle, device::SamplerHandle)>,
+    revision: HashMap<Entity, u32>,
     sampler: device::SamplerHandle,
+    samplers: HashMap<Sampler, device::SamplerHandle>,
     window: Window,
+    settings: RenderSettings,
 
     material: HashMap<Entity, RenderMaterial>,
 
-    batch: BTreeSet<(Entity, Entity, Entity)>,
     shadow_batches: HashMap<Entity, RefBatch<ShadowParams>>,
     draw_batches: HashMap<Entity, RefBatch<Params>>,
     draw_back_batches: HashMap<Entity, RefBatch<ShadowParams>>,
+    draw_blend_batches: HashMap<Entity, RefBatch<Params>>,
+    draw_pbr_batches: HashMap<Entity, RefBatch<PbrParams>>,
+    draw_pbr_blend_batches: HashMap<Entity, RefBatch<PbrParams>>,
 
     spare_matrix_buffers: Vec<device::BufferHandle<[[f32; 4]; 4]>>,
     used_matrix_buffers: Vec<device::BufferHandle<[[f32; 4]; 4]>>,
@@ -332,8 +873,10 @@ pub struct RenderManager<R> {
 impl RenderManagerContext {
     fn _new(mut device: gfx::GlDevice,
             window: Window,
-            size: (i32, i32)) -> RenderManagerContext {
+            size: (i32, i32),
+            settings: RenderSettings) -> RenderManagerContext {
 
+        let settings = settings.clamped();
         let (width, height) = size;
         let frame = gfx::Frame::new(width as u16, height as u16);
         let mut back_state = gfx::DrawState::new().depth(gfx::state::Comparison::Less, true);
@@ -343,6 +886,11 @@ impl RenderManagerContext {
             offset: None,
         };
         let state = gfx::DrawState::new().depth(gfx::state::Comparison::Less, true);
+        // transparent surfaces are tested against the depth buffer, but do
+        // not write to it so that surfaces behind them are not rejected
+        let blend_state = gfx::DrawState::new()
+            .depth(gfx::state::Comparison::LessEqual, false)
+            .blend(gfx::BlendPreset::Alpha);
 
         let sampler = device.create_sampler(
             gfx::tex::SamplerInfo::new(
@@ -350,17 +898,19 @@ impl RenderManagerContext {
             )
         );
 
-        let (shadow_prog, shadow_data, shadow_shared_mat) = {
-            let buff = device.create_buffer::<SharedMatrix>(1, gfx::BufferUsage::Static);
+        let shadow_shared_mat = device.create_buffer::<SharedShadow>(1, gfx::BufferUsage::Static);
+        let shared_lights = device.create_buffer::<SharedLights>(1, gfx::BufferUsage::Static);
+
+        let (shadow_prog, shadow_data) = {
             let data = ShadowParams {
-                shared_mat: buff.raw(),
-                model: buff.raw(),
+                shared_mat: shadow_shared_mat.raw(),
+                model: shadow_shared_mat.raw(),
                 offset: 0
             };
             (device.link_program(SHADOW_VERTEX_SRC.clone(),
                                  SHADOW_FRAGMENT_SRC.clone())
                   .ok().expect("Failed to link program"),
-             data, buff)
+             data)
         };
 
         let (prog, data, shared_mat) = {
@@ -381,24 +931,28 @@ impl RenderManagerContext {
             let data = Params {
                 shared_mat: buff.raw(),
                 shadow_shared_mat: shadow_shared_mat.raw(),
-                shadow_bias_mat: [
-                    [0.5, 0.0, 0.0, 0.0],
-                    [0.0, 0.5, 0.0, 0.0],
-                    [0.0, 0.0, 0.5, 0.0],
-                    [0.5, 0.5, 0.5, 1.0],
-                ],
                 material: unused.raw(),
                 ka_texture: (dummy_texture, Some(sampler)),
                 kd_texture: (dummy_texture, Some(sampler)),
                 ks_texture: (dummy_texture, Some(sampler)),
+                d_texture: (dummy_texture, Some(sampler)),
+                norm_texture: (dummy_texture, Some(sampler)),
+                bump_texture: (dummy_texture, Some(sampler)),
 
                 light_color: [1., 1., 1., 1.],
                 light_normal: [1., 0., 0., 0.],
-                shadow: (dummy_texture, Some(sampler)),
+                lights: shared_lights.raw(),
+                light_count: 0,
+                shadow_atlas: (dummy_texture, Some(sampler)),
+                shadow_count: 0,
+                shadow0: (dummy_texture, Some(sampler)),
+                shadow1: (dummy_texture, Some(sampler)),
+                shadow2: (dummy_texture, Some(sampler)),
+                shadow3: (dummy_texture, Some(sampler)),
                 model: buff.raw(),
                 offset: 0
             };
-            (device.link_program(VERTEX_SRC.clone(), FRAGMENT_SRC.clone())
+            (device.link_program(VERTEX_SRC.clone(), &lit_fragment_src(FRAGMENT_SRC)[])
                   .ok().expect("Failed to link program"),
              data, buff)
         };
@@ -415,9 +969,51 @@ impl RenderManagerContext {
              data)
         };
 
+        let shared_environment = device.create_buffer::<SharedEnvironment>(1, gfx::BufferUsage::Static);
+        let (pbr_prog, pbr_data) = {
+            let unused = device.create_buffer::<SharedPbrMaterial>(1, gfx::BufferUsage::Static);
+            let dummy = data.ka_texture;
+            let pbr_data = PbrParams {
+                shared_mat: data.shared_mat,
+                shadow_shared_mat: data.shadow_shared_mat,
+                pbr_material: unused.raw(),
+                base_color_texture: dummy,
+                metallic_roughness_texture: dummy,
+                emissive_texture: dummy,
+                occlusion_texture: dummy,
+                normal_texture: dummy,
+
+                environment: shared_environment.raw(),
+                environment_map: dummy,
+                eye_position: [0., 0., 0., 1.],
+
+                light_color: data.light_color,
+                light_normal: data.light_normal,
+                lights: data.lights,
+                light_count: 0,
+                shadow_atlas: dummy,
+                shadow_count: 0,
+                shadow0: dummy,
+                shadow1: dummy,
+                shadow2: dummy,
+                shadow3: dummy,
+                model: data.model,
+                offset: 0
+            };
+            (device.link_program(VERTEX_SRC.clone(), &lit_fragment_src(PBR_FRAGMENT_SRC)[])
+                  .ok().expect("Failed to link program"),
+             pbr_data)
+        };
+
+        let environment_sampler = device.create_sampler(
+            gfx::tex::SamplerInfo::new(
+                gfx::tex::FilterMethod::Trilinear, gfx::tex::WrapMode::Tile
+            )
+        );
+
         let shadow_info = gfx::tex::TextureInfo {
-            width: 2048,
-            height: 2048,
+            width: settings.shadow_resolution as u16,
+            height: settings.shadow_resolution as u16,
             depth: 1,
             levels: 1,
             kind: gfx::tex::TextureKind::Texture2D,
@@ -431,68 +1027,156 @@ impl RenderManagerContext {
 
         let shadow_sampler = device.create_sampler(shadow_sampler);
 
-        let shadow = device.create_texture(shadow_info)
-                           .ok().expect("Failed to create texture");
+        let shadow_cascades: Vec<ShadowCascade> = (0..settings.shadow_cascades).map(|_| {
+            let texture = device.create_texture(shadow_info)
+                                .ok().expect("Failed to create texture");
 
-        let mut shadow_frame = gfx::Frame::new(
-            shadow_info.width as u16,
-            shadow_info.height as u16
-        );
+            let mut frame = gfx::Frame::new(
+                shadow_info.width as u16,
+                shadow_info.height as u16
+            );
+            frame.depth = Some(render::target::Plane::Texture(texture, 0, None));
+
+            ShadowCascade {
+                frame: frame,
+                texture: texture,
+                matrix: device.create_buffer::<SharedMatrix>(1, gfx::BufferUsage::Static)
+            }
+        }).collect();
 
-        shadow_frame.depth = Some(render::target::Plane::Texture(shadow, 0, None));
 
+        let atlas_info = gfx::tex::TextureInfo {
+            width: settings.shadow_atlas_resolution as u16,
+            height: settings.shadow_atlas_resolution as u16,
+            .. shadow_info
+        };
+        let shadow_atlas = device.create_texture(atlas_info)
+                                 .ok().expect("Failed to create texture");
+        let mut shadow_atlas_frame = gfx::Frame::new(
+            atlas_info.width as u16,
+            atlas_info.height as u16
+        );
+        shadow_atlas_frame.depth = Some(render::target::Plane::Texture(shadow_atlas, 0, None));
+        let shadow_atlas_tiles = (0..ATLAS_TILES).map(|_| {
+            device.create_buffer::<SharedMatrix>(1, gfx::BufferUsage::Static)
+        }).collect();
+
+        let mut context = gfx::batch::Context::new();
+        let (sky_data, sky_cube_batch, sky_equirect_batch) = {
+            let mesh = device.create_mesh(&SKY_TRIANGLE);
+            let state = gfx::DrawState::new().depth(gfx::state::Comparison::LessEqual, false);
+            let cube_prog = device.link_program(SKY_VERTEX_SRC.clone(),
+                                                SKY_CUBE_FRAGMENT_SRC.clone())
+                                  .ok().expect("Failed to link program");
+            let equirect_prog = device.link_program(SKY_VERTEX_SRC.clone(),
+                                                    SKY_EQUIRECT_FRAGMENT_SRC.clone())
+                                      .ok().expect("Failed to link program");
+            let cube: RefBatch<SkyParams> = context.make_batch(
+                &cube_prog, &mesh, mesh.to_slice(gfx::PrimitiveType::TriangleList), &state
+            ).ok().expect("Failed to create batch.");
+            let equirect: RefBatch<SkyParams> = context.make_batch(
+                &equirect_prog, &mesh, mesh.to_slice(gfx::PrimitiveType::TriangleList), &state
+            ).ok().expect("Failed to create batch.");
+            let data = SkyParams {
+                sky_inverse: Matrix4::identity().into_fixed(),
+                sky_intensity: 1.,
+                sky_map: data.ka_texture
+            };
+            (data, cube, equirect)
+        };
 
         RenderManagerContext {
             data: data,
+            pbr_prog: pbr_prog,
+            pbr_data: pbr_data,
+            pbr_material: HashMap::new(),
+            shared_environment: shared_environment,
+            environment: None,
+            environment_map: None,
+            environment_sampler: environment_sampler,
             render: device.create_renderer(),
             device: device,
-            context: gfx::batch::Context::new(),
+            context: context,
             frame: frame,
             state: state,
             back_state: back_state,
+            blend_state: blend_state,
             prog: prog,
             meshes: HashMap::new(),
+            geometry: HashMap::new(),
             textures: HashMap::new(),
+            revision: HashMap::new(),
             material: HashMap::new(),
             sampler: sampler,
+            samplers: HashMap::new(),
             window: window,
+            settings: settings,
             shadow_data: shadow_data,
             shadow_prog: shadow_prog,
             shadow_shared_mat: shadow_shared_mat,
             shared_mat: shared_mat,
-            shadow: shadow,
-            shadow_frame: shadow_frame,
+            shadow_cascades: shadow_cascades,
+            shadow_atlas: shadow_atlas,
+            shadow_atlas_frame: shadow_atlas_frame,
+            shadow_atlas_tiles: shadow_atlas_tiles,
+            shadow_atlas_batches: HashMap::new(),
+            shared_lights: shared_lights,
             shadow_sampler: shadow_sampler,
-            batch: BTreeSet::new(),
             shadow_batches: HashMap::new(),
             draw_batches: HashMap::new(),
             draw_back_batches: HashMap::new(),
+            draw_blend_batches: HashMap::new(),
+            draw_pbr_batches: HashMap::new(),
+            draw_pbr_blend_batches: HashMap::new(),
             spare_matrix_buffers: Vec::new(),
             used_matrix_buffers: Vec::new(),
             shared_geometry: Vec::new(),
             shared_geometry_material: Vec::new(),
             back_prog: back_prog,
             back_data: back_data,
+            sky_data: sky_data,
+            sky_cube_batch: sky_cube_batch,
+            sky_equirect_batch: sky_equirect_batch,
         }
     }
 
+    /// Check if a vertex buffer or texture was replaced since it was
+    /// uploaded, the caller frees the old handles once it has new ones
+    fn outdated<RD: Renderable+GetIoState>(&self, db: &RD, oid: Entity) -> bool {
+        self.revision.get(&oid) != Some(&db.revision(oid))
+    }
+
     fn load_meshes<RD: Renderable+GetIoState>(&mut self, db: &RD) {
         for (oid, vb) in db.vertex_buffer_iter() {
-            if self.meshes.get(&oid).is_none() {
+            if self.meshes.get(&oid).is_none() || self.outdated(db, oid) {
+                // every mesh is given the full vertex format so that the
+                // same program can draw any of them
                 let mesh = match vb.vertex {
                     Geo(ref d) => {
-                        let data: Vec<VertexGeoTex> = d.iter()
+                        let data: Vec<VertexGeoTexNormTan> = d.iter()
                             .map(|v| {
-                                VertexGeoTex {
+                                VertexGeoTexNormTan {
                                     position: v.position,
-                                    texture: [0., 0.]
+                                    texture: [0., 0.],
+                                    normal: [0., 0., 0.],
+                                    tangent: [0., 0., 0., 1.]
                                 }
                             })
                             .collect();
                         self.device.create_mesh(&data[])
                     },
                     GeoTex(ref d) => {
-                        self.device.create_mesh(&d[])
+                        let data: Vec<VertexGeoTexNormTan> = d.iter()
+                            .map(|v| {
+                                VertexGeoTexNormTan {
+                                    position: v.position,
+                                    texture: v.texture,
+                                    normal: [0., 0., 0.],
+                                    tangent: [0., 0., 0., 1.]
+                                }
+                            })
+                            .collect();
+                        self.device.create_mesh(&data[])
                     },
                     GeoNorm(ref d) => {
                         let data: Vec<VertexGeoTexNorm> = d.iter()
@@ -504,10 +1188,12 @@ impl RenderManagerContext {
                                 }
                             })
                             .collect();
+                        let data = generate_tangents(&data[], &vb.index[]);
                         self.device.create_mesh(&data[])
                     },
                     GeoTexNorm(ref d) => {
-                        self.device.create_mesh(&d[])
+                        let data = generate_tangents(&d[], &vb.index[]);
+                        self.device.create_mesh(&data[])
                     },
                     GeoTexNormTan(ref d) => {
                         self.device.create_mesh(&d[])
@@ -518,38 +1204,125 @@ impl RenderManagerContext {
 
                 let index = self.device.create_buffer_static(&vb[]);
 
-                self.meshes.insert(oid, Mesh {
+                let old = self.meshes.insert(oid, Mesh {
                     index: index,
                     mesh: mesh
                 });
+                if let Some(old) = old {
+                    self.free_mesh(old);
+                }
+                self.revision.insert(oid, db.revision(oid));
             }
         }
     }
 
     fn load_textures<RD: Renderable+GetIoState>(&mut self, db: &RD) {
         for (oid, text) in db.texture_iter() {
-            if self.textures.get(&oid).is_none() {
-                let tinfo = gfx::tex::TextureInfo {
-                    width: text.width() as u16,
-                    height: text.height() as u16,
-                    depth: 1 as u16,
-                    levels: 1,
-                    kind: gfx::tex::TextureKind::Texture2D,
-                    format: match text.depth() {
-                        4 => gfx::tex::Format::Unsigned(gfx::tex::Components::RGBA, 8, gfx::attrib::IntSubType::Normalized),
-                        3 => gfx::tex::Format::Unsigned(gfx::tex::Components::RGB, 8, gfx::attrib::IntSubType::Normalized),
-                        _ => panic!("Unsupported color depth")
-                    }
-                };
+            if self.textures.get(&oid).is_none() || self.outdated(db, oid) {
+                let texture = self.upload_texture(text);
+                let sampler = self.sampler_handle(text.sampler());
+                // the sampler is shared and stays alive
+                if let Some((old, _)) = self.textures.insert(oid, (texture, sampler)) {
+                    self.device.delete_texture(old);
+                }
+                self.revision.insert(oid, db.revision(oid));
+            }
+        }
+    }
+
+    /// All the attributes of a mesh are in one vertex buffer
+    fn free_mesh(&mut self, mesh: Mesh) {
+        if let Some(attr) = mesh.mesh.attributes.first() {
+            self.device.delete_buffer_raw(attr.buffer);
+        }
+        self.device.delete_buffer(mesh.index);
+    }
 
-                let img_info = tinfo.to_image_info();
-                let texture = self.device.create_texture(tinfo)
-                                         .ok().expect("Failed to create texture");
-                self.device.update_texture(&texture, &img_info, text.data())
-                    .ok().expect("Failed to update texture.");
-                self.textures.insert(oid, texture);
+    /// Create a texture with every mip level of `text`. If the sampler
+    /// needs mip levels that the texture does not have they are made by
+    /// the device.
+    fn upload_texture(&mut self, text: &Texture) -> device::TextureHandle {
+        self.try_upload_texture(text).expect("Failed to create texture")
+    }
+
+    /// Upload a texture, None if the device does not accept its format
+    /// or size
+    fn try_upload_texture(&mut self, text: &Texture) -> Option<device::TextureHandle> {
+        let generate = text.levels() == 1 && text.sampler().filter.uses_mipmaps() &&
+                       !text.format().is_compressed();
+        let tinfo = gfx::tex::TextureInfo {
+            width: text.width() as u16,
+            height: text.height() as u16,
+            depth: match text.kind() {
+                Kind::Array(n) => n as u16,
+                _ => 1
+            },
+            levels: (if generate { text.full_levels() } else { text.levels() }) as u8,
+            kind: match text.kind() {
+                Kind::Texture2D => gfx::tex::TextureKind::Texture2D,
+                Kind::Cube => gfx::tex::TextureKind::TextureCube,
+                Kind::Array(_) => gfx::tex::TextureKind::Texture2DArray
+            },
+            format: texture_format(text.format())
+        };
+
+        let texture = match self.device.create_texture(tinfo) {
+            Ok(texture) => texture,
+            Err(_) => return None
+        };
+        for level in (0..text.levels()) {
+            let (width, height) = text.level_size(level);
+            let mut img_info = tinfo.to_image_info();
+            img_info.width = width as u16;
+            img_info.height = height as u16;
+            img_info.mipmap = level as u8;
+            if text.kind() == Kind::Cube {
+                // each face is its own image, selected by the z offset
+                img_info.depth = 1;
+                for face in (0..text.layers()) {
+                    img_info.zoffset = face as u16;
+                    if self.device.update_texture(&texture, &img_info, text.layer(level, face)).is_err() {
+                        self.device.delete_texture(texture);
+                        return None;
+                    }
+                }
+            } else {
+                if self.device.update_texture(&texture, &img_info, text.level(level)).is_err() {
+                    self.device.delete_texture(texture);
+                    return None;
+                }
             }
         }
+        if generate {
+            self.device.generate_mipmap(&texture);
+        }
+        Some(texture)
+    }
+
+    /// samplers are shared by every texture with the same settings
+    fn sampler_handle(&mut self, sampler: Sampler) -> device::SamplerHandle {
+        if let Some(&handle) = self.samplers.get(&sampler) {
+            return handle;
+        }
+        let filter = match sampler.filter {
+            Filter::Nearest => gfx::tex::FilterMethod::Scale,
+            Filter::Bilinear => gfx::tex::FilterMethod::Bilinear,
+            Filter::Trilinear => gfx::tex::FilterMethod::Trilinear,
+            Filter::Anisotropic(n) => gfx::tex::FilterMethod::Anisotropic(n)
+        };
+        let wrap = match sampler.wrap {
+            Wrap::Repeat => gfx::tex::WrapMode::Tile,
+            Wrap::Mirror => gfx::tex::WrapMode::Mirror,
+            Wrap::Clamp => gfx::tex::WrapMode::Clamp
+        };
+        let handle = self.device.create_sampler(gfx::tex::SamplerInfo::new(filter, wrap));
+        self.samplers.insert(sampler, handle);
+        handle
+    }
+
+    fn texture_param(&self, oid: Entity) -> gfx::shade::TextureParam {
+        let &(texture, sampler) = self.textures.get(&oid).expect("Could not find texture");
+        (texture, Some(sampler))
     }
 
     fn load_materials<RD: Renderable+GetIoState>(&mut self, db: &RD) {
@@ -559,7 +1332,8 @@ impl RenderManagerContext {
             } else {None};
 
             if update == Some(true) {
-                self.material.remove(&oid);
+                let old = self.material.remove(&oid).unwrap();
+                self.device.delete_buffer(old.buffer);
             } else if update == Some(false) {
                 continue;
             }
@@ -574,6 +1348,11 @@ impl RenderManagerContext {
                 ka_use_texture: if mat.map_ka().is_some() {1} else {0},
                 kd_use_texture: if mat.map_kd().is_some() {1} else {0},
                 ks_use_texture: if mat.map_ks().is_some() {1} else {0},
+                d_use_texture: if mat.map_d().is_some() {1} else {0},
+                d: mat.d(),
+                norm_use_texture: if mat.map_norm().is_some() {1} else {0},
+                bump_use_texture: if mat.map_bump().is_some() {1} else {0},
+                bump_scale: mat.bump_scale(),
             }];
             let buff = self.device.create_buffer_static(material);
             self.material.insert(oid, RenderMaterial {
@@ -582,22 +1361,119 @@ impl RenderManagerContext {
                 ka_texture: mat.map_ka(),
                 ks_texture: mat.map_ks(),
                 kd_texture: mat.map_kd(),
+                d_texture: mat.map_d(),
+                norm_texture: mat.map_norm(),
+                bump_texture: mat.map_bump(),
             }); 
         }       
     }
 
+    fn load_pbr_materials<RD: Renderable+GetIoState>(&mut self, db: &RD) {
+        for (oid, &mat) in db.pbr_material_iter() {
+            if let Some(material) = self.pbr_material.get(&oid) {
+                if material.material == mat {
+                    continue;
+                }
+            }
+
+            let c = mat.base_color();
+            let e = mat.emissive();
+            let material = &[SharedPbrMaterial {
+                base_color: [c[0], c[1], c[2], mat.alpha()],
+                emissive: [e[0], e[1], e[2], 0.],
+                metallic: mat.metallic(),
+                roughness: mat.roughness(),
+                occlusion_strength: mat.occlusion_strength(),
+                normal_scale: mat.normal_scale(),
+                base_color_use_texture: if mat.map_base_color().is_some() {1} else {0},
+                metallic_roughness_use_texture: if mat.map_metallic_roughness().is_some() {1} else {0},
+                emissive_use_texture: if mat.map_emissive().is_some() {1} else {0},
+                occlusion_use_texture: if mat.map_occlusion().is_some() {1} else {0},
+                normal_use_texture: if mat.map_normal().is_some() {1} else {0},
+                alpha_cutoff: mat.alpha_cutoff(),
+            }];
+            let buff = self.device.create_buffer_static(material);
+            let old = self.pbr_material.insert(oid, RenderPbrMaterial {
+                material: mat,
+                buffer: buff,
+                base_color_texture: mat.map_base_color(),
+                metallic_roughness_texture: mat.map_metallic_roughness(),
+                emissive_texture: mat.map_emissive(),
+                occlusion_texture: mat.map_occlusion(),
+                normal_texture: mat.map_normal(),
+            });
+            if let Some(old) = old {
+                self.device.delete_buffer(old.buffer);
+            }
+        }
+    }
+
+    fn load_environment<RD: Renderable+GetIoState>(&mut self, db: &RD) {
+        // without an environment use a dim constant ambient, this is close
+        // to the ambient term of the MTL materials
+        let env = db.environment().map(|e| e.clone())
+                    .unwrap_or(Environment::uniform([0.2, 0.2, 0.2]));
+        // a reloaded map keeps its entity, so the revision is checked too
+        let current = match (env.map(), self.environment_map) {
+            (Some(id), Some((last, revision, _))) => id == last && revision == db.revision(id),
+            (None, _) => true,
+            (Some(_), None) => false
+        };
+        if current && self.environment.as_ref() == Some(&env) {
+            return;
+        }
+
+        // reflections need a full mip chain to approximate roughness, an
+        // image the device can not take leaves only the ambient light
+        let map = match (env.map(), self.environment_map) {
+            (Some(id), Some((_, revision, texture))) if current => Some((id, revision, texture)),
+            (Some(id), _) => db.get_texture(id).and_then(|text| {
+                let mut text = text.clone();
+                text.set_sampler(Sampler { wrap: Wrap::Repeat, filter: Filter::Trilinear });
+                self.try_upload_texture(&text).map(|texture| (id, db.revision(id), texture))
+            }),
+            (None, _) => None
+        };
+
+        let mut shared = SharedEnvironment {
+            irradiance: [[0.; 4]; pbr::SH_COEFFICIENTS],
+            environment_levels: 0,
+            environment_intensity: env.intensity()
+        };
+        for (i, c) in env.coefficients().iter().enumerate() {
+            shared.irradiance[i] = [c[0], c[1], c[2], 0.];
+        }
+        if let Some((id, _, texture)) = map {
+            let text = db.get_texture(id).unwrap();
+            shared.environment_levels = text.full_levels() as i32;
+            self.pbr_data.environment_map = (texture, Some(self.environment_sampler));
+        }
+        self.device.update_buffer(self.shared_environment, &[shared], 0);
+
+        if let Some((_, _, old)) = self.environment_map {
+            if map.map(|(_, _, texture)| texture) != Some(old) {
+                self.device.delete_texture(old);
+            }
+        }
+        self.environment_map = map;
+        self.environment = Some(env);
+    }
+
     fn load_batches<RD: Renderable+GetIoState>(&mut self, db: &RD) {
         let scene = db.scene().expect("no scene set");
-        self.batch.clear();
         self.shadow_batches.clear();
         self.draw_batches.clear();
         self.draw_back_batches.clear();
+        self.draw_blend_batches.clear();
+        self.draw_pbr_batches.clear();
+        self.draw_pbr_blend_batches.clear();
+        self.shadow_atlas_batches.clear();
+        self.geometry.clear();
 
-        for (id, draw) in db.scene_iter(scene).inner_join_map(db.drawable_iter()) {
-            self.batch.insert((draw.geometry, draw.material, id));
-
+        for (_, draw) in db.scene_iter(scene).inner_join_map(db.drawable_iter()) {
             if !self.shadow_batches.contains_key(&draw.geometry) {
                 let geo = db.geometry(draw.geometry).expect("failed to find geometry");
+                self.geometry.insert(draw.geometry, *geo);
                 let vb = self.meshes.get(&geo.vb).expect("Could not get vertex buffer");
 
                 let batch: RefBatch<ShadowParams> = self.context.make_batch(
@@ -626,6 +1502,45 @@ impl RenderManagerContext {
                 ).ok().expect("Failed to create batch.");
                 self.draw_batches.insert(draw.geometry, batch);
 
+                let batch: RefBatch<Params> = self.context.make_batch(
+                    &self.prog,
+                    &vb.mesh,
+                    gfx::Slice {
+                        start: geo.offset as u32,
+                        end: (geo.offset + geo.count) as u32,
+                        prim_type: gfx::PrimitiveType::TriangleList,
+                        kind: gfx::SliceKind::Index32(vb.index, 0)
+                    },
+                    &self.blend_state
+                ).ok().expect("Failed to create batch.");
+                self.draw_blend_batches.insert(draw.geometry, batch);
+
+                let batch: RefBatch<PbrParams> = self.context.make_batch(
+                    &self.pbr_prog,
+                    &vb.mesh,
+                    gfx::Slice {
+                        start: geo.offset as u32,
+                        end: (geo.offset + geo.count) as u32,
+                        prim_type: gfx::PrimitiveType::TriangleList,
+                        kind: gfx::SliceKind::Index32(vb.index, 0)
+                    },
+                    &self.state
+                ).ok().expect("Failed to create batch.");
+                self.draw_pbr_batches.insert(draw.geometry, batch);
+
+                let batch: RefBatch<PbrParams> = self.context.make_batch(
+                    &self.pbr_prog,
+                    &vb.mesh,
+                    gfx::Slice {
+                        start: geo.offset as u32,
+                        end: (geo.offset + geo.count) as u32,
+                        prim_type: gfx::PrimitiveType::TriangleList,
+                        kind: gfx::SliceKind::Index32(vb.index, 0)
+                    },
+                    &self.blend_state
+                ).ok().expect("Failed to create batch.");
+                self.draw_pbr_blend_batches.insert(draw.geometry, batch);
+
                 let batch: RefBatch<ShadowParams> = self.context.make_batch(
                     &self.back_prog,
                     &vb.mesh,
@@ -652,7 +1567,17 @@ impl RenderManagerContext {
         buffer
     }
 
-    fn load_matrices<RD: Renderable+GetIoState>(&mut self, db: &RD) {
+    fn recycle_matrices(&mut self) {
+        for m in self.used_matrix_buffers.drain() {
+            self.spare_matrix_buffers.push(m);
+        }
+    }
+
+    /// upload the matrices of the supplied drawables, the returned lists
+    /// are grouped by geometry, and by geometry and material
+    fn load_matrices<RD: Renderable+GetIoState>(&mut self, db: &RD, ids: &[Entity])
+        -> (Vec<(u32, device::BufferHandle<[[f32; 4]; 4]>, usize, usize)>,
+            Vec<(u32, u32, device::BufferHandle<[[f32; 4]; 4]>, usize, usize)>) {
         let max = 512;
         let mut matrices = Vec::new();
         matrices.reserve(512);
@@ -660,14 +1585,14 @@ impl RenderManagerContext {
         let mut shared_g = Vec::new();
         let mut shared_gm = Vec::new();
 
-        for m in self.used_matrix_buffers.drain() {
-            self.spare_matrix_buffers.push(m);
-        }
+        let batch: BTreeSet<(Entity, Entity, Entity)> = ids.iter().filter_map(|&id| {
+            db.drawable(id).map(|d| (d.geometry, d.material, id))
+        }).collect();
 
         let mut mat = self.fetch_matrix();
 
         let mut last = None;
-        for &(g, m, id) in self.batch.clone().iter() {
+        for &(g, m, id) in batch.iter() {
             last = if let Some((lg, lm, mut idx_gm, mut idx_g)) = last {
                 if (lg, lm) != (g, m) {
                     shared_gm.push((lg, lm, mat, matrices.len()-idx_gm, idx_gm));
@@ -701,56 +1626,414 @@ impl RenderManagerContext {
             self.device.update_buffer(mat, &matrices[], 0)
         }
 
-        self.shared_geometry = shared_g;
-        self.shared_geometry_material = shared_gm;
+        (shared_g, shared_gm)
     }
 
-    fn draw_shadow(&mut self, cam: &Camera) {
+    /// upload the matrices of the supplied drawables keeping their order,
+    /// neighbours that share a geometry and material are drawn together
+    fn load_sorted_matrices<RD: Renderable+GetIoState>(&mut self, db: &RD, ids: &[Entity])
+        -> Vec<(u32, u32, device::BufferHandle<[[f32; 4]; 4]>, usize, usize)> {
+        let max = 512;
+        let mut matrices = Vec::new();
+        matrices.reserve(512);
+
+        let mut out: Vec<(u32, u32, device::BufferHandle<[[f32; 4]; 4]>, usize, usize)> = Vec::new();
+        let mut mat = self.fetch_matrix();
+
+        for &id in ids.iter() {
+            let draw = match db.drawable(id) {
+                Some(draw) => draw,
+                None => continue
+            };
+
+            if matrices.len() == max {
+                self.device.update_buffer(mat, &matrices[], 0);
+                mat = self.fetch_matrix();
+                matrices.clear();
+            }
+
+            let merge = match out.last() {
+                // after switching buffers nothing ends at the current length
+                Some(&(g, m, _, len, offset)) => {
+                    (g, m) == (draw.geometry, draw.material) &&
+                    offset + len == matrices.len()
+                }
+                None => false
+            };
+
+            if merge {
+                out.last_mut().unwrap().3 += 1;
+            } else {
+                out.push((draw.geometry, draw.material, mat, 1, matrices.len()));
+            }
+            matrices.push(db.position(id).into_fixed());
+        }
+
+        if matrices.len() != 0 {
+            self.device.update_buffer(mat, &matrices[], 0);
+        }
+        out
+    }
+
+    fn cascades(&self, cam: &Camera) -> Vec<Cascade> {
+        let dir = self.data.light_normal;
+        let far = self.settings.shadow_distance.min(cam.far());
+        let splits = shadow::cascade_splits(cam.near(), far,
+                                            self.shadow_cascades.len(),
+                                            self.settings.cascade_lambda);
+        shadow::cascades(cam,
+                         Vector3::new(dir[0], dir[1], dir[2]),
+                         &splits[],
+                         self.settings.shadow_resolution)
+    }
+
+    fn draw_shadow(&mut self, cascades: &[Cascade]) {
         let cdata = gfx::ClearData {
             color: [0.3, 0.3, 0.3, 1.0],
             depth: 2.0,
             stencil: 0,
         };
-        self.render.clear(cdata, gfx::DEPTH, &self.shadow_frame);
-
-        let pos = cam.move_with_vector(&Vector3::new(0f32, 15., 0.));
-        let proj = cgmath::ortho(
-            -25.,  // + pos.x,
-             25.,  // + pos.x,
-            -25.,  // + pos.y,
-             25.,  // + pos.y,
-            -500., // + pos.z,
-             500., // + pos.z
+
+        let bias: Matrix4<f32> = Matrix4::new(
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 0.5, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.5, 0.5, 0.5, 1.0,
         );
 
-        let dir = self.data.light_normal;
+        let mut shared = SharedShadow {
+            shadow_mat: [Matrix4::identity().into_fixed(); MAX_CASCADES],
+            shadow_split: [0.; MAX_CASCADES]
+        };
+
+        for (i, (target, cascade)) in self.shadow_cascades.iter().zip(cascades.iter()).enumerate() {
+            let proj = cascade.matrices.projection;
+            let view = cascade.matrices.view;
+
+            shared.shadow_mat[i] = bias.mul_m(&proj).mul_m(&view).into_fixed();
+            shared.shadow_split[i] = cascade.far;
+
+            let shadow_mat = &[SharedMatrix {
+                proj_mat: proj.into_fixed(),
+                view_mat: view.into_fixed()
+            }];
+            self.device.update_buffer(target.matrix, shadow_mat, 0);
+
+            self.render.clear(cdata, gfx::DEPTH, &target.frame);
+            self.shadow_data.shared_mat = target.matrix.raw();
+            for &(geo, matrix, len, offset) in self.shared_geometry.iter() {
+                self.shadow_data.model = matrix.raw();
+                self.shadow_data.offset = offset as i32;
+                self.render.draw_instanced(
+                    &(self.shadow_batches.get(&geo).expect("Missing draw"),
+                      &self.shadow_data,
+                      &self.context),
+                    len as u32,
+                    0,
+                    &target.frame,
+                );
+            };
+        }
+
+        self.device.update_buffer(self.shadow_shared_mat, &[shared], 0);
+
+        // unused cascades are never sampled, but still need a texture bound
+        let textures: Vec<device::TextureHandle> = (0..MAX_CASCADES).map(|i| {
+            let i = if i < self.shadow_cascades.len() { i } else { 0 };
+            self.shadow_cascades[i].texture
+        }).collect();
+        self.data.shadow0 = (textures[0], Some(self.shadow_sampler));
+        self.data.shadow1 = (textures[1], Some(self.shadow_sampler));
+        self.data.shadow2 = (textures[2], Some(self.shadow_sampler));
+        self.data.shadow3 = (textures[3], Some(self.shadow_sampler));
+    }
+
+    fn atlas_batch(&mut self, geo: Entity, tile: usize) -> RefBatch<ShadowParams> {
+        if !self.shadow_atlas_batches.contains_key(&(geo, tile)) {
+            // each batch is clipped to its tile so that geometry outside of
+            // the light's frustum does not land in a neighbouring tile
+            let (x, y, w, h) = shadow::tile_rect(tile, self.settings.shadow_atlas_resolution);
+            let mut state = self.state.clone();
+            state.scissor = Some(device::target::Rect {x: x, y: y, w: w, h: h});
+
+            let g = *self.geometry.get(&geo).expect("failed to find geometry");
+            let vb = self.meshes.get(&g.vb).expect("Could not get vertex buffer");
+            let batch: RefBatch<ShadowParams> = self.context.make_batch(
+                &self.shadow_prog,
+                &vb.mesh,
+                gfx::Slice {
+                    start: g.offset as u32,
+                    end: (g.offset + g.count) as u32,
+                    prim_type: gfx::PrimitiveType::TriangleList,
+                    kind: gfx::SliceKind::Index32(vb.index, 0)
+                },
+                &state
+            ).ok().expect("Failed to create batch.");
+            self.shadow_atlas_batches.insert((geo, tile), batch);
+        }
+        *self.shadow_atlas_batches.get(&(geo, tile)).unwrap()
+    }
 
-        let view: Matrix4<f32> = cgmath::Matrix4::look_at(
-            &pos.add_v(&Vector3::new(dir[0], dir[1], dir[2])),
-            &pos,
-            &Vector3::new(0f32, 1., 0.)
+    fn draw_shadow_atlas(&mut self, tiles: &[(usize, DrawMatrices)]) -> Vec<Matrix4<f32>> {
+        let cdata = gfx::ClearData {
+            color: [0.3, 0.3, 0.3, 1.0],
+            depth: 1.0,
+            stencil: 0,
+        };
+        self.render.clear(cdata, gfx::DEPTH, &self.shadow_atlas_frame);
+
+        let bias: Matrix4<f32> = Matrix4::new(
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 0.5, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.5, 0.5, 0.5, 1.0,
         );
 
-        let shadow_mat = &[SharedMatrix {
-            proj_mat: proj.into_fixed(),
-            view_mat: view.into_fixed()
-        }];
+        // `atlas_batch` needs all of self, so the casters are moved out
+        // while the tiles are drawn
+        let geometry = mem::replace(&mut self.shared_geometry, Vec::new());
+        let mut out: Vec<Matrix4<f32>> = repeat(Matrix4::identity()).take(ATLAS_TILES).collect();
+        for &(tile, ref m) in tiles.iter() {
+            let proj = shadow::tile_matrix(tile).mul_m(&m.projection);
+            out[tile] = bias.mul_m(&proj).mul_m(&m.view);
+
+            let buffer = self.shadow_atlas_tiles[tile];
+            self.device.update_buffer(buffer, &[SharedMatrix {
+                proj_mat: proj.into_fixed(),
+                view_mat: m.view.into_fixed()
+            }], 0);
+
+            self.shadow_data.shared_mat = buffer.raw();
+            for &(geo, matrix, len, offset) in geometry.iter() {
+                let batch = self.atlas_batch(geo, tile);
+                self.shadow_data.model = matrix.raw();
+                self.shadow_data.offset = offset as i32;
+                self.render.draw_instanced(
+                    &(&batch, &self.shadow_data, &self.context),
+                    len as u32,
+                    0,
+                    &self.shadow_atlas_frame,
+                );
+            }
+        }
+        self.shared_geometry = geometry;
+        out
+    }
+
+    /// gather the lights in the scene, the returned tiles still need to be
+    /// drawn into the shadow atlas before the lights are uploaded
+    fn load_lights<RD: Renderable+GetIoState>(&mut self, db: &RD, camera: &Camera)
+        -> (SharedLights, Vec<(usize, DrawMatrices)>) {
+        let origin = Vector4::new(0f32, 0., 0., 1.);
+        let mut lights = Vec::new();
+        self.data.shadow_count = 0;
+        for (key, light) in db.light_iter() {
+            match light {
+                &graphics::Light::Point(_) | &graphics::Light::Spot(_) => {
+                    if lights.len() < MAX_LIGHTS {
+                        lights.push((key, *light));
+                    }
+                }
+                &graphics::Light::Directional(d) => {
+                    let n = d.normal();
+                    let n = Vector4::new(n.x, n.y, n.z, 0.);
+                    let n = db.position(key).mul_v(&n).normalize();
+                    let color = d.color().mul_s(d.intensity());
+                    self.data.light_color = [color.x, color.y, color.z, 1.];
+                    self.data.light_normal = [n.x, n.y, n.z, n.w];
+                    self.data.shadow_count = if d.casts_shadows() {
+                        self.shadow_cascades.len() as i32
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+
+        let eye = camera.origin();
+        let position = |&: key: Entity| {
+            let p = db.position(key).mul_v(&origin);
+            Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        };
+
+        let shadowed: Vec<(Entity, ShadowKind, f32)> = lights.iter()
+            .filter(|&&(_, ref l)| l.casts_shadows())
+            .map(|&(key, ref l)| {
+                let kind = match *l {
+                    graphics::Light::Spot(_) => ShadowKind::Spot,
+                    _ => ShadowKind::Point
+                };
+                (key, kind, position(key).sub_p(&eye).length())
+            }).collect();
+        let slots = shadow::allocate_atlas(&shadowed[]);
+
+        let mut shared = SharedLights {
+            light_position: [[0.; 4]; MAX_LIGHTS],
+            light_direction: [[0.; 4]; MAX_LIGHTS],
+            light_point_color: [[0.; 4]; MAX_LIGHTS],
+            light_shadow: [[-1., 0., 0., 0.]; MAX_LIGHTS],
+            shadow_tile_mat: [Matrix4::identity().into_fixed(); ATLAS_TILES]
+        };
+        let mut tiles = Vec::new();
+
+        for (i, &(key, light)) in lights.iter().enumerate() {
+            let p = position(key);
+            let slot = slots.iter().find(|s| s.light == key);
+
+            let (color, range) = match light {
+                graphics::Light::Point(l) => {
+                    shared.light_direction[i] = [0., 0., 0., -2.];
+                    if let Some(slot) = slot {
+                        for (face, m) in shadow::point_matrices(p, l.range()).iter().enumerate() {
+                            tiles.push((slot.tile + face, *m));
+                        }
+                    }
+                    (l.color().mul_s(l.intensity()), l.range())
+                }
+                graphics::Light::Spot(l) => {
+                    let n = l.normal();
+                    let n = db.position(key).mul_v(&Vector4::new(n.x, n.y, n.z, 0.));
+                    let n = Vector3::new(n.x, n.y, n.z).normalize();
+                    shared.light_direction[i] = [n.x, n.y, n.z, l.angle().cos()];
+                    if let Some(slot) = slot {
+                        tiles.push((slot.tile, shadow::spot_matrices(p, n, l.angle(), l.range())));
+                    }
+                    (l.color().mul_s(l.intensity()), l.range())
+                }
+                graphics::Light::Directional(_) => unreachable!()
+            };
+
+            shared.light_position[i] = [p.x, p.y, p.z, range];
+            shared.light_point_color[i] = [color.x, color.y, color.z, 1.];
+            if let Some(slot) = slot {
+                shared.light_shadow[i] = [slot.tile as f32, 0., 0., 0.];
+            }
+        }
+
+        self.data.light_count = lights.len() as i32;
+        self.data.shadow_atlas = (self.shadow_atlas, Some(self.shadow_sampler));
+        (shared, tiles)
+    }
 
-        self.device.update_buffer(self.shadow_shared_mat, shadow_mat, 0);
+    fn bind_pbr_material(&mut self, mat: Entity) {
+        let mat = self.pbr_material.get(&mat).expect("Could not find material");
+        if let Some(t) = mat.base_color_texture {
+            self.pbr_data.base_color_texture = self.texture_param(t);
+        }
+        if let Some(t) = mat.metallic_roughness_texture {
+            self.pbr_data.metallic_roughness_texture = self.texture_param(t);
+        }
+        if let Some(t) = mat.emissive_texture {
+            self.pbr_data.emissive_texture = self.texture_param(t);
+        }
+        if let Some(t) = mat.occlusion_texture {
+            self.pbr_data.occlusion_texture = self.texture_param(t);
+        }
+        if let Some(t) = mat.normal_texture {
+            self.pbr_data.normal_texture = self.texture_param(t);
+        }
+        self.pbr_data.pbr_material = mat.buffer.raw();
+    }
+
+    /// the physically based program shares the lights and shadows with
+    /// the MTL program
+    fn sync_pbr_data(&mut self, eye: Point3<f32>) {
+        self.pbr_data.eye_position = [eye.x, eye.y, eye.z, 1.];
+        self.pbr_data.light_normal = self.data.light_normal;
+        self.pbr_data.light_color = self.data.light_color;
+        self.pbr_data.light_count = self.data.light_count;
+        self.pbr_data.shadow_atlas = self.data.shadow_atlas;
+        self.pbr_data.shadow_count = self.data.shadow_count;
+        self.pbr_data.shadow0 = self.data.shadow0;
+        self.pbr_data.shadow1 = self.data.shadow1;
+        self.pbr_data.shadow2 = self.data.shadow2;
+        self.pbr_data.shadow3 = self.data.shadow3;
+    }
+
+    /// draw instances of a geometry using the program its material needs
+    fn draw_surface(&mut self,
+                    geo: Entity,
+                    mat: Entity,
+                    matrix: device::BufferHandle<[[f32; 4]; 4]>,
+                    len: usize,
+                    offset: usize,
+                    blend: bool) {
+        if self.pbr_material.contains_key(&mat) {
+            self.bind_pbr_material(mat);
+            self.pbr_data.model = matrix.raw();
+            self.pbr_data.offset = offset as i32;
+
+            let batches = if blend { &self.draw_pbr_blend_batches } else { &self.draw_pbr_batches };
+            self.render.draw_instanced(
+                &(batches.get(&geo).expect("Missing draw"),
+                  &self.pbr_data,
+                  &self.context),
+                len as u32,
+                0,
+                &self.frame,
+            );
+        } else {
+            self.bind_material(mat);
+            self.data.model = matrix.raw();
+            self.data.offset = offset as i32;
 
-        for &(geo, matrix, len, offset) in self.shared_geometry.iter() {
-            self.shadow_data.model = matrix.raw();
-            self.shadow_data.offset = offset as i32;
+            let batches = if blend { &self.draw_blend_batches } else { &self.draw_batches };
             self.render.draw_instanced(
-                &(self.shadow_batches.get(&geo).expect("Missing draw"),
-                  &self.shadow_data,
+                &(batches.get(&geo).expect("Missing draw"),
+                  &self.data,
                   &self.context),
                 len as u32,
                 0,
-                &self.shadow_frame,
+                &self.frame,
             );
+        }
+    }
+
+    fn bind_material(&mut self, mat: Entity) {
+        let mat = self.material.get(&mat).expect("Could not find material");
+        if let Some(ka) = mat.ka_texture {
+            self.data.ka_texture = self.texture_param(ka);
+        }
+        if let Some(kd) = mat.kd_texture {
+            self.data.kd_texture = self.texture_param(kd);
+        }
+        if let Some(ks) = mat.ks_texture {
+            self.data.ks_texture = self.texture_param(ks);
+        }
+        if let Some(d) = mat.d_texture {
+            self.data.d_texture = self.texture_param(d);
+        }
+        if let Some(norm) = mat.norm_texture {
+            self.data.norm_texture = self.texture_param(norm);
+        }
+        if let Some(bump) = mat.bump_texture {
+            self.data.bump_texture = self.texture_param(bump);
+        }
+        self.data.material = mat.buffer.raw();
+    }
+
+    /// the sky is drawn after the opaque surfaces so only the pixels they
+    /// did not cover are shaded, blended surfaces are drawn over it
+    fn draw_skybox<RD: Renderable+GetIoState>(&mut self, db: &RD, sky: Skybox,
+                                              proj: &Matrix4<f32>, view: &Matrix4<f32>) {
+        let cube = match db.get_texture(sky.texture) {
+            Some(text) => text.kind() == Kind::Cube,
+            None => return
+        };
+
+        // only the rotation of the camera changes the direction
+        let mut rotation = *view;
+        rotation.w = Vector4::new(0., 0., 0., 1.);
+        let inverse = match proj.mul_m(&rotation).invert() {
+            Some(m) => m,
+            None => return
         };
 
+        self.sky_data.sky_inverse = inverse.into_fixed();
+        self.sky_data.sky_intensity = sky.intensity;
+        self.sky_data.sky_map = self.texture_param(sky.texture);
+        let batch = if cube { &self.sky_cube_batch } else { &self.sky_equirect_batch };
+        self.render.draw(&(batch, &self.sky_data, &self.context), &self.frame);
     }
 
     fn draw<RD: Renderable+GetIoState>(&mut self, db: &RD) {
@@ -777,21 +2060,43 @@ impl RenderManagerContext {
 
         self.device.update_buffer(self.shared_mat, shared_mat, 0);
 
-        for (key, light) in db.light_iter() {
-            match light {
-                &graphics::Light::Point(_) => {}
-                &graphics::Light::Directional(d) => {
-                    let n = d.normal();
-                    let n = Vector4::new(n.x, n.y, n.z, 0.);
-                    let n = db.position(key).mul_v(&n).normalize();
-                    let color = d.color().mul_s(d.intensity());
-                    self.data.light_color = [color.x, color.y, color.z, 1.];
-                    self.data.light_normal = [n.x, n.y, n.z, n.w];
-                }
-            }
+        let (mut lights, tiles) = self.load_lights(db, &camera);
+        let cascades = if self.data.shadow_count != 0 {
+            let cascades = self.cascades(&camera);
+            // only the cascades that could be fitted are sampled
+            self.data.shadow_count = cascades.len() as i32;
+            cascades
+        } else {
+            Vec::new()
+        };
+
+        // the main pass only needs what the camera can see, while the
+        // shadow passes need everything inside of any of the light volumes
+        self.recycle_matrices();
+        let visible = cull::visible(db, &[Frustum::from_matrix(&proj.mul_m(&view))]);
+        let (opaque, mut transparent) = cull::partition_transparent(db, &visible[]);
+        let (_, shared_gm) = self.load_matrices(db, &opaque[]);
+        self.shared_geometry_material = shared_gm;
+        cull::sort_back_to_front(db, &mut transparent[], camera.origin());
+        let sorted = self.load_sorted_matrices(db, &transparent[]);
+
+        let light_frusta: Vec<Frustum> = cascades.iter()
+            .map(|c| &c.matrices)
+            .chain(tiles.iter().map(|&(_, ref m)| m))
+            .map(|m| Frustum::from_matrix(&m.projection.mul_m(&m.view)))
+            .collect();
+        let casters = cull::visible(db, &light_frusta[]);
+        let (shared_g, _) = self.load_matrices(db, &casters[]);
+        self.shared_geometry = shared_g;
+
+        for (i, m) in self.draw_shadow_atlas(&tiles[]).iter().enumerate() {
+            lights.shadow_tile_mat[i] = m.into_fixed();
         }
+        self.device.update_buffer(self.shared_lights, &[lights], 0);
 
-        self.draw_shadow(&camera);
+        if cascades.len() != 0 {
+            self.draw_shadow(&cascades[]);
+        }
 
         for &(geo, _, matrix, len, offset) in self.shared_geometry_material.iter() {
             self.back_data.model = matrix.raw();
@@ -807,39 +2112,18 @@ impl RenderManagerContext {
             );
         };
 
-        for &(geo, mat, matrix, len, offset) in self.shared_geometry_material.iter() {
-            let mat = self.material.get(&mat).expect("Could not find material");
-            if let Some(ka) = mat.ka_texture {
-                self.data.ka_texture =
-                    (*self.textures.get(&ka)
-                          .expect("Could not find texture"),
-                     Some(self.sampler));
-            }
-            if let Some(kd) = mat.kd_texture {
-                self.data.kd_texture =
-                    (*self.textures.get(&kd)
-                          .expect("Could not find texture"),
-                     Some(self.sampler));
-            }
-            if let Some(ks) = mat.ks_texture {
-                self.data.ks_texture =
-                    (*self.textures.get(&ks)
-                          .expect("Could not find texture"),
-                     Some(self.sampler));
-            }
-            self.data.material = mat.buffer.raw();
-            self.data.shadow = (self.shadow, Some(self.shadow_sampler));
-            self.data.model = matrix.raw();
-            self.data.offset = offset as i32;
+        self.sync_pbr_data(camera.origin());
+        for &(geo, mat, matrix, len, offset) in self.shared_geometry_material.clone().iter() {
+            self.draw_surface(geo, mat, matrix, len, offset, false);
+        };
 
-            self.render.draw_instanced(
-                &(self.draw_batches.get(&geo).expect("Missing draw"),
-                  &self.data,
-                  &self.context),
-                len as u32,
-                0,
-                &self.frame,
-            );
+        if let Some(sky) = db.skybox() {
+            self.draw_skybox(db, sky, &proj, &view);
+        }
+
+        // blended surfaces are drawn last, furthest first
+        for &(geo, mat, matrix, len, offset) in sorted.iter() {
+            self.draw_surface(geo, mat, matrix, len, offset, true);
         };
 
         self.device.submit(self.render.as_buffer());
@@ -860,8 +2144,9 @@ impl RenderManagerContext {
         self.load_meshes(&db);
         self.load_textures(&db);
         self.load_materials(&db);
+        self.load_pbr_materials(&db);
+        self.load_environment(&db);
         self.load_batches(&db);
-        self.load_matrices(&db);
         self.draw(&db);
     }
 }
@@ -880,6 +2165,7 @@ impl<RD: Renderable+GetIoState+Send> sm_render::RenderFactory<RD, RenderManager<
             size: (i32, i32),
             _: Option<Arc<hl::Device>>) -> RenderManager<RD> {
 
+        let settings = self.settings;
         let (sender, recv) = channel();
         window.make_context_current();
         let device = gfx::GlDevice::new(|s| io.get_proc_address(s));
@@ -897,7 +2183,7 @@ impl<RD: Renderable+GetIoState+Send> sm_render::RenderFactory<RD, RenderManager<
             window.make_context_current();
             let recv: Receiver<RD> = recv;
 
-            let mut rc = RenderManagerContext::_new(device, window, size);
+            let mut rc = RenderManagerContext::_new(device, window, size, settings);
             loop {
                 // wait for a copy of the game
                 let mut db = match recv.recv() {
@@ -934,6 +2220,7 @@ impl<RD: Renderable+GetIoState+Send> sm_render::RenderFactory<RD, RenderManager<
             mut window: Window,
             size: (i32, i32)) -> RenderManager<RD> {
 
+        let settings = self.settings;
         let (sender, recv) = channel();
         window.make_context_current();
         let device = gfx::GlDevice::new(|s| io.get_proc_address(s));
@@ -951,7 +2238,7 @@ impl<RD: Renderable+GetIoState+Send> sm_render::RenderFactory<RD, RenderManager<
             window.make_context_current();
             let recv: Receiver<RD> = recv;
 
-            let mut rc = RenderManagerContext::_new(device, window, size);
+            let mut rc = RenderManagerContext::_new(device, window, size, settings);
             loop {
                 // wait for a copy of the game
                 let mut db = match recv.recv() {
@@ -981,9 +2268,17 @@ impl<RD: Renderable+GetIoState+Send> sm_render::RenderFactory<RD, RenderManager<
     }
 }
 
-#[derive(Copy)]
-pub struct RenderFactory;
+#[derive(Copy, Clone)]
+pub struct RenderFactory {
+    settings: RenderSettings
+}
 
 impl RenderFactory {
-    pub fn new() -> RenderFactory { RenderFactory }
+    pub fn new() -> RenderFactory {
+        RenderFactory::with_settings(RenderSettings::new())
+    }
+
+    pub fn with_settings(settings: RenderSettings) -> RenderFactory {
+        RenderFactory { settings: settings }
+    }
 }
\ No newline at end of file
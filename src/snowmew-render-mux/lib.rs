@@ -32,7 +32,7 @@ use std::sync::Arc;
 
 use input::{Window, GetIoState};
 
-use render::Renderable;
+use render::{Renderable, RenderSettings};
 
 impl<'r, RD: Renderable+GetIoState+Send> render::Render<RD> for RenderMux<'r, RD> {
     fn update(&mut self, db: RD) {
@@ -53,7 +53,7 @@ impl<'r, RD: Renderable+GetIoState+Send> render::RenderFactory<RD, RenderMux<'r,
             cl: Option<Arc<Device>>) -> RenderMux<'r, RD> {
 
         let rm: RenderMux<RD> = {
-            let rf: Box<render::RenderFactory<RD, gfx::RenderManager<RD>>> = Box::new(gfx::RenderFactory::new());
+            let rf: Box<render::RenderFactory<RD, gfx::RenderManager<RD>>> = Box::new(gfx::RenderFactory::with_settings(self.settings));
             let render: Box<gfx::RenderManager<RD>> = Box::new(rf.init(io, window, size, cl));
             RenderMux {
                 render: render as Box<render::Render<RD>>
@@ -71,7 +71,7 @@ impl<'r, RD: Renderable+GetIoState+Send> render::RenderFactory<RD, RenderMux<'r,
             size: (i32, i32)) -> RenderMux<'r, RD> {
 
         let rm: RenderMux<RD> = {
-            let rf: Box<render::RenderFactory<RD, gfx::RenderManager<RD>>> = Box::new(gfx::RenderFactory::new());
+            let rf: Box<render::RenderFactory<RD, gfx::RenderManager<RD>>> = Box::new(gfx::RenderFactory::with_settings(self.settings));
             let render: Box<gfx::RenderManager<RD>> = Box::new(rf.init(io, window, size));
             RenderMux {
                 render: render as Box<render::Render<RD>>
@@ -82,9 +82,17 @@ impl<'r, RD: Renderable+GetIoState+Send> render::RenderFactory<RD, RenderMux<'r,
 }
 
 
-#[derive(Copy)]
-pub struct RenderFactory;
+#[derive(Copy, Clone)]
+pub struct RenderFactory {
+    settings: RenderSettings
+}
 
 impl RenderFactory {
-    pub fn new() -> RenderFactory { RenderFactory }
+    pub fn new() -> RenderFactory {
+        RenderFactory::with_settings(RenderSettings::new())
+    }
+
+    pub fn with_settings(settings: RenderSettings) -> RenderFactory {
+        RenderFactory { settings: settings }
+    }
 }
\ No newline at end of file
@@ -21,6 +21,9 @@ use cgmath::deg;
 // use ovr;
 use ovr::{EyeRenderDescriptor, FovPort, Pose,};
 
+const NEAR: f32 = 0.01;
+const FAR: f32 = 10000.;
+
 #[derive(Copy)]
 /// Camera can be used to do Camera like actions
 pub struct Camera {
@@ -63,11 +66,47 @@ impl Camera {
 
     /// Create a perspective matrix for the Camera
     pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix_range(NEAR, FAR)
+    }
+
+    /// Create a perspective matrix for the Camera that is clipped
+    /// to `near` and `far` instead of the default clipping planes
+    pub fn projection_matrix_range(&self, near: f32, far: f32) -> Matrix4<f32> {
         perspective(
-            deg(80f32), self.width / self.height, 0.01f32, 10000f32
+            deg(80f32), self.width / self.height, near, far
         )
     }
 
+    /// The distance to the near clipping plane
+    pub fn near(&self) -> f32 { NEAR }
+
+    /// The distance to the far clipping plane
+    pub fn far(&self) -> f32 { FAR }
+
+    /// Calculate the eight world space corners of the slice of the
+    /// view frustum that lies between `near` and `far`, this is `None`
+    /// if the projection is degenerate and can not be inverted
+    pub fn frustum_corners(&self, near: f32, far: f32) -> Option<[Point3<f32>; 8]> {
+        let proj = self.projection_matrix_range(near, far);
+        let inv = match proj.mul_m(&self.view_matrix()).invert() {
+            Some(inv) => inv,
+            None => return None
+        };
+
+        let mut out = [Point3::new(0f32, 0., 0.); 8];
+        let mut i = 0;
+        for &z in [-1f32, 1.].iter() {
+            for &y in [-1f32, 1.].iter() {
+                for &x in [-1f32, 1.].iter() {
+                    let p = inv.mul_v(&Vector4::new(x, y, z, 1.));
+                    out[i] = Point3::new(p.x/p.w, p.y/p.w, p.z/p.w);
+                    i += 1;
+                }
+            }
+        }
+        Some(out)
+    }
+
     /// Create a view matrix for the Camera
     pub fn view_matrix(&self) -> Matrix4<f32> {
         view_matrix(&self.transform)
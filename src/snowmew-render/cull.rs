@@ -0,0 +1,140 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::num::Float;
+use std::cmp::Ordering;
+
+use cgmath::{Matrix, Matrix4};
+use cgmath::{Vector, Vector3, Vector4, EuclideanVector};
+use cgmath::{Point, Point3};
+use collision::sphere::Sphere;
+use collect::iter::OrderedSetIterator;
+
+use snowmew::Entity;
+use position::Positions;
+use graphics::Graphics;
+use Renderable;
+
+#[derive(Copy, Clone)]
+/// The six clipping planes of a view volume, each plane is stored as
+/// `(a, b, c, d)` with the normal pointing into the volume
+pub struct Frustum {
+    planes: [Vector4<f32>; 6]
+}
+
+fn normalize_plane(p: Vector4<f32>) -> Vector4<f32> {
+    let len = Vector3::new(p.x, p.y, p.z).length();
+    p.div_s(len)
+}
+
+impl Frustum {
+    /// Extract the planes from a combined projection * view matrix
+    pub fn from_matrix(m: &Matrix4<f32>) -> Frustum {
+        let row = |&: i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        Frustum {
+            planes: [
+                normalize_plane(r3.add_v(&r0)),
+                normalize_plane(r3.sub_v(&r0)),
+                normalize_plane(r3.add_v(&r1)),
+                normalize_plane(r3.sub_v(&r1)),
+                normalize_plane(r3.add_v(&r2)),
+                normalize_plane(r3.sub_v(&r2))
+            ]
+        }
+    }
+
+    /// Check to see if any part of the sphere is inside of the volume
+    pub fn contains_sphere(&self, sphere: &Sphere<f32>) -> bool {
+        let c = &sphere.center;
+        self.planes.iter().all(|p| {
+            p.x * c.x + p.y * c.y + p.z * c.z + p.w >= -sphere.radius
+        })
+    }
+}
+
+/// Move a bounding sphere from model space into world space
+pub fn world_sphere(m: &Matrix4<f32>, sphere: &Sphere<f32>) -> Sphere<f32> {
+    let c = m.mul_v(&Vector4::new(sphere.center.x, sphere.center.y, sphere.center.z, 1.));
+    let scale = Vector3::new(m.x.x, m.x.y, m.x.z).length()
+        .max(Vector3::new(m.y.x, m.y.y, m.y.z).length())
+        .max(Vector3::new(m.z.x, m.z.y, m.z.z).length());
+
+    Sphere::new(Point3::new(c.x / c.w, c.y / c.w, c.z / c.w), sphere.radius * scale)
+}
+
+/// Find every drawable in the current scene that is inside at least one
+/// of the supplied view volumes. A geometry without vertices has an empty
+/// sphere at its origin. The result is ordered by `Entity`.
+pub fn visible<R: Renderable>(db: &R, frusta: &[Frustum]) -> Vec<Entity> {
+    let scene = match db.scene() {
+        Some(scene) => scene,
+        None => return Vec::new()
+    };
+
+    db.scene_iter(scene)
+      .inner_join_map(db.drawable_iter())
+      .filter(|&(id, draw)| {
+          let sphere = world_sphere(&db.position(id), &db.sphere(draw.geometry));
+          frusta.iter().any(|f| f.contains_sphere(&sphere))
+      })
+      .map(|(id, _)| id)
+      .collect()
+}
+
+/// Split the drawables into those that can be drawn opaque, and those
+/// whose material needs to be blended
+pub fn partition_transparent<R: Renderable>(db: &R, ids: &[Entity]) -> (Vec<Entity>, Vec<Entity>) {
+    ids.iter().map(|&id| id).partition(|&id| {
+        match db.drawable(id) {
+            Some(d) => {
+                if let Some(m) = db.material(d.material) {
+                    !m.is_transparent()
+                } else if let Some(m) = db.pbr_material(d.material) {
+                    !m.is_transparent()
+                } else {
+                    true
+                }
+            }
+            None => true
+        }
+    })
+}
+
+/// A total order for distances, NaN is placed after every number
+pub fn distance_order(a: f32, b: f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Sort the drawables so that the furthest from `eye` comes first. The
+/// distance is measured to the center of each drawable's bounding sphere,
+/// drawables with a NaN distance are placed last.
+pub fn sort_back_to_front<R: Renderable>(db: &R, ids: &mut [Entity], eye: Point3<f32>) {
+    let distance = |&: id: Entity| {
+        let center = db.drawable(id)
+            .map(|d| db.sphere(d.geometry).center)
+            .unwrap_or(Point3::new(0f32, 0., 0.));
+        let sphere = world_sphere(&db.position(id), &Sphere::new(center, 0.));
+        sphere.center.sub_p(&eye).length()
+    };
+
+    // negating keeps NaN last while the numbers are sorted high to low
+    ids.sort_by(|&a, &b| distance_order(-distance(a), -distance(b)));
+}
@@ -18,6 +18,8 @@
 
 extern crate "rustc-serialize" as rustc_serialize;
 extern crate cgmath;
+extern crate collision;
+extern crate collect;
 extern crate ovr;
 
 extern crate "snowmew-core" as snowmew;
@@ -30,21 +32,104 @@ extern crate opencl;
 
 #[cfg(feature="use_opencl")]
 use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::cmp::{min, max};
 
 /// contains utility functions for managing a camera
 pub mod camera;
+/// contains the math for fitting shadow maps to the camera
+pub mod shadow;
+/// contains renderer independent visibility tests and draw ordering
+pub mod cull;
 
+/// The maximum number of cascades a directional light can be split into
+pub const MAX_CASCADES: usize = 4;
+
+/// The largest shadow map or shadow atlas in texels, the size of a
+/// texture is stored in 16 bits
+pub const MAX_SHADOW_RESOLUTION: u32 = 65535;
+
+#[derive(Copy, Clone)]
+/// Tunables for the render, these are fixed when the render is created
+pub struct RenderSettings {
+    /// The width and height in texels of each shadow map
+    pub shadow_resolution: u32,
+    /// The number of cascades used for the directional light,
+    /// this may not exceed `MAX_CASCADES`
+    pub shadow_cascades: usize,
+    /// How far from the camera shadows are drawn
+    pub shadow_distance: f32,
+    /// Blend between uniform (0.) and logarithmic (1.) cascade splits
+    pub cascade_lambda: f32,
+    /// The width and height in texels of the atlas used for point and
+    /// spot light shadows
+    pub shadow_atlas_resolution: u32
+}
+
+impl RenderSettings {
+    /// Create the default settings
+    pub fn new() -> RenderSettings {
+        RenderSettings {
+            shadow_resolution: 2048,
+            shadow_cascades: MAX_CASCADES,
+            shadow_distance: 150.,
+            cascade_lambda: 0.75,
+            shadow_atlas_resolution: 4096
+        }
+    }
+
+    /// Bring the settings into the range the render supports, the
+    /// cascades are kept between 1 and `MAX_CASCADES` and the
+    /// resolutions between 1 and `MAX_SHADOW_RESOLUTION`
+    pub fn clamped(mut self) -> RenderSettings {
+        self.shadow_cascades = max(1, min(self.shadow_cascades, MAX_CASCADES));
+        self.shadow_resolution = max(1, min(self.shadow_resolution, MAX_SHADOW_RESOLUTION));
+        self.shadow_atlas_resolution = max(1, min(self.shadow_atlas_resolution, MAX_SHADOW_RESOLUTION));
+        self
+    }
+}
+
+/// A texture drawn behind everything in the scene
+#[derive(Clone, RustcEncodable, RustcDecodable, Copy, PartialEq, Debug)]
+pub struct Skybox {
+    /// a cube map, or an equirectangular 2D texture
+    pub texture: snowmew::Entity,
+    /// multiplier applied to the texture
+    pub intensity: f32
+}
+
+impl Skybox {
+    pub fn new(texture: snowmew::Entity) -> Skybox {
+        Skybox {
+            texture: texture,
+            intensity: 1.
+        }
+    }
+}
+
+/// What an extra window shows
 #[derive(Clone, RustcEncodable, RustcDecodable, Copy)]
+pub struct View {
+    pub camera: snowmew::Entity,
+    pub scene: snowmew::common::Scene
+}
+
+#[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct RenderData {
     camera: Option<snowmew::Entity>,
-    scene: Option<snowmew::common::Scene>
+    scene: Option<snowmew::common::Scene>,
+    skybox: Option<Skybox>,
+    /// keyed by `WindowConfig::id`
+    views: BTreeMap<usize, View>
 }
 
 impl RenderData {
     pub fn new() -> RenderData {
         RenderData {
             camera: None,
-            scene: None
+            scene: None,
+            skybox: None,
+            views: BTreeMap::new()
         }
     }
 }
@@ -72,6 +157,31 @@ pub trait Renderable: graphics::Graphics + position::Positions {
     fn scene(&self) -> Option<snowmew::common::Scene> {
         self.get_render_data().scene
     }
+
+    /// set the skybox, `None` clears the background to a flat colour
+    fn set_skybox(&mut self, skybox: Option<Skybox>) {
+        self.get_render_data_mut().skybox = skybox;
+    }
+
+    /// get the skybox for rendering
+    fn skybox(&self) -> Option<Skybox> {
+        self.get_render_data().skybox
+    }
+
+    /// set what the extra window with the `WindowConfig::id` of `window`
+    /// shows, without a view it shows the same as the main window
+    fn set_view(&mut self, window: usize, view: Option<View>) {
+        let views = &mut self.get_render_data_mut().views;
+        match view {
+            Some(view) => { views.insert(window, view); }
+            None => { views.remove(&window); }
+        }
+    }
+
+    /// get the view of an extra window
+    fn view(&self, window: usize) -> Option<View> {
+        self.get_render_data().views.get(&window).map(|&v| v)
+    }
 }
 
 /// Render is a trait that describes the describes how a render is implemented
@@ -99,6 +209,40 @@ pub trait RenderFactory<T, R: Render<T>> {
             size: (i32, i32)) -> R;
 }
 
+/// A render that draws nothing, it is used to run the engine without
+/// a display
+#[derive(Copy, Clone)]
+pub struct NullRender;
+
+impl<T> Render<T> for NullRender {
+    fn update(&mut self, _: T) {}
+}
+
+/// Creates a `NullRender`, the window it is given is dropped
+#[derive(Copy, Clone)]
+pub struct NullRenderFactory;
+
+#[cfg(feature="use_opencl")]
+impl<T> RenderFactory<T, NullRender> for NullRenderFactory {
+    fn init(self: Box<NullRenderFactory>,
+            _: &input::IOManager,
+            _: input::Window,
+            _: (i32, i32),
+            _: Option<Arc<opencl::hl::Device>>) -> NullRender {
+        NullRender
+    }
+}
+
+#[cfg(not(feature="use_opencl"))]
+impl<T> RenderFactory<T, NullRender> for NullRenderFactory {
+    fn init(self: Box<NullRenderFactory>,
+            _: &input::IOManager,
+            _: input::Window,
+            _: (i32, i32)) -> NullRender {
+        NullRender
+    }
+}
+
 /// Convert your game into
 pub trait IntoRender {
     /// The output render form of your gamestate
@@ -0,0 +1,234 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::num::Float;
+use std::f32;
+
+use cgmath::{Matrix, Matrix4};
+use cgmath::{Vector, Vector3, Vector4, EuclideanVector};
+use cgmath::{Point, Point3};
+use cgmath::{ortho, perspective, rad};
+
+use snowmew::Entity;
+use camera::{Camera, DrawMatrices};
+use cull::distance_order;
+
+/// The depth of the light's clipping volume, objects further then this
+/// from the center of a cascade will not cast a shadow into it.
+const SHADOW_DEPTH: f32 = 500.;
+
+/// The near clipping plane used when rendering from a point or spot light
+const LIGHT_NEAR: f32 = 0.05;
+
+/// The number of tiles on each side of the shadow atlas
+pub const ATLAS_SIDE: usize = 4;
+
+/// The total number of tiles in the shadow atlas
+pub const ATLAS_TILES: usize = ATLAS_SIDE * ATLAS_SIDE;
+
+#[derive(Copy)]
+/// A single slice of a cascaded shadow map
+pub struct Cascade {
+    /// The view space distance where this cascade ends
+    pub far: f32,
+    /// The matrices used to render the shadow map from the light's
+    /// point of view
+    pub matrices: DrawMatrices
+}
+
+/// Calculate the far distance of each of the `count` cascades that
+/// cover the range `near` to `far`. `lambda` blends between a uniform
+/// split (0.) and a logarithmic split (1.)
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..count+1).map(|i| {
+        let p = i as f32 / count as f32;
+        let log = near * (far / near).powf(p);
+        let uniform = near + (far - near) * p;
+        lambda * log + (1. - lambda) * uniform
+    }).collect()
+}
+
+/// Fit an orthographic light volume around each slice of the camera's
+/// frustum. `light` is the direction pointing towards the light and
+/// `resolution` is the size in texels of the shadow map. The volume is
+/// sized from a bounding sphere and snapped to texel increments so that
+/// the shadow edges do not shimmer when the camera moves or turns.
+/// The list ends early if the camera's frustum can not be found.
+pub fn cascades(camera: &Camera,
+                light: Vector3<f32>,
+                splits: &[f32],
+                resolution: u32) -> Vec<Cascade> {
+    let light = light.normalize();
+    let up = if light.y.abs() > 0.99 {
+        Vector3::new(0f32, 0., 1.)
+    } else {
+        Vector3::new(0f32, 1., 0.)
+    };
+
+    let mut near = camera.near();
+    let mut out = Vec::new();
+    for &far in splits.iter() {
+        // a degenerate camera has no frustum to fit the rest into
+        let corners = match camera.frustum_corners(near, far) {
+            Some(corners) => corners,
+            None => break
+        };
+        near = far;
+
+        let center = corners.iter()
+            .fold(Vector3::new(0f32, 0., 0.), |acc, p| acc.add_v(&p.to_vec()))
+            .div_s(corners.len() as f32);
+        let radius = corners.iter()
+            .map(|p| p.to_vec().sub_v(&center).length())
+            .fold(0f32, |a, b| a.max(b));
+        // round the radius so the volume does not change size each frame
+        let radius = (radius * 16.).ceil() / 16.;
+
+        let view = Matrix4::look_at(
+            &Point3::from_vec(&center.add_v(&light)),
+            &Point3::from_vec(&center),
+            &up
+        );
+        let mut proj = ortho(-radius, radius, -radius, radius, -SHADOW_DEPTH, SHADOW_DEPTH);
+
+        // snap the world origin to a texel in the shadow map
+        let half = resolution as f32 / 2.;
+        let origin = proj.mul_m(&view).mul_v(&Vector4::new(0f32, 0., 0., 1.));
+        let (x, y) = (origin.x * half, origin.y * half);
+        proj.w.x += (x.round() - x) / half;
+        proj.w.y += (y.round() - y) / half;
+
+        out.push(Cascade {
+            far: far,
+            matrices: DrawMatrices {
+                projection: proj,
+                view: view
+            }
+        });
+    }
+    out
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// The type of shadow a light casts into the atlas
+pub enum ShadowKind {
+    /// A single perspective shadow map
+    Spot,
+    /// A cube map stored as six tiles
+    Point
+}
+
+impl ShadowKind {
+    /// The number of atlas tiles this kind of shadow needs
+    pub fn tiles(&self) -> usize {
+        match *self {
+            ShadowKind::Spot => 1,
+            ShadowKind::Point => 6
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// A range of tiles in the shadow atlas that was given to a light
+pub struct AtlasSlot {
+    /// The light that owns the slot
+    pub light: Entity,
+    /// The first tile used by the light
+    pub tile: usize,
+    /// The kind of shadow stored in the slot
+    pub kind: ShadowKind
+}
+
+/// Assign atlas tiles to the shadowed lights. `lights` is a list of
+/// `(light, kind, distance from camera)`, the closest lights are given
+/// tiles first and a light with a NaN distance is given tiles last.
+/// Lights that do not fit into the atlas are not shadowed.
+pub fn allocate_atlas(lights: &[(Entity, ShadowKind, f32)]) -> Vec<AtlasSlot> {
+    let mut lights: Vec<(Entity, ShadowKind, f32)> = lights.iter().map(|&x| x).collect();
+    lights.sort_by(|&(_, _, a), &(_, _, b)| distance_order(a, b));
+
+    let mut next = 0;
+    let mut out = Vec::new();
+    for &(light, kind, _) in lights.iter() {
+        if next + kind.tiles() <= ATLAS_TILES {
+            out.push(AtlasSlot {
+                light: light,
+                tile: next,
+                kind: kind
+            });
+            next += kind.tiles();
+        }
+    }
+    out
+}
+
+/// Create a matrix that squeezes clip space into a single tile of the atlas
+pub fn tile_matrix(tile: usize) -> Matrix4<f32> {
+    let scale = 1. / ATLAS_SIDE as f32;
+    let (col, row) = ((tile % ATLAS_SIDE) as f32, (tile / ATLAS_SIDE) as f32);
+    Matrix4::new(
+        scale, 0.,    0., 0.,
+        0.,    scale, 0., 0.,
+        0.,    0.,    1., 0.,
+        (2. * col + 1.) * scale - 1., (2. * row + 1.) * scale - 1., 0., 1.
+    )
+}
+
+/// The pixel rectangle `(x, y, width, height)` of a tile in an atlas
+/// that is `resolution` pixels on a side.
+pub fn tile_rect(tile: usize, resolution: u32) -> (u16, u16, u16, u16) {
+    let size = resolution as usize / ATLAS_SIDE;
+    let (col, row) = (tile % ATLAS_SIDE, tile / ATLAS_SIDE);
+    ((col * size) as u16, (row * size) as u16, size as u16, size as u16)
+}
+
+fn up_for(dir: &Vector3<f32>) -> Vector3<f32> {
+    if dir.y.abs() > 0.99 {
+        Vector3::new(0f32, 0., 1.)
+    } else {
+        Vector3::new(0f32, 1., 0.)
+    }
+}
+
+/// Calculate the matrices needed to render a spot light's shadow map
+/// `angle` is half of the cone's width in radians.
+pub fn spot_matrices(position: Point3<f32>,
+                     direction: Vector3<f32>,
+                     angle: f32,
+                     range: f32) -> DrawMatrices {
+    let direction = direction.normalize();
+    DrawMatrices {
+        projection: perspective(rad(2. * angle), 1., LIGHT_NEAR, range),
+        view: Matrix4::look_at(&position, &position.add_v(&direction), &up_for(&direction))
+    }
+}
+
+/// Calculate the matrices for each face of a point light's cube map. The
+/// faces are ordered +X, -X, +Y, -Y, +Z, -Z.
+pub fn point_matrices(position: Point3<f32>, range: f32) -> [DrawMatrices; 6] {
+    let projection = perspective(rad(f32::consts::FRAC_PI_2), 1., LIGHT_NEAR, range);
+    let face = |&: dir: Vector3<f32>, up: Vector3<f32>| {
+        DrawMatrices {
+            projection: projection,
+            view: Matrix4::look_at(&position, &position.add_v(&dir), &up)
+        }
+    };
+
+    [face(Vector3::new( 1f32, 0., 0.), Vector3::new(0f32, -1., 0.)),
+     face(Vector3::new(-1f32, 0., 0.), Vector3::new(0f32, -1., 0.)),
+     face(Vector3::new(0f32,  1., 0.), Vector3::new(0f32, 0.,  1.)),
+     face(Vector3::new(0f32, -1., 0.), Vector3::new(0f32, 0., -1.)),
+     face(Vector3::new(0f32, 0.,  1.), Vector3::new(0f32, -1., 0.)),
+     face(Vector3::new(0f32, 0., -1.), Vector3::new(0f32, -1., 0.))]
+}
@@ -0,0 +1,234 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+use std::cmp;
+use std::num::Float;
+
+/// The number of steps a fixed step loop may run in one frame before
+/// it gives up on catching up
+pub const DEFAULT_MAX_CATCH_UP: usize = 5;
+
+/// How much a new frame time moves `FrameStats::average`
+const AVERAGE_WEIGHT: f64 = 0.1;
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+/// How the time of a frame is turned into game steps
+pub enum Timestep {
+    /// Every step is `step` seconds long. Time is collected until there
+    /// is enough for a step, a long frame runs several steps but never
+    /// more than `max_catch_up`, the rest of the time is dropped.
+    Fixed { step: f64, max_catch_up: usize },
+    /// One step per frame that is as long as the frame, at most `max_step`
+    Variable { max_step: f64 },
+    /// One step per frame as long as the frame takes less than `step`,
+    /// a longer frame is split into up to `max_catch_up` equal steps
+    /// that are no longer than `step`
+    Hybrid { step: f64, max_catch_up: usize }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+/// Configures the main loop, see `is_valid` for the steps it accepts
+pub struct LoopPolicy {
+    pub timestep: Timestep,
+    /// Game time passes this many times faster than real time, below one
+    /// is slow motion and zero pauses the game
+    pub time_scale: f64
+}
+
+impl LoopPolicy {
+    pub fn fixed(step: f64) -> LoopPolicy {
+        LoopPolicy {
+            timestep: Timestep::Fixed { step: step, max_catch_up: DEFAULT_MAX_CATCH_UP },
+            time_scale: 1.
+        }
+    }
+
+    pub fn variable(max_step: f64) -> LoopPolicy {
+        LoopPolicy {
+            timestep: Timestep::Variable { max_step: max_step },
+            time_scale: 1.
+        }
+    }
+
+    pub fn hybrid(step: f64) -> LoopPolicy {
+        LoopPolicy {
+            timestep: Timestep::Hybrid { step: step, max_catch_up: DEFAULT_MAX_CATCH_UP },
+            time_scale: 1.
+        }
+    }
+
+    /// true if the step lengths are finite and above zero, and a fixed
+    /// or hybrid loop may run at least one step a frame
+    pub fn is_valid(&self) -> bool {
+        match self.timestep {
+            Timestep::Fixed { step, max_catch_up } |
+            Timestep::Hybrid { step, max_catch_up } => {
+                step > 0. && step.is_finite() && max_catch_up > 0
+            }
+            Timestep::Variable { max_step } => max_step > 0. && max_step.is_finite()
+        }
+    }
+
+    /// How often a frame should start in seconds, frames that match the
+    /// step of a fixed or hybrid loop run one step each. A variable loop
+    /// has no period of its own.
+    pub fn frame_period(&self) -> Option<f64> {
+        match self.timestep {
+            Timestep::Fixed { step, .. } | Timestep::Hybrid { step, .. } => Some(step),
+            Timestep::Variable { .. } => None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+/// How long the frames are taking, all times are in seconds of real time
+pub struct FrameStats {
+    /// the number of frames so far
+    pub frames: u64,
+    pub last: f64,
+    /// a moving average that favours the recent frames
+    pub average: f64,
+    pub min: f64,
+    pub max: f64,
+    /// the number of game steps the last frame ran
+    pub steps: usize,
+    /// the game time that has passed
+    pub game_time: f64,
+    /// scaled time that was thrown away because the loop could not
+    /// keep up
+    pub dropped: f64,
+    /// how far the game is into the next fixed step between 0 and 1, a
+    /// render can use this to blend between the last two steps
+    pub alpha: f64
+}
+
+impl FrameStats {
+    pub fn new() -> FrameStats {
+        FrameStats {
+            frames: 0,
+            last: 0.,
+            average: 0.,
+            min: 0.,
+            max: 0.,
+            steps: 0,
+            game_time: 0.,
+            dropped: 0.,
+            alpha: 0.
+        }
+    }
+
+    /// The frames per second from the average frame time
+    pub fn fps(&self) -> f64 {
+        if self.average > 0. { 1. / self.average } else { 0. }
+    }
+
+    fn frame(&mut self, elapsed: f64) {
+        if self.frames == 0 {
+            self.average = elapsed;
+            self.min = elapsed;
+            self.max = elapsed;
+        } else {
+            self.average += (elapsed - self.average) * AVERAGE_WEIGHT;
+            self.min = self.min.min(elapsed);
+            self.max = self.max.max(elapsed);
+        }
+        self.frames += 1;
+        self.last = elapsed;
+    }
+}
+
+/// Turns the real time of each frame into game steps
+pub struct Clock {
+    policy: LoopPolicy,
+    accumulator: f64,
+    stats: FrameStats
+}
+
+impl Clock {
+    /// Panics if the policy is not valid, see `LoopPolicy::is_valid`
+    pub fn new(policy: LoopPolicy) -> Clock {
+        assert!(policy.is_valid(), "a loop policy needs a finite step above zero and a catch up of at least one");
+        Clock {
+            policy: policy,
+            accumulator: 0.,
+            stats: FrameStats::new()
+        }
+    }
+
+    pub fn policy(&self) -> LoopPolicy { self.policy }
+
+    pub fn stats(&self) -> FrameStats { self.stats }
+
+    pub fn time_scale(&self) -> f64 { self.policy.time_scale }
+
+    /// Negative scales are treated as a pause
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.policy.time_scale = scale.max(0.);
+    }
+
+    pub fn is_paused(&self) -> bool { self.policy.time_scale == 0. }
+
+    /// Called once per frame with the seconds since the last frame,
+    /// returns the length of each game step to run this frame. While
+    /// paused a single step of zero is returned, so the game still runs
+    /// each frame and can resume itself.
+    pub fn advance(&mut self, elapsed: f64) -> Vec<f64> {
+        let elapsed = elapsed.max(0.);
+        self.stats.frame(elapsed);
+        // alpha is left alone so a render blends the same way until
+        // the game resumes
+        if self.is_paused() {
+            self.stats.steps = 1;
+            return vec![0.];
+        }
+
+        let scaled = elapsed * self.policy.time_scale;
+        // only a fixed step loop carries time over to the next frame
+        self.stats.alpha = 0.;
+
+        let steps: Vec<f64> = match self.policy.timestep {
+            Timestep::Fixed { step, max_catch_up } => {
+                self.accumulator += scaled;
+                let due = (self.accumulator / step).floor() as usize;
+                self.accumulator -= due as f64 * step;
+                if due > max_catch_up {
+                    self.stats.dropped += (due - max_catch_up) as f64 * step;
+                }
+                self.stats.alpha = self.accumulator / step;
+                (0..cmp::min(due, max_catch_up)).map(|_| step).collect()
+            }
+            Timestep::Variable { max_step } => {
+                if scaled > max_step {
+                    self.stats.dropped += scaled - max_step;
+                }
+                if scaled > 0. { vec![scaled.min(max_step)] } else { Vec::new() }
+            }
+            Timestep::Hybrid { step, max_catch_up } => {
+                let count = (scaled / step).ceil() as usize;
+                if count == 0 {
+                    Vec::new()
+                } else if count > max_catch_up {
+                    self.stats.dropped += scaled - max_catch_up as f64 * step;
+                    (0..max_catch_up).map(|_| step).collect()
+                } else {
+                    (0..count).map(|_| scaled / count as f64).collect()
+                }
+            }
+        };
+
+        self.stats.steps = steps.len();
+        self.stats.game_time += steps.iter().fold(0., |sum, dt| sum + *dt);
+        steps
+    }
+}
@@ -12,8 +12,14 @@
 //   See the License for the specific language governing permissions and
 //   limitations under the License.
 
+#![feature(core)]
+
 extern crate "rustc-serialize" as rustc_serialize;
 
+pub use clock::{Clock, LoopPolicy, Timestep, FrameStats, DEFAULT_MAX_CATCH_UP};
+
+mod clock;
+
 #[derive(Copy, Clone, RustcEncodable, RustcDecodable)]
 /// Determines when the timer should fire
 pub enum Phase {
@@ -32,6 +32,7 @@ extern crate "snowmew-input-integrator" as _input_integrator;
 #[cfg(feature="use_opencl")]
 extern crate opencl;
 extern crate glfw;
+extern crate time;
 
 pub use _core::table;
 pub use _core::common::Entity as Entity;
@@ -41,11 +42,17 @@ pub mod render {
     pub use _render::{
         RenderData,
         Renderable,
-        IntoRender
+        Skybox,
+        View,
+        IntoRender,
+        RenderSettings,
+        MAX_CASCADES
     };
     pub use _render::{
         RenderFactory,
-        Render
+        Render,
+        NullRender,
+        NullRenderFactory
     };
     pub use _input::DisplayConfig;
     pub use _render::camera::{
@@ -64,6 +71,9 @@ pub mod graphics {
         Light,
         material,
         Material,
+        pbr,
+        PbrMaterial,
+        Environment,
         texture,
         Texture,
         VertexBuffer,
@@ -115,7 +125,12 @@ pub mod input {
     pub use _input_integrator::{
         InputIntegrator,
         InputIntegratorGameData,
-        InputIntegratorState
+        InputIntegratorState,
+        ActionMap,
+        ActionKind,
+        Binding,
+        Input,
+        ConfigError
     };
     pub use _input_integrator::input_integrator as integrator;
 }
@@ -123,7 +138,10 @@ pub mod input {
 pub mod debug {
     pub use _debugger::{
         Debugger,
-        DebuggerGameData
+        DebuggerGameData,
+        Recorder,
+        Player,
+        PlaybackError
     };
 }
 
@@ -135,11 +153,14 @@ pub mod random {
 }
 
 pub mod loader {
-    pub use _loader::Obj;
+    pub use _loader::{Obj, Gltf, GltfScene, GltfCamera, Projection};
+    pub use _loader::{AssetManager, Watcher, Loaded, Progress};
+    pub use _loader::{load_texture, load_cube, load_equirect, Usage};
+    pub use _loader::{LoadError, LoadErrorKind, LoadResult, Mode};
 }
 
 pub mod timer {
-    pub use _timer::{Timer, Phase};
+    pub use _timer::{Timer, Phase, Clock, LoopPolicy, Timestep, FrameStats, DEFAULT_MAX_CATCH_UP};
 }
 
 #[cfg(feature="networking")]
@@ -152,6 +173,9 @@ pub mod config {
     use std::sync::Arc;
     use std::old_io::timer::Timer;
     use std::time::Duration;
+    use std::num::Float;
+    use std::cmp;
+    use time;
 
     #[cfg(feature="use_opencl")]
     use opencl::hl::{Device, get_platforms};
@@ -160,9 +184,11 @@ pub mod config {
     use glfw::{self, Glfw};
 
     use super::input::{Event, EventGroup, DisplayConfig};
+    use super::debug::Player;
     use super::core;
-    use super::render::{self, IntoRender};
+    use super::render::{self, IntoRender, Renderable};
     use super::input;
+    use super::timer::{Clock, LoopPolicy};
 
     #[cfg(feature="use_opencl")]
     fn get_cl() -> Option<Arc<Device>> {
@@ -207,8 +233,12 @@ pub mod config {
         #[cfg(feature="use_opencl")]
         /// Configure if the engine should use OpenCL
         pub use_opencl: bool,
-        /// Configure the cadence, the minimum peroid for a frame update
-        pub cadance_ms: i64
+        /// Configure the cadence, the minimum peroid for a frame update.
+        /// This is only used by a variable timestep, a fixed or hybrid
+        /// loop starts a frame every step.
+        pub cadance_ms: i64,
+        /// How the time of each frame is turned into `Cadance` events
+        pub timing: LoopPolicy
     }
 
     impl SnowmewConfig {
@@ -223,7 +253,8 @@ pub mod config {
                     window: true,
                 },
                 use_opencl: true,
-                cadance_ms: 15
+                cadance_ms: 15,
+                timing: LoopPolicy::fixed(0.015)
             }
         }
 
@@ -236,7 +267,8 @@ pub mod config {
                     hmd: true,
                     window: true,
                 },
-                cadance_ms: 15
+                cadance_ms: 15,
+                timing: LoopPolicy::fixed(0.015)
             }
         }
 
@@ -263,45 +295,288 @@ pub mod config {
         /// Start the game engine running based on the confirmation.
         pub fn start<GameData: Clone+input::GetIoState+IntoRender<RenderGameState=RD>,
                      Game: core::Game<GameData, Event>,
-                     RD: Clone,
+                     RD: Clone+Renderable+input::GetIoState,
                      R: render::Render<RD>,
-                     RF: render::RenderFactory<RD, R>>
+                     RF: render::RenderFactory<RD, R>+Clone>
                      (self,
                       render: Box<RF>,
-                      mut game: Game,
-                      mut gd: GameData) {
-            let mut im = input::IOManager::new(setup_glfw());
+                      game: Game,
+                      gd: GameData) {
+            self.run(input::IOManager::new(setup_glfw()), render, game, gd, None);
+        }
+
+        /// Start the game engine like `start` but with the windows and
+        /// input of `im`. With a `NullBackend` the engine runs without a
+        /// display until the backend's script is played, the game data
+        /// of the last frame is returned.
+        pub fn start_with<GameData: Clone+input::GetIoState+IntoRender<RenderGameState=RD>,
+                          Game: core::Game<GameData, Event>,
+                          RD: Clone+Renderable+input::GetIoState,
+                          R: render::Render<RD>,
+                          RF: render::RenderFactory<RD, R>+Clone>
+                          (self,
+                           im: input::IOManager,
+                           render: Box<RF>,
+                           game: Game,
+                           gd: GameData) -> GameData {
+            self.run(im, render, game, gd, None)
+        }
 
+        /// Start the game engine like `start` but feed it the events of a
+        /// recording instead of the live input. Each recorded `Cadance`
+        /// is played as one frame, once the recording runs out the live
+        /// input takes over.
+        pub fn replay<GameData: Clone+input::GetIoState+IntoRender<RenderGameState=RD>,
+                      Game: core::Game<GameData, Event>,
+                      RD: Clone+Renderable+input::GetIoState,
+                      R: render::Render<RD>,
+                      RF: render::RenderFactory<RD, R>+Clone>
+                      (self,
+                       render: Box<RF>,
+                       game: Game,
+                       gd: GameData,
+                       player: Player<Event>) {
+            self.run(input::IOManager::new(setup_glfw()), render, game, gd, Some(player));
+        }
+
+        fn run<GameData: Clone+input::GetIoState+IntoRender<RenderGameState=RD>,
+               Game: core::Game<GameData, Event>,
+               RD: Clone+Renderable+input::GetIoState,
+               R: render::Render<RD>,
+               RF: render::RenderFactory<RD, R>+Clone>
+               (self,
+                mut im: input::IOManager,
+                render: Box<RF>,
+                mut game: Game,
+                mut gd: GameData,
+                mut player: Option<Player<Event>>) -> GameData {
             // create display
             let display = match self.display.create_display(&mut im) {
-                None => return,
+                None => return gd,
                 Some(display) => display
             };
             let ih = display.handle();
+            im.read_io_state(&ih, gd.get_io_state_mut());
 
+            // each extra window gets a render of its own
+            let factory = (*render).clone();
             let mut render = self.config_render(&mut im, display, render);
+            let mut windows: Vec<(input::WindowConfig, input::InputHandle, R)> = Vec::new();
 
             let mut timer = Timer::new().unwrap();
-            let timer_port = timer.periodic(Duration::milliseconds(self.cadance_ms));
-            let candance_scale = self.cadance_ms as f64 / 1000.;
+            // the timer is rounded to the millisecond, the clock keeps the
+            // difference so no game time is lost
+            let period = self.timing.frame_period()
+                .map(|s| cmp::max((s * 1000.).round() as i64, 1))
+                .unwrap_or(self.cadance_ms);
+            let timer_port = timer.periodic(Duration::milliseconds(period));
+
+            let mut clock = Clock::new(self.timing);
+            gd.get_io_state_mut().time_scale = clock.time_scale();
+            let mut last = time::precise_time_s();
 
             while !im.should_close(&ih) {
                 timer_port.recv().ok().expect("failed to recv");
+                // the game may have changed the time scale on the last frame
+                let now = time::precise_time_s();
+                clock.set_time_scale(gd.get_io_state().time_scale);
+                let steps = clock.advance(now - last);
+                last = now;
+                gd.get_io_state_mut().frame_stats = clock.stats();
+
                 im.poll();
                 loop {
                     match im.next_event(&ih) {
-                        EventGroup::Game(evt) => gd = game.step(evt, gd),
+                        // live input is ignored while a recording is played
+                        EventGroup::Game(evt) => if player.is_none() { gd = game.step(evt, gd) },
                         EventGroup::Window(evt) => { gd.window_action(evt) },
                         EventGroup::Nop => break
                     }
                 }
+                // input from the extra windows goes to the game, their size
+                // and position do not belong to the main window
+                for &(_, handle, _) in windows.iter() {
+                    loop {
+                        match im.next_event(&handle) {
+                            EventGroup::Game(evt) => if player.is_none() { gd = game.step(evt, gd) },
+                            EventGroup::Window(_) => (),
+                            EventGroup::Nop => break
+                        }
+                    }
+                }
+                // windows that the player closed are taken out of the game
+                while let Some(i) = windows.iter().position(|&(_, ref h, _)| im.should_close(h)) {
+                    let (config, handle, render) = windows.remove(i);
+                    drop(render);
+                    im.close_window(&handle);
+                    gd.get_io_state_mut().windows.retain(|w| w.id != config.id);
+                }
 
-                gd = game.step(Event::Cadance(candance_scale), gd);
+                let cadance = match player {
+                    Some(ref mut player) => {
+                        let mut cadance = None;
+                        for evt in player {
+                            if let Event::Cadance(_) = evt {
+                                cadance = Some(evt);
+                                break;
+                            }
+                            gd = game.step(evt, gd);
+                        }
+                        cadance
+                    }
+                    None => None
+                };
+                match cadance {
+                    Some(cadance) => gd = game.step(cadance, gd),
+                    None => {
+                        player = None;
+                        for &dt in steps.iter() {
+                            gd = game.step(Event::Cadance(dt), gd);
+                        }
+                    }
+                }
 
-                let next_title = gd.get_io_state().window_title.clone();
-                im.set_title(&ih, next_title);
+                im.apply_io_state(&ih, gd.get_io_state());
+                self.update_windows(&mut im, &mut windows, gd.get_io_state_mut(), &factory);
                 render.update(gd.clone().into_render());
+
+                for &mut (ref config, ref handle, ref mut render) in windows.iter_mut() {
+                    let mut rd = gd.clone().into_render();
+                    if let Some(view) = rd.view(config.id) {
+                        rd.set_camera(view.camera);
+                        rd.set_scene(view.scene);
+                    }
+                    rd.get_io_state_mut().size = im.framebuffer_size(handle);
+                    render.update(rd);
+                }
+            }
+            gd
+        }
+
+        /// Open and close windows until they match `io.windows`, windows
+        /// are matched by their id. A window that can not be opened is
+        /// removed from `io.windows` so it is not tried again.
+        fn update_windows<RD: Clone,
+                          R: render::Render<RD>,
+                          RF: render::RenderFactory<RD, R>+Clone>
+                          (&self,
+                           im: &mut input::IOManager,
+                           windows: &mut Vec<(input::WindowConfig, input::InputHandle, R)>,
+                           io: &mut input::IoState,
+                           factory: &RF) {
+            while let Some(i) = windows.iter().position(|&(ref c, _, _)| {
+                !io.windows.iter().any(|w| w.id == c.id)
+            }) {
+                let (_, handle, render) = windows.remove(i);
+                drop(render);
+                im.close_window(&handle);
+            }
+
+            let mut failed = Vec::new();
+            for wanted in io.windows.iter() {
+                match windows.iter_mut().find(|&&mut (ref c, _, _)| c.id == wanted.id) {
+                    Some(&mut (ref mut config, ref handle, _)) => {
+                        if config != wanted {
+                            im.configure_window(handle, wanted);
+                            *config = wanted.clone();
+                        }
+                        im.set_vsync(handle, io.vsync);
+                        continue;
+                    }
+                    None => ()
+                }
+
+                let window = match im.open_window(wanted) {
+                    Some(window) => window,
+                    None => {
+                        failed.push(wanted.id);
+                        continue;
+                    }
+                };
+                let handle = window.handle();
+                im.set_vsync(&handle, io.vsync);
+                let render = self.config_render(im, window, Box::new(factory.clone()));
+                windows.push((wanted.clone(), handle, render));
+            }
+            io.windows.retain(|w| !failed.contains(&w.id));
+        }
+    }
+
+    #[derive(Copy)]
+    /// Runs a game without a display, render or input devices. This is
+    /// used by dedicated servers, simulations and tests.
+    pub struct Headless {
+        /// The minimum period of a tick, if not set the ticks are
+        /// run back to back
+        pub cadance_ms: Option<i64>,
+        /// The time in seconds that is sent with each `Cadance`
+        pub step: f64,
+        /// Stop after this many ticks, if not set the game runs until
+        /// the predicate given to `run` holds
+        pub ticks: Option<usize>
+    }
+
+    impl Headless {
+        /// Run as fast as possible with the same step as `SnowmewConfig`
+        pub fn new() -> Headless {
+            Headless {
+                cadance_ms: None,
+                step: 0.015,
+                ticks: None
+            }
+        }
+
+        /// Turn a script of one list of events per tick into the events
+        /// for `run`, each tick is ended with a `Cadance`
+        pub fn script(&self, frames: Vec<Vec<Event>>) -> Vec<Event> {
+            let mut events = Vec::new();
+            for frame in frames.into_iter() {
+                events.extend(frame.into_iter());
+                events.push(Event::Cadance(self.step));
+            }
+            events
+        }
+
+        /// Step the game until `until` holds for the game data or
+        /// `ticks` have been run, and return the game data of the last
+        /// tick. A tick passes the events up to and including the next
+        /// `Cadance` to the game, so a recording is played with the steps
+        /// it was recorded with. Once `events` runs out each tick is only
+        /// a `Cadance` of `step`.
+        pub fn run<GameData,
+                   Game: core::Game<GameData, Event>,
+                   E: Iterator<Item=Event>,
+                   F: FnMut(&GameData) -> bool>
+                   (&self,
+                    mut game: Game,
+                    mut gd: GameData,
+                    mut events: E,
+                    mut until: F) -> GameData {
+            let mut timer = Timer::new().unwrap();
+            let timer_port = self.cadance_ms.map(|ms| timer.periodic(Duration::milliseconds(ms)));
+
+            let mut tick = 0;
+            while self.ticks.map(|t| tick < t).unwrap_or(true) {
+                if let Some(ref port) = timer_port {
+                    port.recv().ok().expect("failed to recv");
+                }
+
+                let mut cadance = None;
+                for evt in events.by_ref() {
+                    if let Event::Cadance(_) = evt {
+                        cadance = Some(evt);
+                        break;
+                    }
+                    gd = game.step(evt, gd);
+                }
+                gd = game.step(cadance.unwrap_or(Event::Cadance(self.step)), gd);
+                tick += 1;
+
+                if until(&gd) {
+                    break;
+                }
             }
+            gd
         }
     }
 }
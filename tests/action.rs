@@ -0,0 +1,89 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-input" as input;
+extern crate "snowmew-input-integrator" as integrator;
+
+use snowmew::game::Game;
+use input::{Event, Button, Modifiers, Axis};
+use integrator::{input_integrator, InputIntegratorState, ActionMap, Binding};
+
+/// records what the actions were on each frame
+struct Record;
+
+impl Game<Vec<(bool, bool, f64)>, InputIntegratorState> for Record {
+    fn step(&mut self, state: InputIntegratorState, gd: Vec<(bool, bool, f64)>) -> Vec<(bool, bool, f64)> {
+        let mut gd = gd;
+        gd.push((state.action_active("save"), state.action_active("slow"), state.action_value("forward")));
+        gd
+    }
+}
+
+fn actions() -> ActionMap {
+    let mut map = ActionMap::new();
+    map.analog("forward");
+    map.bind("forward", Binding::button(Button::KeyboardW));
+    map.bind("forward", Binding::button(Button::KeyboardS).scale(-1.));
+    map.bind("forward", Binding::axis(0, Axis::LeftY).scale(-1.));
+    map.digital("slow");
+    map.bind("slow", Binding::button(Button::KeyboardS));
+    map.digital("save");
+    map.bind("save", Binding::button(Button::KeyboardS).with(Button::KeyboardLeftControl));
+    map
+}
+
+fn down(button: Button) -> Event {
+    Event::ButtonDown(button, Modifiers::none())
+}
+
+fn run(events: &[Event]) -> (bool, bool, f64) {
+    let (mut game, mut gd) = input_integrator(Record, Vec::new());
+    gd.set_actions(actions());
+    for &e in events.iter() {
+        gd = game.step(e, gd);
+    }
+    gd = game.step(Event::Cadance(0.1), gd);
+    *gd.inner.last().unwrap()
+}
+
+#[test]
+fn analog_sums_bindings() {
+    assert_eq!(run(&[]), (false, false, 0.));
+    assert_eq!(run(&[down(Button::KeyboardW)]), (false, false, 1.));
+    assert_eq!(run(&[Event::Axis(0, Axis::LeftY, -1.),
+                     down(Button::KeyboardW)]), (false, false, 1.));
+    assert_eq!(run(&[Event::Axis(0, Axis::LeftY, -0.25)]), (false, false, 0.25));
+}
+
+#[test]
+fn chord_shadows_plain_key() {
+    assert_eq!(run(&[down(Button::KeyboardS)]), (false, true, -1.));
+    assert_eq!(run(&[down(Button::KeyboardLeftControl),
+                     down(Button::KeyboardS)]), (true, false, 0.));
+}
+
+#[test]
+fn bindings_round_trip() {
+    let mut map = actions();
+    let saved = map.bindings_to_string();
+    map.unbind("forward");
+    map.unbind("save");
+    assert!(map.bindings("forward").is_empty());
+
+    map.bindings_from_str(&saved[]).unwrap();
+    assert_eq!(map.bindings("forward"), actions().bindings("forward"));
+    assert_eq!(map.bindings("save"), actions().bindings("save"));
+    assert!(map.bindings_from_str("{ not json").is_err());
+}
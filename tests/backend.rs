@@ -0,0 +1,183 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate snowmew;
+
+use snowmew::render::NullRenderFactory;
+use snowmew::input::{IoState, IOManager, Backend, NullBackend};
+use snowmew::input::{Event, EventGroup, WindowEvent, Button, Modifiers};
+use snowmew::config::SnowmewConfig;
+use snowmew::timer::LoopPolicy;
+use snowmew::core::Game;
+
+use fixture::Scene;
+
+mod fixture;
+
+#[derive(Clone)]
+struct Counts {
+    frames: usize,
+    paused: usize,
+    pressed: Vec<Button>
+}
+
+type GameData = Scene<Counts>;
+
+fn game_data() -> GameData {
+    Scene::with(Counts {
+        frames: 0,
+        paused: 0,
+        pressed: Vec::new()
+    })
+}
+
+struct Counter;
+
+impl Game<GameData, Event> for Counter {
+    fn step(&mut self, event: Event, mut gd: GameData) -> GameData {
+        match event {
+            Event::Cadance(_) => gd.game.frames += 1,
+            Event::ButtonDown(button, _) => gd.game.pressed.push(button),
+            _ => ()
+        }
+        gd
+    }
+}
+
+// pauses on P and resumes itself after two paused frames
+struct Pauser;
+
+impl Game<GameData, Event> for Pauser {
+    fn step(&mut self, event: Event, mut gd: GameData) -> GameData {
+        match event {
+            Event::ButtonDown(Button::KeyboardP, _) => gd.io.time_scale = 0.,
+            Event::Cadance(dt) => {
+                gd.game.frames += 1;
+                if dt == 0. {
+                    gd.game.paused += 1;
+                    if gd.game.paused == 2 {
+                        gd.io.time_scale = 1.;
+                    }
+                }
+            }
+            _ => ()
+        }
+        gd
+    }
+}
+
+fn down(button: Button) -> EventGroup {
+    EventGroup::Game(Event::ButtonDown(button, Modifiers::none()))
+}
+
+#[test]
+fn start_runs_the_script() {
+    let backend = NullBackend::new().script(vec![
+        vec![],
+        vec![down(Button::KeyboardA)],
+        vec![down(Button::KeyboardB)]
+    ]);
+
+    let mut sc = SnowmewConfig::new();
+    sc.cadance_ms = 1;
+    // one step per frame however short the frames are
+    sc.timing = LoopPolicy::variable(1.);
+    sc.display.resolution = Some((640, 480));
+
+    let gd = sc.start_with(IOManager::null(backend),
+                           Box::new(NullRenderFactory),
+                           Counter,
+                           game_data());
+
+    assert_eq!(gd.game.frames, 3);
+    assert_eq!(gd.game.pressed, vec![Button::KeyboardA, Button::KeyboardB]);
+    assert_eq!(gd.io.size, (640, 480));
+}
+
+#[test]
+fn paused_game_can_resume() {
+    let backend = NullBackend::new().script(vec![
+        vec![],
+        vec![down(Button::KeyboardP)],
+        vec![],
+        vec![],
+        vec![]
+    ]);
+
+    let mut sc = SnowmewConfig::new();
+    sc.cadance_ms = 1;
+    sc.timing = LoopPolicy::variable(1.);
+
+    let gd = sc.start_with(IOManager::null(backend),
+                           Box::new(NullRenderFactory),
+                           Pauser,
+                           game_data());
+
+    // the paused frames still step the game, with no time passing
+    assert_eq!(gd.game.frames, 5);
+    assert_eq!(gd.game.paused, 2);
+    assert_eq!(gd.io.time_scale, 1.);
+}
+
+#[test]
+fn one_frame_per_poll() {
+    let mut backend = NullBackend::new().script(vec![
+        vec![down(Button::KeyboardA)],
+        vec![],
+        vec![down(Button::KeyboardB)]
+    ]);
+    let window = backend.window((320, 240)).unwrap();
+    let handle = window.handle();
+
+    assert_eq!(backend.next_event(&handle), EventGroup::Window(WindowEvent::Size(320, 240)));
+    assert_eq!(backend.next_event(&handle), EventGroup::Nop);
+
+    backend.poll();
+    assert_eq!(backend.next_event(&handle), down(Button::KeyboardA));
+    assert_eq!(backend.next_event(&handle), EventGroup::Nop);
+    backend.poll();
+    assert_eq!(backend.next_event(&handle), EventGroup::Nop);
+    backend.poll();
+    assert_eq!(backend.frame(), 3);
+
+    // the window stays open until the last events are read
+    assert!(!backend.should_close(&handle));
+    assert_eq!(backend.next_event(&handle), down(Button::KeyboardB));
+    assert!(backend.should_close(&handle));
+}
+
+#[test]
+fn keep_open() {
+    let mut backend = NullBackend::new().keep_open();
+    let window = backend.window((320, 240)).unwrap();
+    let handle = window.handle();
+
+    backend.poll();
+    assert!(!backend.should_close(&handle));
+    backend.close(&handle);
+    assert!(backend.should_close(&handle));
+}
+
+#[test]
+fn apply_io_state() {
+    let mut backend = NullBackend::new();
+    let window = backend.window((320, 240)).unwrap();
+    let handle = window.handle();
+
+    let mut io = IoState::new();
+    io.window_title = "headless".to_string();
+    backend.apply_io_state(&handle, &io);
+    assert_eq!(backend.title(&handle), Some("headless"));
+    assert!(backend.get_proc_address("glClear").is_null());
+}
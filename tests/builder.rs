@@ -0,0 +1,101 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-graphics" as graphics;
+
+use std::num::Float;
+
+use graphics::builder::Mesh;
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1],
+     a[2] * b[0] - a[0] * b[2],
+     a[0] * b[1] - a[1] * b[0]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// every index is valid, normals and tangents have unit length and every
+/// triangle faces the same way as its vertex normals
+fn check(mesh: &Mesh) {
+    assert!(mesh.indices.len() > 0);
+    assert_eq!(mesh.indices.len() % 3, 0);
+    assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+
+    for v in mesh.vertices.iter() {
+        assert!((dot(v.normal, v.normal) - 1.).abs() < 0.001);
+        let t = [v.tangent[0], v.tangent[1], v.tangent[2]];
+        assert!((dot(t, t) - 1.).abs() < 0.001);
+        assert!(dot(v.normal, t).abs() < 0.001);
+        assert_eq!(v.tangent[3].abs(), 1.);
+    }
+
+    for tri in mesh.indices.chunks(3) {
+        let v: Vec<_> = tri.iter().map(|&i| mesh.vertices[i as usize]).collect();
+        let face = cross(sub(v[1].position, v[0].position), sub(v[2].position, v[0].position));
+        for v in v.iter() {
+            assert!(dot(face, v.normal) > 0.);
+        }
+    }
+}
+
+#[test]
+fn plane() {
+    let mesh = Mesh::plane(2., 4., 3, 5);
+    assert_eq!(mesh.vertices.len(), 4 * 6);
+    assert_eq!(mesh.indices.len(), 3 * 5 * 6);
+    check(&mesh);
+}
+
+#[test]
+fn spheres_point_out() {
+    for mesh in [Mesh::uv_sphere(2., 16, 8), Mesh::icosphere(2., 2)].iter() {
+        check(mesh);
+        for v in mesh.vertices.iter() {
+            assert!((dot(v.position, v.position).sqrt() - 2.).abs() < 0.001);
+            assert!(dot(v.position, v.normal) > 0.);
+        }
+    }
+}
+
+#[test]
+fn icosphere_triangles() {
+    assert_eq!(Mesh::icosphere(1., 0).indices.len(), 20 * 3);
+    assert_eq!(Mesh::icosphere(1., 2).indices.len(), 20 * 16 * 3);
+}
+
+#[test]
+fn solids() {
+    check(&Mesh::cylinder(1., 2., 12));
+    check(&Mesh::cone(1., 2., 12));
+    check(&Mesh::torus(2., 0.5, 24, 12));
+    check(&Mesh::capsule(0.5, 1., 12, 4));
+}
+
+#[test]
+fn heightfield_slope() {
+    // rises by one unit for every unit of x
+    let heights: Vec<f32> = (0..9).map(|i| (i % 3) as f32).collect();
+    let mesh = Mesh::heightfield(2., 2., 2, 2, &heights[]);
+    check(&mesh);
+    let n = mesh.vertices[4].normal;
+    let s = 1. / 2f32.sqrt();
+    assert!((n[0] + s).abs() < 0.001 && (n[1] - s).abs() < 0.001 && n[2].abs() < 0.001);
+}
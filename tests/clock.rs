@@ -0,0 +1,175 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-timer" as timer;
+
+use timer::{Clock, LoopPolicy, Timestep, DEFAULT_MAX_CATCH_UP};
+
+#[test]
+fn fixed_accumulates() {
+    let mut clock = Clock::new(LoopPolicy::fixed(0.25));
+
+    assert_eq!(clock.advance(0.125), vec![]);
+    assert_eq!(clock.stats().alpha, 0.5);
+    assert_eq!(clock.advance(0.25), vec![0.25]);
+    assert_eq!(clock.stats().alpha, 0.5);
+    assert_eq!(clock.advance(0.625), vec![0.25, 0.25, 0.25]);
+    assert_eq!(clock.stats().alpha, 0.);
+}
+
+#[test]
+fn fixed_catch_up_is_limited() {
+    let mut clock = Clock::new(LoopPolicy::fixed(0.25));
+
+    let steps = clock.advance(2.125);
+    assert_eq!(steps.len(), DEFAULT_MAX_CATCH_UP);
+    assert_eq!(clock.stats().dropped, 0.75);
+    // the time that was dropped is not made up later
+    assert_eq!(clock.advance(0.125), vec![0.25]);
+}
+
+#[test]
+fn variable() {
+    let mut clock = Clock::new(LoopPolicy::variable(0.5));
+
+    assert_eq!(clock.advance(0.25), vec![0.25]);
+    assert_eq!(clock.advance(1.), vec![0.5]);
+    assert_eq!(clock.stats().dropped, 0.5);
+    assert_eq!(clock.advance(0.), vec![]);
+}
+
+#[test]
+fn hybrid() {
+    let mut clock = Clock::new(LoopPolicy::hybrid(0.25));
+
+    assert_eq!(clock.advance(0.125), vec![0.125]);
+    assert_eq!(clock.advance(0.75), vec![0.25, 0.25, 0.25]);
+    assert_eq!(clock.advance(0.375), vec![0.1875, 0.1875]);
+
+    clock.advance(2.);
+    assert_eq!(clock.stats().steps, DEFAULT_MAX_CATCH_UP);
+    assert_eq!(clock.stats().dropped, 0.75);
+}
+
+#[test]
+fn catch_up_can_be_changed() {
+    let mut clock = Clock::new(LoopPolicy {
+        timestep: Timestep::Fixed { step: 0.25, max_catch_up: 2 },
+        time_scale: 1.
+    });
+
+    assert_eq!(clock.advance(1.), vec![0.25, 0.25]);
+    assert_eq!(clock.stats().dropped, 0.5);
+}
+
+#[test]
+fn time_scale() {
+    let mut clock = Clock::new(LoopPolicy::fixed(0.25));
+
+    clock.set_time_scale(0.5);
+    assert_eq!(clock.advance(0.5), vec![0.25]);
+
+    // a paused game still gets a step each frame so it can resume
+    clock.set_time_scale(0.);
+    assert!(clock.is_paused());
+    assert_eq!(clock.advance(1.), vec![0.]);
+    assert_eq!(clock.stats().dropped, 0.);
+
+    clock.set_time_scale(2.);
+    assert_eq!(clock.advance(0.25), vec![0.25, 0.25]);
+}
+
+#[test]
+fn pause_and_resume() {
+    let mut clock = Clock::new(LoopPolicy::fixed(0.25));
+
+    assert_eq!(clock.advance(0.375), vec![0.25]);
+    assert_eq!(clock.stats().alpha, 0.5);
+
+    clock.set_time_scale(0.);
+    assert_eq!(clock.advance(1.), vec![0.]);
+    assert_eq!(clock.advance(1.), vec![0.]);
+    assert_eq!(clock.stats().steps, 1);
+    assert_eq!(clock.stats().game_time, 0.25);
+    assert_eq!(clock.stats().alpha, 0.5);
+
+    // the time left from before the pause is carried on
+    clock.set_time_scale(1.);
+    assert_eq!(clock.advance(0.125), vec![0.25]);
+    assert_eq!(clock.stats().alpha, 0.);
+}
+
+#[test]
+fn frame_stats() {
+    let mut clock = Clock::new(LoopPolicy::variable(1.));
+
+    clock.advance(0.25);
+    assert_eq!(clock.stats().fps(), 4.);
+    clock.advance(0.5);
+    clock.advance(0.125);
+
+    let stats = clock.stats();
+    assert_eq!(stats.frames, 3);
+    assert_eq!(stats.last, 0.125);
+    assert_eq!(stats.min, 0.125);
+    assert_eq!(stats.max, 0.5);
+    assert_eq!(stats.steps, 1);
+    assert_eq!(stats.game_time, 0.875);
+}
+
+#[test]
+fn alpha_is_only_kept_by_fixed_steps() {
+    let mut clock = Clock::new(LoopPolicy::hybrid(0.25));
+    clock.advance(0.125);
+    assert_eq!(clock.stats().alpha, 0.);
+
+    let mut clock = Clock::new(LoopPolicy::fixed(0.25));
+    clock.advance(0.375);
+    assert_eq!(clock.stats().alpha, 0.5);
+}
+
+#[test]
+fn frame_period_follows_the_step() {
+    assert_eq!(LoopPolicy::fixed(0.25).frame_period(), Some(0.25));
+    assert_eq!(LoopPolicy::hybrid(0.5).frame_period(), Some(0.5));
+    assert_eq!(LoopPolicy::variable(0.5).frame_period(), None);
+    assert!(!LoopPolicy::fixed(0.).is_valid());
+}
+
+#[test]
+fn invalid_policies() {
+    assert!(LoopPolicy::fixed(0.25).is_valid());
+    assert!(LoopPolicy::variable(0.25).is_valid());
+
+    // a step that is not a number or never ends
+    let nan: f64 = 0. / 0.;
+    let inf: f64 = 1. / 0.;
+    assert!(!LoopPolicy::fixed(nan).is_valid());
+    assert!(!LoopPolicy::fixed(inf).is_valid());
+    assert!(!LoopPolicy::hybrid(inf).is_valid());
+    assert!(!LoopPolicy::variable(nan).is_valid());
+    assert!(!LoopPolicy::variable(inf).is_valid());
+
+    // a loop that may never run a step
+    let fixed = LoopPolicy {
+        timestep: Timestep::Fixed { step: 0.25, max_catch_up: 0 },
+        time_scale: 1.
+    };
+    let hybrid = LoopPolicy {
+        timestep: Timestep::Hybrid { step: 0.25, max_catch_up: 0 },
+        time_scale: 1.
+    };
+    assert!(!fixed.is_valid());
+    assert!(!hybrid.is_valid());
+}
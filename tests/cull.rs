@@ -0,0 +1,136 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-position" as position;
+extern crate "snowmew-graphics" as graphics;
+extern crate "snowmew-render" as render;
+extern crate cgmath;
+
+use snowmew::common::{Common, Entity};
+use position::Positions;
+use graphics::{Graphics, Geometry, Material, VertexBuffer};
+use graphics::geometry::VertexGeo;
+use render::Renderable;
+use render::camera::Camera;
+use render::cull::{visible, partition_transparent, sort_back_to_front, Frustum};
+use cgmath::{Matrix, Matrix4, Vector3, Point3};
+
+use fixture::Scene;
+
+mod fixture;
+
+/// create a scene with a unit triangle placed at each of the positions
+fn scene(positions: &[Vector3<f32>]) -> (Scene, Vec<Entity>) {
+    let mut db = fixture::scene();
+
+    let vb = db.new_vertex_buffer(VertexBuffer::new_position(
+        vec![VertexGeo { position: [-1., -1., 0.] },
+             VertexGeo { position: [ 1., -1., 0.] },
+             VertexGeo { position: [ 0.,  1., 0.] }],
+        vec![0, 1, 2]
+    ));
+    let geo = db.new_geometry(Geometry::triangles(vb, 0, 3));
+    let mat = db.new_material(Material::new());
+
+    let scene = db.new_scene();
+    db.set_scene(scene);
+
+    let ids = positions.iter().map(|p| {
+        let id = db.new_object(Some(scene.0));
+        db.set_draw(id, geo, mat);
+        db.set_displacement(id, *p);
+        id
+    }).collect();
+
+    (db, ids)
+}
+
+fn camera_frustum() -> Frustum {
+    let camera = Camera::new(100, 100, Matrix4::identity());
+    Frustum::from_matrix(&camera.projection_matrix().mul_m(&camera.view_matrix()))
+}
+
+#[test]
+fn cull_outside_camera() {
+    let (db, ids) = scene(&[Vector3::new(0f32, 0., -10.),
+                            Vector3::new(0f32, 0., 10.),
+                            Vector3::new(1000f32, 0., -10.)]);
+
+    assert_eq!(visible(&db, &[camera_frustum()]), vec![ids[0]]);
+}
+
+#[test]
+fn cull_keeps_partially_visible() {
+    // the center is just outside of the left plane, but the
+    // triangle still overlaps the view
+    let (db, ids) = scene(&[Vector3::new(-9f32, 0., -10.)]);
+
+    assert_eq!(visible(&db, &[camera_frustum()]), ids);
+}
+
+#[test]
+fn cull_union_of_frusta() {
+    let (db, ids) = scene(&[Vector3::new(0f32, 0., -10.),
+                            Vector3::new(0f32, 0., 10.)]);
+
+    let behind = Camera::new(100, 100, Matrix4::from_translation(&Vector3::new(0f32, 0., 20.)));
+    let behind = Frustum::from_matrix(&behind.projection_matrix().mul_m(&behind.view_matrix()));
+
+    assert_eq!(visible(&db, &[behind]), ids);
+    assert_eq!(visible(&db, &[camera_frustum(), behind]), ids);
+    assert_eq!(visible(&db, &[]), vec![]);
+}
+
+#[test]
+fn transparent_split() {
+    let (mut db, ids) = scene(&[Vector3::new(0f32, 0., -10.),
+                                Vector3::new(0f32, 0., -20.)]);
+
+    let mut glass = Material::new();
+    glass.set_d(0.5);
+    let glass = db.new_material(glass);
+    let geo = db.drawable(ids[1]).unwrap().geometry;
+    db.set_draw(ids[1], geo, glass);
+
+    let (opaque, transparent) = partition_transparent(&db, &ids[]);
+    assert_eq!(opaque, vec![ids[0]]);
+    assert_eq!(transparent, vec![ids[1]]);
+}
+
+#[test]
+fn sort_furthest_first() {
+    let (db, ids) = scene(&[Vector3::new(0f32, 0., -10.),
+                            Vector3::new(0f32, 0., -30.),
+                            Vector3::new(5f32, 0., -20.)]);
+
+    let mut sorted = ids.clone();
+    sort_back_to_front(&db, &mut sorted[], Point3::new(0f32, 0., 0.));
+    assert_eq!(sorted, vec![ids[1], ids[2], ids[0]]);
+
+    sort_back_to_front(&db, &mut sorted[], Point3::new(0f32, 0., -40.));
+    assert_eq!(sorted, vec![ids[0], ids[2], ids[1]]);
+}
+
+#[test]
+fn nan_positions_sort_last() {
+    let nan = 0f32 / 0f32;
+    let (db, ids) = scene(&[Vector3::new(0f32, nan, -10.),
+                            Vector3::new(0f32, 0., -10.),
+                            Vector3::new(0f32, 0., -30.)]);
+
+    let mut sorted = ids.clone();
+    sort_back_to_front(&db, &mut sorted[], Point3::new(0f32, 0., 0.));
+    assert_eq!(sorted, vec![ids[2], ids[1], ids[0]]);
+}
@@ -0,0 +1,92 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+//! The game data shared by the tests, include it with `mod fixture;`
+
+#![allow(dead_code)]
+
+extern crate "snowmew-core" as _core;
+extern crate "snowmew-position" as _position;
+extern crate "snowmew-graphics" as _graphics;
+extern crate "snowmew-render" as _render;
+extern crate "snowmew-input" as _input;
+
+use self::_core::common::{Common, CommonData, Entity, Delete};
+use self::_position::{Positions, PositionData};
+use self::_graphics::{Graphics, GraphicsData};
+use self::_render::{Renderable, RenderData};
+use self::_input::{GetIoState, IoState};
+
+/// Every kind of engine data, `game` holds whatever else a test
+/// needs to keep track of
+#[derive(Clone)]
+pub struct Scene<T=()> {
+    pub common: CommonData,
+    pub position: PositionData,
+    pub graphics: GraphicsData,
+    pub render: RenderData,
+    pub io: IoState,
+    pub game: T
+}
+
+impl<T> Scene<T> {
+    pub fn with(game: T) -> Scene<T> {
+        Scene {
+            common: CommonData::new(),
+            position: PositionData::new(),
+            graphics: GraphicsData::new(),
+            render: RenderData::new(),
+            io: IoState::new(),
+            game: game
+        }
+    }
+}
+
+/// an empty scene
+pub fn scene() -> Scene {
+    Scene::with(())
+}
+
+impl<T> Common for Scene<T> {
+    fn get_common<'a>(&'a self) -> &'a CommonData { &self.common }
+    fn get_common_mut<'a>(&'a mut self) -> &'a mut CommonData { &mut self.common }
+}
+
+impl<T> Positions for Scene<T> {
+    fn get_position<'a>(&'a self) -> &'a PositionData { &self.position }
+    fn get_position_mut<'a>(&'a mut self) -> &'a mut PositionData { &mut self.position }
+}
+
+impl<T> Graphics for Scene<T> {
+    fn get_graphics<'a>(&'a self) -> &'a GraphicsData { &self.graphics }
+    fn get_graphics_mut<'a>(&'a mut self) -> &'a mut GraphicsData { &mut self.graphics }
+}
+
+impl<T> Renderable for Scene<T> {
+    fn get_render_data<'a>(&'a self) -> &'a RenderData { &self.render }
+    fn get_render_data_mut<'a>(&'a mut self) -> &'a mut RenderData { &mut self.render }
+}
+
+impl<T> GetIoState for Scene<T> {
+    fn get_io_state<'a>(&'a self) -> &'a IoState { &self.io }
+    fn get_io_state_mut<'a>(&'a mut self) -> &'a mut IoState { &mut self.io }
+}
+
+impl<T> Delete for Scene<T> {
+    fn delete(&mut self, oid: Entity) -> bool {
+        self.graphics.delete(oid) |
+        self.position.delete(oid) |
+        self.common.delete(oid)
+    }
+}
@@ -0,0 +1,66 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-input" as input;
+
+use std::num::Float;
+
+use input::{Gamepad, Event, Button, Modifiers, Axis, dead_zone, stick_dead_zone};
+
+#[test]
+fn dead_zone_rescales() {
+    assert_eq!(dead_zone(0.1, 0.2), 0.);
+    assert_eq!(dead_zone(-0.2, 0.2), 0.);
+    assert_eq!(dead_zone(1., 0.2), 1.);
+    assert_eq!(dead_zone(-1., 0.2), -1.);
+    assert!((dead_zone(0.6, 0.2) - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn stick_dead_zone_is_round() {
+    assert_eq!(stick_dead_zone(0.1, 0.1, 0.2), (0., 0.));
+    let (x, y) = stick_dead_zone(0.5, 0.5, 0.2);
+    assert!((x - y).abs() < 1e-9 && x > 0.);
+}
+
+#[test]
+fn events_only_on_change() {
+    let mut pad = Gamepad::new(1);
+    let mut out = Vec::new();
+    let rest = [0., 0., -1., 0., 0., -1.];
+    pad.update(&rest, &[false; 11], 0.2, &mut out);
+    assert_eq!(out, vec![]);
+
+    let pushed = [1., 0., -1., 0., 0., 1.];
+    pad.update(&pushed, &[true], 0.2, &mut out);
+    assert_eq!(out.len(), 3);
+    assert!(out.contains(&Event::Axis(1, Axis::RightTrigger, 1.)));
+    assert!(out.contains(&Event::ButtonDown(Button::GamepadA(1), Modifiers::none())));
+
+    out.clear();
+    pad.update(&pushed, &[true], 0.2, &mut out);
+    assert_eq!(out, vec![]);
+
+    pad.disconnect(&mut out);
+    assert!(out.contains(&Event::Axis(1, Axis::RightTrigger, 0.)));
+    assert!(out.contains(&Event::ButtonUp(Button::GamepadA(1), Modifiers::none())));
+}
+
+#[test]
+fn hat_is_the_dpad() {
+    let mut pad = Gamepad::new(0);
+    let mut out = Vec::new();
+    pad.update(&[0., 0., -1., 0., 0., -1., -1., 0.], &[], 0.2, &mut out);
+    assert_eq!(out, vec![Event::ButtonDown(Button::GamepadLeft(0), Modifiers::none())]);
+}
@@ -0,0 +1,198 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-position" as position;
+extern crate "snowmew-graphics" as graphics;
+extern crate "snowmew-loader" as loader;
+extern crate cgmath;
+
+use std::old_io::{File, TempDir};
+use std::mem;
+
+use snowmew::common::Common;
+use position::Positions;
+use graphics::Graphics;
+use graphics::light::Light;
+use loader::{Gltf, GltfCamera, Projection, LoadErrorKind};
+use cgmath::{Matrix, Vector4};
+
+use fixture::scene;
+
+mod fixture;
+
+/// a single triangle followed by its u16 indices
+fn triangle() -> Vec<u8> {
+    let mut out = Vec::new();
+    for &f in [0f32, 0., 0., 1., 0., 0., 0., 1., 0.].iter() {
+        let bits: u32 = unsafe { mem::transmute(f) };
+        out.push_all(&le(bits)[]);
+    }
+    out.push_all(&[0, 0, 1, 0, 2, 0, 0, 0]);
+    out
+}
+
+fn le(v: u32) -> [u8; 4] {
+    [v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]
+}
+
+fn document(buffer: &str) -> String {
+    format!(r#"{{
+        "asset": {{"version": "2.0"}},
+        "scene": 0,
+        "scenes": [{{"nodes": [0]}}],
+        "nodes": [
+            {{"name": "root", "translation": [1, 0, 0], "children": [1, 2]}},
+            {{"name": "tri", "mesh": 0, "translation": [0, 2, 0]}},
+            {{"name": "lamp", "camera": 0,
+              "extensions": {{"KHR_lights_punctual": {{"light": 0}}}}}}
+        ],
+        "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}},
+                                     "indices": 1, "material": 0}}]}}],
+        "materials": [{{"pbrMetallicRoughness": {{"baseColorFactor": [1, 0, 0, 1],
+                                                  "metallicFactor": 0.25}}}}],
+        "cameras": [{{"type": "perspective",
+                      "perspective": {{"yfov": 1.0, "znear": 0.1}}}}],
+        "extensions": {{"KHR_lights_punctual": {{"lights": [{{"type": "point"}}]}}}},
+        "buffers": [{}],
+        "bufferViews": [{{"buffer": 0, "byteLength": 36}},
+                        {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}],
+        "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}},
+                      {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}]
+    }}"#, buffer)
+}
+
+fn check(gltf: &Gltf) {
+    let mut db = scene();
+    let imported = gltf.import(&mut db, None).ok().expect("import failed");
+
+    assert_eq!(imported.roots, vec![*imported.names.get("root").unwrap()]);
+    let tri = *imported.names.get("tri").unwrap();
+    let lamp = *imported.names.get("lamp").unwrap();
+
+    // the child is placed relative to its parent
+    let p = db.position(tri).mul_v(&Vector4::new(0f32, 0., 0., 1.));
+    assert_eq!((p.x, p.y, p.z), (1., 2., 0.));
+
+    let draw = db.drawable(tri).expect("triangle is not drawable");
+    let mat = db.pbr_material(draw.material).expect("material is not pbr");
+    assert_eq!(mat.base_color(), [1., 0., 0.]);
+    assert_eq!(mat.metallic(), 0.25);
+
+    assert_eq!(imported.cameras, vec![GltfCamera {
+        node: lamp,
+        projection: Projection::Perspective {
+            yfov: 1.,
+            aspect_ratio: None,
+            znear: 0.1,
+            zfar: None
+        }
+    }]);
+    assert_eq!(imported.lights.len(), 1);
+    match db.get_light(imported.lights[0]) {
+        Some(&Light::Point(_)) => (),
+        _ => panic!("expected a point light")
+    }
+    let p = db.position(imported.lights[0]).mul_v(&Vector4::new(0f32, 0., 0., 1.));
+    assert_eq!((p.x, p.y, p.z), (1., 0., 0.));
+}
+
+/// the document with the triangle embedded, `edit` can change it
+/// before it is loaded
+fn embedded<F: Fn(String) -> String>(edit: F) -> Gltf {
+    let mut data = String::from_str("data:application/octet-stream;base64,");
+    data.push_str("AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=");
+    let buffer = format!(r#"{{"byteLength": 44, "uri": "{}"}}"#, data);
+
+    let dir = TempDir::new("gltf").unwrap();
+    let path = dir.path().join("triangle.gltf");
+    File::create(&path).write_str(&edit(document(&buffer[]))[]).unwrap();
+
+    Gltf::load(&path).ok().expect("failed to load gltf")
+}
+
+#[test]
+fn gltf_embedded_buffer() {
+    check(&embedded(|doc| doc));
+}
+
+#[test]
+fn masked_material_keeps_cutoff() {
+    let masked = embedded(|doc| {
+        doc.replace(r#""baseColorFactor": [1, 0, 0, 1]"#, r#""baseColorFactor": [1, 0, 0, 0.75]"#)
+           .replace(r#""metallicFactor": 0.25}"#,
+                    r#""metallicFactor": 0.25}, "alphaMode": "MASK", "alphaCutoff": 0.25"#)
+    });
+    let mut db = scene();
+    let imported = masked.import(&mut db, None).ok().expect("import failed");
+    let draw = db.drawable(*imported.names.get("tri").unwrap()).unwrap();
+    let mat = db.pbr_material(draw.material).unwrap();
+    assert_eq!(mat.alpha_cutoff(), 0.25);
+    assert_eq!(mat.alpha(), 0.75);
+    assert!(!mat.is_transparent());
+
+    // an opaque material ignores the cutoff
+    let opaque = embedded(|doc| doc.replace(r#""metallicFactor": 0.25}"#,
+                                            r#""metallicFactor": 0.25}, "alphaCutoff": 0.25"#));
+    let mut db = scene();
+    let imported = opaque.import(&mut db, None).ok().expect("import failed");
+    let draw = db.drawable(*imported.names.get("tri").unwrap()).unwrap();
+    assert_eq!(db.pbr_material(draw.material).unwrap().alpha_cutoff(), 0.);
+}
+
+#[test]
+fn invalid_documents() {
+    let stretched = embedded(|doc| {
+        doc.replace(r#""translation": [0, 2, 0]"#, r#""translation": [0, 2, 0], "scale": [1, 2, 1]"#)
+    });
+    // vec2 positions are too short to read a vertex from
+    let flat = embedded(|doc| doc.replace(r#""count": 3, "type": "VEC3""#, r#""count": 3, "type": "VEC2""#));
+    let short = embedded(|doc| doc.replace(r#""translation": [1, 0, 0]"#, r#""translation": [1, 0]"#));
+
+    for gltf in [stretched, flat, short].iter() {
+        match gltf.import(&mut scene(), None) {
+            Err(e) => match e.kind {
+                LoadErrorKind::Invalid(_) => (),
+                _ => panic!("expected an invalid file")
+            },
+            Ok(_) => panic!("expected an error")
+        }
+    }
+}
+
+#[test]
+fn glb_binary_chunk() {
+    let mut json = document(r#"{"byteLength": 44}"#).into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let bin = triangle();
+
+    let mut glb = Vec::new();
+    glb.push_all(b"glTF");
+    glb.push_all(&le(2)[]);
+    glb.push_all(&le((12 + 8 + json.len() + 8 + bin.len()) as u32)[]);
+    glb.push_all(&le(json.len() as u32)[]);
+    glb.push_all(b"JSON");
+    glb.push_all(&json[]);
+    glb.push_all(&le(bin.len() as u32)[]);
+    glb.push_all(b"BIN\0");
+    glb.push_all(&bin[]);
+
+    let dir = TempDir::new("glb").unwrap();
+    let path = dir.path().join("triangle.glb");
+    File::create(&path).write_all(&glb[]).unwrap();
+
+    check(&Gltf::load(&path).ok().expect("failed to load glb"));
+}
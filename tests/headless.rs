@@ -0,0 +1,118 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate snowmew;
+
+use snowmew::config::Headless;
+use snowmew::core::Game;
+use snowmew::debug::Player;
+use snowmew::input::{Event, Button, Modifiers};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sim {
+    ticks: usize,
+    time: f64,
+    pressed: Vec<Button>
+}
+
+fn sim() -> Sim {
+    Sim {
+        ticks: 0,
+        time: 0.,
+        pressed: Vec::new()
+    }
+}
+
+struct Step;
+
+impl Game<Sim, Event> for Step {
+    fn step(&mut self, event: Event, mut gd: Sim) -> Sim {
+        match event {
+            Event::Cadance(dt) => {
+                gd.ticks += 1;
+                gd.time += dt;
+            }
+            Event::ButtonDown(button, _) => gd.pressed.push(button),
+            _ => ()
+        }
+        gd
+    }
+}
+
+fn down(button: Button) -> Event {
+    Event::ButtonDown(button, Modifiers::none())
+}
+
+#[test]
+fn runs_for_ticks() {
+    let mut headless = Headless::new();
+    headless.ticks = Some(10);
+    headless.step = 0.25;
+
+    let gd = headless.run(Step, sim(), Vec::new().into_iter(), |_| false);
+    assert_eq!(gd.ticks, 10);
+    assert_eq!(gd.time, 2.5);
+}
+
+#[test]
+fn runs_until() {
+    let gd = Headless::new().run(Step, sim(), Vec::new().into_iter(), |gd| gd.ticks == 7);
+    assert_eq!(gd.ticks, 7);
+}
+
+#[test]
+fn fixed_cadance() {
+    let mut headless = Headless::new();
+    headless.cadance_ms = Some(1);
+    headless.ticks = Some(3);
+
+    let gd = headless.run(Step, sim(), Vec::new().into_iter(), |_| false);
+    assert_eq!(gd.ticks, 3);
+}
+
+#[test]
+fn scripted_events() {
+    let mut headless = Headless::new();
+    headless.ticks = Some(4);
+
+    let script = headless.script(vec![
+        vec![down(Button::KeyboardA)],
+        vec![],
+        vec![down(Button::KeyboardB), down(Button::KeyboardC)]
+    ]);
+    assert_eq!(script.len(), 6);
+
+    let gd = headless.run(Step, sim(), script.into_iter(), |gd| gd.pressed.len() == 3);
+    assert_eq!(gd.ticks, 3);
+    assert_eq!(gd.pressed, vec![Button::KeyboardA, Button::KeyboardB, Button::KeyboardC]);
+}
+
+#[test]
+fn recorded_events() {
+    let player = Player::new(vec![
+        down(Button::KeyboardA),
+        Event::Cadance(0.5),
+        Event::Cadance(0.25)
+    ]);
+
+    let mut headless = Headless::new();
+    headless.ticks = Some(3);
+    headless.step = 1.;
+
+    // the recorded steps are used until the recording runs out
+    let gd = headless.run(Step, sim(), player, |_| false);
+    assert_eq!(gd.ticks, 3);
+    assert_eq!(gd.time, 1.75);
+    assert_eq!(gd.pressed, vec![Button::KeyboardA]);
+}
@@ -0,0 +1,371 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-graphics" as graphics;
+extern crate "snowmew-position" as position;
+extern crate "snowmew-loader" as loader;
+
+use std::old_io::{File, TempDir, timer};
+use std::time::Duration;
+
+use snowmew::common::{Common, Entity};
+use graphics::{Graphics, Format};
+use position::Positions;
+use loader::{Obj, AssetManager, Watcher, Loaded, LoadErrorKind, Mode, Usage};
+
+use fixture::{Scene, scene};
+
+mod fixture;
+
+/// write each `(name, contents)` pair into a new directory
+fn files(list: &[(&str, &str)]) -> TempDir {
+    let dir = TempDir::new("obj").unwrap();
+    for &(name, text) in list.iter() {
+        File::create(&dir.path().join(name)).write_str(text).unwrap();
+    }
+    dir
+}
+
+/// the drawable children of an imported object
+fn children(db: &Scene, id: Entity) -> Vec<Entity> {
+    db.child_iter(id).map(|c| c.collect()).unwrap_or(Vec::new())
+}
+
+const TRIANGLE: &'static str = "mtllib tri.mtl
+o tri
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+";
+
+/// a png with one red pixel
+const PIXEL: [u8; 70] = [
+    137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 1,
+    0, 0, 0, 1, 8, 6, 0, 0, 0, 31, 21, 196, 137, 0, 0, 0, 13, 73, 68, 65,
+    84, 120, 156, 99, 248, 207, 192, 240, 31, 0, 5, 0, 1, 255, 137, 153, 61, 29, 0, 0,
+    0, 0, 73, 69, 78, 68, 174, 66, 96, 130
+];
+
+#[test]
+fn missing_material_library() {
+    let dir = files(&[("tri.obj", TRIANGLE)]);
+    let path = dir.path().join("tri.obj");
+
+    let err = Obj::load(&path).err().expect("expected an error");
+    assert_eq!(err.path, path);
+    assert_eq!(err.line, Some(1));
+    match err.kind {
+        LoadErrorKind::Io(_) => (),
+        _ => panic!("expected an io error")
+    }
+
+    // the library and the material are both reported
+    let obj = Obj::load_with(&path, Mode::Lenient).ok().expect("lenient load failed");
+    assert_eq!(obj.warnings().len(), 2);
+    match obj.warnings()[1].kind {
+        LoadErrorKind::UnknownMaterial(ref m) => assert_eq!(&m[], "red"),
+        _ => panic!("expected an unknown material")
+    }
+    assert_eq!(obj.warnings()[1].line, Some(6));
+
+    let mut db = scene();
+    let ids = obj.import(&mut db);
+    let tri = children(&db, *ids.get("tri").unwrap());
+    let draw = db.drawable(tri[0]).expect("tri was not imported");
+    assert!(db.material(draw.material).is_some());
+}
+
+#[test]
+fn missing_texture() {
+    let dir = files(&[("tri.obj", TRIANGLE),
+                      ("tri.mtl", "newmtl red\nKd 1 0 0\nmap_Kd missing.png\n")]);
+    let path = dir.path().join("tri.obj");
+
+    let err = Obj::load(&path).err().expect("expected an error");
+    assert_eq!(err.path, dir.path().join("tri.mtl"));
+    assert_eq!(err.line, Some(3));
+
+    let obj = Obj::load_with(&path, Mode::Lenient).ok().expect("lenient load failed");
+    assert_eq!(obj.warnings().len(), 1);
+
+    let mut db = scene();
+    let ids = obj.import(&mut db);
+    let tri = children(&db, *ids.get("tri").unwrap());
+    let draw = db.drawable(tri[0]).expect("tri was not imported");
+    let mat = db.material(draw.material).unwrap();
+    let texture = db.get_texture(mat.map_kd().expect("no fallback texture")).unwrap();
+    assert_eq!((texture.width(), texture.height()), (8, 8));
+}
+
+#[test]
+fn map_name_with_spaces() {
+    let dir = files(&[("tri.obj", TRIANGLE),
+                      ("tri.mtl", "newmtl red\nKd 1 0 0\nmap_bump -bm 0.5 my bump.png\n")]);
+    let path = dir.path().join("tri.obj");
+
+    let err = Obj::load(&path).err().expect("expected an error");
+    assert_eq!(err.path, dir.path().join("tri.mtl"));
+    assert_eq!(err.line, Some(3));
+
+    File::create(&dir.path().join("my bump.png")).write(&PIXEL).unwrap();
+    assert!(Obj::load(&path).is_ok());
+}
+
+#[test]
+fn colour_maps_are_srgb() {
+    let dir = files(&[("tri.obj", TRIANGLE),
+                      ("tri.mtl", "newmtl red\nmap_Kd red.png\nmap_bump red.png\n")]);
+    File::create(&dir.path().join("red.png")).write(&PIXEL).unwrap();
+
+    let mut db = scene();
+    let ids = Obj::load(&dir.path().join("tri.obj")).ok().expect("load failed").import(&mut db);
+    let tri = children(&db, *ids.get("tri").unwrap());
+    let mat = db.material(db.drawable(tri[0]).unwrap().material).unwrap().clone();
+
+    // the same image is loaded once for each way it is used
+    let kd = db.get_texture(mat.map_kd().unwrap()).unwrap();
+    let bump = db.get_texture(mat.map_bump().unwrap()).unwrap();
+    assert_eq!(kd.format(), Format::SRGBA8);
+    assert_eq!(bump.format(), Format::RGBA8);
+}
+
+#[test]
+fn hdr_images_are_float() {
+    // a flat 2x1 rgbe image of 1.0 and 0.5
+    let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+    data.push_all(&[128, 128, 128, 129, 128, 128, 128, 128]);
+    let dir = files(&[]);
+    let path = dir.path().join("sky.hdr");
+    File::create(&path).write(&data[]).unwrap();
+
+    let texture = loader::load_texture(&path).ok().expect("load failed");
+    assert_eq!(texture.format(), Format::RGBA32F);
+    assert_eq!((texture.width(), texture.height()), (2, 1));
+    assert_eq!(texture.texel(0, 0, 0), 1.);
+    assert_eq!(texture.texel(1, 0, 2), 0.5);
+    assert_eq!(texture.texel(1, 0, 3), 1.);
+
+    File::create(&path).write(&data[..data.len() - 1]).unwrap();
+    assert!(loader::load_texture(&path).is_err());
+}
+
+#[test]
+fn shared_textures_are_reported() {
+    let dir = files(&[("tri.obj", TRIANGLE),
+                      ("tri.mtl", "newmtl red\nmap_Kd red.png\nnewmtl blue\nmap_Kd red.png\n")]);
+    File::create(&dir.path().join("red.png")).write(&PIXEL).unwrap();
+
+    // the second material finds the texture already loaded
+    let mut reports = Vec::new();
+    Obj::load_reporting(&dir.path().join("tri.obj"), Mode::Strict,
+                        &mut |done, total| reports.push((done, total))).ok().expect("load failed");
+    let (done, total) = *reports.last().unwrap();
+    assert_eq!(done, total);
+    assert_eq!(total, 4);
+    assert!(reports.windows(2).all(|w| w[1].0 <= w[0].0 + 1));
+}
+
+#[test]
+fn groups_share_a_buffer() {
+    let dir = files(&[("quad.obj", "mtllib quad.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+g lower
+usemtl red
+f 1 2 3
+g upper
+usemtl blue
+f 1 3 4
+"),
+                      ("quad.mtl", "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n")]);
+
+    let obj = Obj::load(&dir.path().join("quad.obj")).ok().expect("load failed");
+    let mut db = scene();
+    let ids = obj.import(&mut db);
+
+    let quad = *ids.get("quad").unwrap();
+    assert!(db.drawable(quad).is_none());
+
+    let groups = children(&db, quad);
+    assert_eq!(groups.len(), 2);
+
+    let draws: Vec<_> = groups.iter().map(|&g| db.drawable(g).unwrap().clone()).collect();
+    let a = db.geometry(draws[0].geometry).unwrap().clone();
+    let b = db.geometry(draws[1].geometry).unwrap().clone();
+    assert_eq!(a.vb, b.vb);
+    assert_eq!((a.offset, a.count), (0, 3));
+    assert_eq!((b.offset, b.count), (3, 3));
+
+    let red = db.material(draws[0].material).unwrap().kd();
+    let blue = db.material(draws[1].material).unwrap().kd();
+    assert_eq!((red, blue), ([1., 0., 0.], [0., 0., 1.]));
+}
+
+#[test]
+fn groups_with_one_material_are_merged() {
+    let dir = files(&[("quad.obj", "mtllib quad.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+g lower
+usemtl red
+f 1 2 3
+g middle
+usemtl blue
+f 2 3 4
+g upper
+usemtl red
+f 1 3 4
+"),
+                      ("quad.mtl", "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n")]);
+
+    let obj = Obj::load(&dir.path().join("quad.obj")).ok().expect("load failed");
+    let mut db = scene();
+    let ids = obj.import(&mut db);
+
+    let groups = children(&db, *ids.get("quad").unwrap());
+    assert_eq!(groups.len(), 2);
+
+    let draws: Vec<_> = groups.iter().map(|&g| db.drawable(g).unwrap().clone()).collect();
+    let red = db.geometry(draws[0].geometry).unwrap().clone();
+    let blue = db.geometry(draws[1].geometry).unwrap().clone();
+    assert_eq!((red.offset, red.count), (0, 6));
+    assert_eq!((blue.offset, blue.count), (6, 3));
+    assert_eq!(db.material(draws[0].material).unwrap().kd(), [1., 0., 0.]);
+}
+
+#[test]
+fn manager_shares_and_frees() {
+    let object = |name: &str| format!("mtllib shared.mtl\no {}\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n", name);
+    let (a, b) = (object("a"), object("b"));
+    let dir = files(&[("a.obj", &a[]), ("b.obj", &b[]),
+                      ("shared.mtl", "newmtl red\nKd 1 0 0\n")]);
+    let (a, b) = (dir.path().join("a.obj"), dir.path().join("b.obj"));
+
+    let mut db = scene();
+    let mut assets = AssetManager::new();
+
+    let first = assets.load_obj(&mut db, &a).ok().unwrap();
+    let again = assets.load_obj(&mut db, &a).ok().unwrap();
+    assert_eq!(first, again);
+    assert_eq!(assets.references(&a), 2);
+
+    let other = assets.load_obj(&mut db, &b).ok().unwrap();
+    let draw_a = db.drawable(children(&db, *first.get("a").unwrap())[0]).unwrap().clone();
+    let draw_b = db.drawable(children(&db, *other.get("b").unwrap())[0]).unwrap().clone();
+    assert_eq!(draw_a.material, draw_b.material);
+    let vb = db.geometry(draw_a.geometry).unwrap().vb;
+
+    // the model stays until its last user is gone
+    assert!(assets.release_obj(&mut db, &a));
+    assert!(db.geometry(draw_a.geometry).is_some());
+    assert!(assets.release_obj(&mut db, &a));
+    assert!(db.geometry(draw_a.geometry).is_none());
+    assert!(db.vertex_buffer(vb).is_none());
+    assert!(!assets.release_obj(&mut db, &a));
+
+    // the material is still used by b
+    assert!(db.material(draw_b.material).is_some());
+    assert!(assets.release_obj(&mut db, &b));
+    assert!(db.material(draw_b.material).is_none());
+}
+
+#[test]
+fn manager_finds_colour_maps() {
+    let dir = files(&[("tri.obj", TRIANGLE), ("tri.mtl", "newmtl red\nmap_Kd red.png\n")]);
+    let (obj, png) = (dir.path().join("tri.obj"), dir.path().join("red.png"));
+    File::create(&png).write(&PIXEL).unwrap();
+
+    let mut db = scene();
+    let mut assets = AssetManager::new();
+    let tri = *assets.load_obj(&mut db, &obj).ok().unwrap().get("tri").unwrap();
+    let draw = db.drawable(children(&db, tri)[0]).unwrap().clone();
+    let kd = db.material(draw.material).unwrap().map_kd();
+
+    assert_eq!(assets.references(&png), 1);
+    assert_eq!(assets.texture(&png, Usage::Color), kd);
+    assert!(kd.is_some());
+    assert_eq!(assets.texture(&png, Usage::Linear), None);
+}
+
+#[test]
+fn reload_keeps_entities() {
+    let dir = files(&[("tri.obj", TRIANGLE), ("tri.mtl", "newmtl red\nKd 1 0 0\n")]);
+    let (obj, mtl) = (dir.path().join("tri.obj"), dir.path().join("tri.mtl"));
+
+    let mut db = scene();
+    let mut assets = AssetManager::new();
+    assets.watch(Watcher::polling());
+    let tri = *assets.load_obj(&mut db, &obj).ok().unwrap().get("tri").unwrap();
+    let child = children(&db, tri)[0];
+    let draw = db.drawable(child).unwrap().clone();
+    let revision = db.revision(draw.material);
+
+    // the stamp includes the size, so a longer file is always seen
+    File::create(&mtl).write_str("newmtl red\nKd 0.5 0 0\n").unwrap();
+    assert!(assets.update(&mut db).is_empty());
+    assert_eq!(children(&db, tri), vec![child]);
+    assert_eq!(db.drawable(child).unwrap().material, draw.material);
+    assert_eq!(db.material(draw.material).unwrap().kd(), [0.5, 0., 0.]);
+    assert!(db.revision(draw.material) != revision);
+
+    // a broken file keeps the old contents
+    File::create(&obj).write_str("mtllib tri.mtl\no tri\nusemtl blue\n").unwrap();
+    assert_eq!(assets.update(&mut db).len(), 1);
+    assert_eq!(children(&db, tri), vec![child]);
+
+    File::create(&obj).write_str("mtllib tri.mtl\no tri\nv 0 0 0\nv 2 0 0\nv 0 2 0\nv 2 2 0\nusemtl red\nf 1 2 3\nf 2 4 3\n").unwrap();
+    assert!(assets.reload(&mut db, &obj).ok().unwrap());
+    assert_eq!(children(&db, tri), vec![child]);
+    let geo = db.geometry(draw.geometry).unwrap().clone();
+    assert_eq!(geo.count, 6);
+}
+
+#[test]
+fn background_loading() {
+    let dir = files(&[("tri.obj", TRIANGLE), ("tri.mtl", "newmtl red\nKd 1 0 0\n")]);
+    let path = dir.path().join("tri.obj");
+
+    let mut db = scene();
+    let mut assets = AssetManager::new();
+    assets.request_obj(&path);
+    assets.request_obj(&path);
+    assert_eq!(assets.progress().pending, 1);
+
+    let mut merged = Vec::new();
+    while merged.is_empty() {
+        merged = assets.merge(&mut db);
+        timer::sleep(Duration::milliseconds(1));
+    }
+    assert!(assets.progress().is_finished());
+    assert_eq!(assets.references(&path), 2);
+
+    let (ref p, ref loaded) = merged[0];
+    assert_eq!(*p, path);
+    match *loaded {
+        Ok(Loaded::Obj(ref ids)) => {
+            let tri = children(&db, *ids.get("tri").unwrap());
+            assert!(db.drawable(tri[0]).is_some());
+        }
+        _ => panic!("expected an obj")
+    }
+}
@@ -0,0 +1,88 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-graphics" as graphics;
+
+use std::num::Float;
+use std::iter::repeat;
+
+use graphics::{Environment, Texture, Format, PbrMaterial};
+use graphics::pbr::equirect_direction;
+
+fn close(a: [f32; 3], b: [f32; 3], e: f32) -> bool {
+    (0..3).all(|i| (a[i] - b[i]).abs() < e)
+}
+
+#[test]
+fn uniform_environment() {
+    let env = Environment::uniform([0.5, 0.25, 1.]);
+    for n in [[0f32, 1., 0.], [1., 0., 0.], [0., 0., -1.]].iter() {
+        assert!(close(env.irradiance(*n), [0.5, 0.25, 1.], 0.0001));
+    }
+}
+
+#[test]
+fn white_image_is_uniform() {
+    let data: Vec<u8> = repeat(255u8).take(64 * 32 * 4).collect();
+    let env = Environment::from_equirect(&Texture::new(64, 32, 4, data), 1);
+
+    for n in [[0f32, 1., 0.], [0., -1., 0.], [1., 0., 0.], [0., 0., 1.]].iter() {
+        assert!(close(env.irradiance(*n), [1., 1., 1.], 0.02));
+    }
+}
+
+#[test]
+fn odd_images_do_not_panic() {
+    // a grey image lights every channel the same
+    let grey: Vec<u8> = repeat(255u8).take(64 * 32).collect();
+    let env = Environment::from_equirect(&Texture::new(64, 32, 1, grey), 1);
+    assert!(close(env.irradiance([0., 1., 0.]), [1., 1., 1.], 0.02));
+
+    let empty = Environment::from_equirect(&Texture::new(0, 0, 4, Vec::new()), 1);
+    assert!(close(empty.irradiance([0., 1., 0.]), [0., 0., 0.], 0.0001));
+
+    let packed = Texture::with_format(4, 4, Format::Etc2RGB, repeat(0u8).take(8).collect());
+    let env = Environment::from_equirect(&packed, 1);
+    assert!(close(env.irradiance([0., 1., 0.]), [0., 0., 0.], 0.0001));
+}
+
+#[test]
+fn bright_sky_lights_the_top() {
+    // top half white, bottom half black
+    let data: Vec<u8> = (0..32).flat_map(|y| {
+        repeat(if y < 16 { 255u8 } else { 0 }).take(64 * 4)
+    }).collect();
+    let env = Environment::from_equirect(&Texture::new(64, 32, 4, data), 1);
+
+    let up = env.irradiance([0., 1., 0.])[0];
+    let side = env.irradiance([1., 0., 0.])[0];
+    let down = env.irradiance([0., -1., 0.])[0];
+    assert!(up > side && side > down);
+    assert!((side - 0.5).abs() < 0.05);
+}
+
+#[test]
+fn equirect_directions() {
+    assert!(close(equirect_direction(0.5, 0.5), [0., 0., -1.], 0.0001));
+    assert!(close(equirect_direction(0.75, 0.5), [1., 0., 0.], 0.0001));
+    assert!(close(equirect_direction(0.1, 0.), [0., 1., 0.], 0.0001));
+}
+
+#[test]
+fn pbr_transparency() {
+    let mut mat = PbrMaterial::simple([1., 0., 0.], 1., 0.2);
+    assert!(!mat.is_transparent());
+    mat.set_alpha(0.5);
+    assert!(mat.is_transparent());
+}
@@ -0,0 +1,110 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-input" as input;
+extern crate "snowmew-debugger" as debugger;
+
+use std::old_io::{MemWriter, BufReader};
+use std::num::Float;
+
+use snowmew::game::Game;
+use input::{Event, Button, Modifiers, Axis, TouchPhase};
+use debugger::{Recorder, Player};
+
+/// keeps every event it was given
+struct Log;
+
+impl Game<Vec<Event>, Event> for Log {
+    fn step(&mut self, event: Event, gd: Vec<Event>) -> Vec<Event> {
+        let mut gd = gd;
+        gd.push(event);
+        gd
+    }
+}
+
+fn events() -> Vec<Event> {
+    vec![Event::ButtonDown(Button::KeyboardW, Modifiers::none()),
+         Event::Move(0, 10.5, -3.),
+         Event::Touch(4, TouchPhase::Begin, 1., 2.),
+         Event::Char('ü'),
+         Event::Axis(2, Axis::LeftTrigger, 0.75),
+         Event::Cadance(0.25),
+         Event::ButtonUp(Button::GamepadA(2), Modifiers { alt: true, .. Modifiers::none() }),
+         Event::Cadance(0.5)]
+}
+
+#[test]
+fn recording_plays_back() {
+    let mut recorder = Recorder::new(Log, MemWriter::new());
+    let mut gd = Vec::new();
+    for e in events().into_iter() {
+        gd = recorder.step(e, gd);
+    }
+    assert!(recorder.error().is_none());
+    assert_eq!(gd, events());
+
+    let (_, out) = recorder.into_inner();
+    let bytes = out.into_inner();
+    let player: Player<Event> = Player::read(&mut BufReader::new(&bytes[])).unwrap();
+    assert_eq!(player.events(), &events()[]);
+
+    let replayed = player.fold(Vec::new(), |gd, e| Log.step(e, gd));
+    assert_eq!(replayed, gd);
+}
+
+/// record `events` and read them back
+fn round_trip(events: Vec<Event>) -> Vec<Event> {
+    let mut recorder = Recorder::new(Log, MemWriter::new());
+    let mut gd = Vec::new();
+    for e in events.into_iter() {
+        gd = recorder.step(e, gd);
+    }
+    let (_, out) = recorder.into_inner();
+    let bytes = out.into_inner();
+    let player: Player<Event> = Player::read(&mut BufReader::new(&bytes[])).unwrap();
+    player.events().to_vec()
+}
+
+#[test]
+fn floats_are_exact() {
+    let cadance = 1.0 / 60.0;
+    let events = vec![Event::Cadance(cadance),
+                      Event::Move(0, 0.1, -1e-300),
+                      Event::Cadance(0f64 / 0f64)];
+    let read = round_trip(events.clone());
+    assert_eq!(&read[..2], &events[..2]);
+    match read[2] {
+        Event::Cadance(v) => assert!(v.is_nan()),
+        _ => panic!("expected a cadance")
+    }
+}
+
+#[test]
+fn number_floats_are_read() {
+    // recordings made before floats were written as bits
+    let text = "{\"variant\":\"Cadance\",\"fields\":[0.5]}\n";
+    let player: Player<Event> = Player::read(&mut BufReader::new(text.as_bytes())).unwrap();
+    assert_eq!(player.events(), &[Event::Cadance(0.5)][]);
+}
+
+#[test]
+fn bad_line_is_reported() {
+    let text = "{\"variant\":\"Cadance\",\"fields\":[0.1]}\n\nnot an event\n";
+    let result: Result<Player<Event>, _> = Player::read(&mut BufReader::new(text.as_bytes()));
+    match result {
+        Err(debugger::PlaybackError::Decode(line, _)) => assert_eq!(line, 3),
+        _ => panic!("expected a decode error")
+    }
+}
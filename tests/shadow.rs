@@ -0,0 +1,111 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-render" as render;
+extern crate cgmath;
+
+use std::num::Float;
+
+use render::{RenderSettings, MAX_CASCADES, MAX_SHADOW_RESOLUTION};
+use render::camera::Camera;
+use render::shadow::{cascade_splits, cascades, allocate_atlas, ShadowKind, ATLAS_TILES};
+use cgmath::{Matrix, Matrix4, Vector3, Vector4};
+
+#[test]
+fn splits_uniform() {
+    let splits = cascade_splits(20., 100., 4, 0.);
+    assert_eq!(splits, vec![40., 60., 80., 100.]);
+}
+
+#[test]
+fn splits_increase() {
+    let splits = cascade_splits(0.01, 150., 4, 0.75);
+    assert_eq!(splits.len(), 4);
+    for w in splits.windows(2) {
+        assert!(w[0] < w[1]);
+    }
+    assert!((splits[3] - 150.).abs() < 0.001);
+}
+
+#[test]
+fn cascades_are_texel_stable() {
+    let splits = cascade_splits(0.01, 50., 2, 0.5);
+    let light = Vector3::new(0.5f32, 1., 0.25);
+
+    // moving the camera by less then a texel should not move the shadow
+    // map in texel sized steps, the world origin should always land on
+    // the center of a texel
+    for &dx in [0f32, 0.001, 0.37, 5.2].iter() {
+        let m = Matrix4::from_translation(&Vector3::new(dx, 0., 0.));
+        let camera = Camera::new(10, 10, m);
+
+        for c in cascades(&camera, light, &splits[], 1024).iter() {
+            let o = c.matrices.projection.mul_m(&c.matrices.view)
+                     .mul_v(&Vector4::new(0f32, 0., 0., 1.));
+            let x = o.x * 512.;
+            let y = o.y * 512.;
+            assert!((x - x.round()).abs() < 0.01);
+            assert!((y - y.round()).abs() < 0.01);
+        }
+    }
+}
+
+#[test]
+fn atlas_prefers_closest_lights() {
+    let lights = [(1, ShadowKind::Spot, 10.),
+                  (2, ShadowKind::Point, 1.),
+                  (3, ShadowKind::Point, 2.),
+                  (4, ShadowKind::Point, 3.),
+                  (5, ShadowKind::Spot, 0.5)];
+
+    let slots = allocate_atlas(&lights[]);
+    let owners: Vec<u32> = slots.iter().map(|s| s.light).collect();
+
+    // the third point light does not fit, but the spot light behind it does
+    assert_eq!(owners, vec![5, 2, 3, 1]);
+    assert_eq!(slots[0].tile, 0);
+    assert_eq!(slots[1].tile, 1);
+    assert_eq!(slots[2].tile, 7);
+    assert_eq!(slots[3].tile, 13);
+    assert!(slots.iter().all(|s| s.tile + s.kind.tiles() <= ATLAS_TILES));
+}
+
+#[test]
+fn atlas_nan_distance_is_last() {
+    let nan: f32 = Float::nan();
+    let lights = vec![
+        (1, ShadowKind::Spot, nan),
+        (2, ShadowKind::Spot, 4.),
+        (3, ShadowKind::Spot, 1.)
+    ];
+    let owners: Vec<u32> = allocate_atlas(&lights[]).iter().map(|s| s.light).collect();
+    assert_eq!(owners, vec![3, 2, 1]);
+}
+
+#[test]
+fn settings_are_clamped() {
+    let mut settings = RenderSettings::new();
+    settings.shadow_cascades = 0;
+    settings.shadow_resolution = 100000;
+    let settings = settings.clamped();
+    assert_eq!(settings.shadow_cascades, 1);
+    assert_eq!(settings.shadow_resolution, MAX_SHADOW_RESOLUTION);
+
+    let mut settings = RenderSettings::new();
+    settings.shadow_cascades = MAX_CASCADES + 3;
+    settings.shadow_atlas_resolution = 0;
+    let settings = settings.clamped();
+    assert_eq!(settings.shadow_cascades, MAX_CASCADES);
+    assert_eq!(settings.shadow_atlas_resolution, 1);
+}
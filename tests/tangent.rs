@@ -0,0 +1,81 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-graphics" as graphics;
+
+use std::num::Float;
+
+use graphics::geometry::{VertexGeoTexNorm, generate_tangents};
+
+fn vert(position: [f32; 3], texture: [f32; 2]) -> VertexGeoTexNorm {
+    VertexGeoTexNorm {
+        position: position,
+        texture: texture,
+        normal: [0., 0., 1.]
+    }
+}
+
+fn close(a: [f32; 4], b: [f32; 4]) -> bool {
+    (0..4).all(|i| (a[i] - b[i]).abs() < 0.0001)
+}
+
+#[test]
+fn tangent_follows_u() {
+    let quad = [vert([0., 0., 0.], [0., 0.]),
+                vert([1., 0., 0.], [1., 0.]),
+                vert([1., 1., 0.], [1., 1.]),
+                vert([0., 1., 0.], [0., 1.])];
+    let index = [0, 1, 2, 0, 2, 3];
+
+    for v in generate_tangents(&quad, &index).iter() {
+        assert!(close(v.tangent, [1., 0., 0., 1.]));
+    }
+
+    // rotating the texture rotates the tangent
+    let quad = [vert([0., 0., 0.], [0., 0.]),
+                vert([1., 0., 0.], [0., 1.]),
+                vert([1., 1., 0.], [-1., 1.]),
+                vert([0., 1., 0.], [-1., 0.])];
+
+    for v in generate_tangents(&quad, &index).iter() {
+        assert!(close(v.tangent, [0., -1., 0., 1.]));
+    }
+}
+
+#[test]
+fn mirrored_texture_flips_bitangent() {
+    let quad = [vert([0., 0., 0.], [0., 1.]),
+                vert([1., 0., 0.], [1., 1.]),
+                vert([1., 1., 0.], [1., 0.]),
+                vert([0., 1., 0.], [0., 0.])];
+    let index = [0, 1, 2, 0, 2, 3];
+
+    for v in generate_tangents(&quad, &index).iter() {
+        assert!(close(v.tangent, [1., 0., 0., -1.]));
+    }
+}
+
+#[test]
+fn tangent_without_texture() {
+    let tri = [vert([0., 0., 0.], [0., 0.]),
+               vert([1., 0., 0.], [0., 0.]),
+               vert([0., 1., 0.], [0., 0.])];
+
+    for v in generate_tangents(&tri, &[0, 1, 2]).iter() {
+        let t = v.tangent;
+        let len = (t[0] * t[0] + t[1] * t[1] + t[2] * t[2]).sqrt();
+        assert!((len - 1.).abs() < 0.0001);
+        assert!(t[2].abs() < 0.0001);
+    }
+}
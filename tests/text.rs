@@ -0,0 +1,56 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-input" as input;
+extern crate "snowmew-input-integrator" as integrator;
+
+use snowmew::game::Game;
+use input::{Event, Button, Modifiers};
+use integrator::{input_integrator, InputIntegratorState};
+
+/// keeps the text and the repeat state of backspace for each frame
+struct Typing;
+
+impl Game<Vec<(String, bool, Modifiers)>, InputIntegratorState> for Typing {
+    fn step(&mut self, state: InputIntegratorState, gd: Vec<(String, bool, Modifiers)>)
+        -> Vec<(String, bool, Modifiers)> {
+        let mut gd = gd;
+        gd.push((state.text().to_string(),
+                 state.button_repeated(Button::KeyboardBackspace),
+                 state.modifiers()));
+        gd
+    }
+}
+
+#[test]
+fn text_is_collected_per_frame() {
+    let shift = Modifiers { shift: true, .. Modifiers::none() };
+    let (mut game, mut gd) = input_integrator(Typing, Vec::new());
+    for &e in [Event::ButtonDown(Button::KeyboardLeftShift, shift),
+               Event::Char('H'),
+               Event::Char('é'),
+               Event::Cadance(0.1),
+               Event::ButtonRepeat(Button::KeyboardBackspace, Modifiers::none()),
+               Event::Cadance(0.1),
+               Event::Cadance(0.1)].iter() {
+        gd = game.step(e, gd);
+    }
+
+    assert_eq!(gd.inner, vec![
+        ("Hé".to_string(), false, shift),
+        ("".to_string(), true, Modifiers::none()),
+        ("".to_string(), false, Modifiers::none())
+    ]);
+}
@@ -0,0 +1,88 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-graphics" as graphics;
+
+use std::iter::repeat;
+
+use graphics::{Texture, Format, Kind};
+use graphics::texture::cube_from_equirect;
+
+#[test]
+fn mipmaps_halve_to_one_pixel() {
+    let mut texture = Texture::new(5, 2, 1, vec![0, 255, 0, 255, 0,
+                                                 0, 255, 0, 255, 0]);
+    texture.generate_mipmaps();
+    assert_eq!(texture.levels(), 3);
+    assert_eq!(texture.level_size(1), (2, 1));
+    assert_eq!(texture.level_size(2), (1, 1));
+    assert_eq!(texture.level(1), &[128u8, 128][]);
+    assert_eq!(texture.level(2), &[128u8][]);
+}
+
+#[test]
+fn srgb_mipmaps_average_light() {
+    let mut texture = Texture::with_format(2, 1, Format::SRGBA8, vec![0, 0, 0, 0,
+                                                                      255, 255, 255, 255]);
+    texture.generate_mipmaps();
+    // half the light is brighter than half the encoded value, alpha is linear
+    assert_eq!(texture.level(1), &[188u8, 188, 188, 128][]);
+}
+
+#[test]
+fn float_and_compressed_sizes() {
+    let texture = Texture::with_format(4, 4, Format::RGBA32F, Vec::new());
+    assert_eq!(texture.depth(), 16);
+    assert_eq!(Format::Etc2RGB.size(5, 4), 16);
+    assert_eq!(Format::Etc2RGBA.size(4, 4), 16);
+}
+
+#[test]
+fn depth_formats() {
+    assert_eq!(Format::from_depth(3), Some(Format::RGB8));
+    assert_eq!(Format::from_depth(0), None);
+    assert_eq!(Format::from_depth(5), None);
+}
+
+#[test]
+fn cube_mipmaps_keep_faces_apart() {
+    let faces = (0..6).map(|i| repeat(i as u8 * 10).take(4).collect()).collect();
+    let mut cube = Texture::cube(2, Format::R8, faces);
+    cube.generate_mipmaps();
+    assert_eq!(cube.kind(), Kind::Cube);
+    assert_eq!(cube.layers(), 6);
+    assert_eq!(cube.levels(), 2);
+    for face in (0..6) {
+        assert_eq!(cube.layer(1, face), &[face as u8 * 10][]);
+    }
+}
+
+#[test]
+fn equirect_poles_map_to_y_faces() {
+    // red sky above the horizon and blue ground below it
+    let (w, h) = (16, 8);
+    let mut data = Vec::new();
+    for y in (0..h) {
+        for _ in (0..w) {
+            let pixel = if y < h / 2 { [255u8, 0, 0, 255] } else { [0, 0, 255, 255] };
+            data.extend(pixel.iter().map(|&c| c));
+        }
+    }
+    let cube = cube_from_equirect(&Texture::new(w, h, 4, data), 4);
+    assert_eq!(cube.kind(), Kind::Cube);
+
+    let center = ((2 * 4 + 2) * 4) as usize;
+    assert_eq!(&cube.layer(0, 2)[center..center + 4], &[255u8, 0, 0, 255][]);
+    assert_eq!(&cube.layer(0, 3)[center..center + 4], &[0u8, 0, 255, 255][]);
+}
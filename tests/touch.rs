@@ -0,0 +1,99 @@
+//   Copyright 2014 Colin Sherratt
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+
+extern crate "snowmew-core" as snowmew;
+extern crate "snowmew-input" as input;
+extern crate "snowmew-input-integrator" as integrator;
+
+use std::num::Float;
+
+use snowmew::game::Game;
+use input::{Event, Button, TouchPhase};
+use integrator::{input_integrator, InputIntegratorState, Pinch};
+
+/// keeps the state of every frame
+struct Frames;
+
+impl Game<Vec<InputIntegratorState>, InputIntegratorState> for Frames {
+    fn step(&mut self, state: InputIntegratorState, gd: Vec<InputIntegratorState>)
+        -> Vec<InputIntegratorState> {
+        let mut gd = gd;
+        gd.push(state);
+        gd
+    }
+}
+
+fn run(events: &[Event]) -> Vec<InputIntegratorState> {
+    let (mut game, mut gd) = input_integrator(Frames, Vec::new());
+    for &e in events.iter() {
+        gd = game.step(e, gd);
+    }
+    gd.inner
+}
+
+fn touch(id: u32, phase: TouchPhase, x: f64, y: f64) -> Event {
+    Event::Touch(id, phase, x, y)
+}
+
+#[test]
+fn touch_is_a_button() {
+    let frames = run(&[touch(1, TouchPhase::Begin, 5., 5.),
+                       Event::Cadance(0.1),
+                       touch(1, TouchPhase::End, 6., 5.),
+                       Event::Cadance(0.1),
+                       Event::Cadance(0.1)]);
+    assert!(frames[0].button_pressed(Button::Touch(1)));
+    assert_eq!(frames[0].pointer_position(1), Some((5., 5.)));
+    assert!(frames[1].button_released(Button::Touch(1)));
+    assert_eq!(frames[1].touches().len(), 1);
+    assert_eq!(frames[1].pointer_position(1), None);
+    assert!(frames[2].touches().is_empty());
+}
+
+#[test]
+fn mouse_is_pointer_zero() {
+    let frames = run(&[Event::Move(0, 3., 4.),
+                       Event::Move(2, 7., 8.),
+                       Event::Cadance(0.1)]);
+    assert_eq!(frames[0].mouse_position(), (3., 4.));
+    assert_eq!(frames[0].pointer_position(0), Some((3., 4.)));
+    assert_eq!(frames[0].pointer_position(2), Some((7., 8.)));
+}
+
+#[test]
+fn two_fingers_pinch_and_pan() {
+    let frames = run(&[touch(1, TouchPhase::Begin, 0., 0.),
+                       touch(2, TouchPhase::Begin, 10., 0.),
+                       Event::Cadance(0.1),
+                       touch(1, TouchPhase::Move, -5., 2.),
+                       touch(2, TouchPhase::Move, 15., 2.),
+                       Event::Cadance(0.1)]);
+
+    assert_eq!(frames[0].pan(), Some((0., 0.)));
+    assert_eq!(frames[1].pan(), Some((0., 2.)));
+    let Pinch { center, scale, rotation } = frames[1].pinch().unwrap();
+    assert_eq!(center, (5., 2.));
+    assert!((scale - 2.).abs() < 1e-9);
+    assert!(rotation.abs() < 1e-9);
+}
+
+#[test]
+fn one_finger_does_not_pinch() {
+    let frames = run(&[touch(1, TouchPhase::Begin, 0., 0.),
+                       Event::Cadance(0.1),
+                       touch(1, TouchPhase::Move, 4., 0.),
+                       Event::Cadance(0.1)]);
+    assert_eq!(frames[1].pinch(), None);
+    assert_eq!(frames[1].pan(), Some((4., 0.)));
+}